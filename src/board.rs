@@ -27,33 +27,52 @@ mod gamestate;
 mod history;
 mod playmove;
 mod utils;
+mod validate;
 mod zobrist;
 
+pub use self::fen::{FenError, FenResult};
+
 use self::{
-    defs::{Pieces, BB_SQUARES},
+    defs::{Pieces, Squares, Wing, BB_SQUARES},
     gamestate::GameState,
     history::History,
     zobrist::{ZobristKey, ZobristRandoms},
 };
 use crate::{
-    defs::{Bitboard, NrOf, Piece, Side, Sides, Square, EMPTY},
-    evaluation::psqt::{self, FLIP, PSQT_MG},
+    defs::MAX_PLY,
+    defs::{Bitboard, Castling, NrOf, Piece, Side, Sides, Square, EMPTY},
+    evaluation::{
+        nnue::{self, Accumulator, Network},
+        psqt::{self, FLIP, PSQT_MG},
+    },
     misc::bits,
 };
 use std::sync::Arc;
 
 // This file implements the engine's board representation; it is bit-board
-// based, with the least significant bit being A1.
+// based, with the least significant bit being A1. The fields are only
+// available within the library; other crates use the functions below, so
+// the board can't be put into an inconsistent state from outside.
 #[derive(Clone)]
 pub struct Board {
-    pub bb_pieces: [[Bitboard; NrOf::PIECE_TYPES]; Sides::BOTH],
-    pub bb_side: [Bitboard; Sides::BOTH],
-    pub game_state: GameState,
-    pub history: History,
-    pub piece_list: [Piece; NrOf::SQUARES],
+    pub(crate) bb_pieces: [[Bitboard; NrOf::PIECE_TYPES]; Sides::BOTH],
+    pub(crate) bb_side: [Bitboard; Sides::BOTH],
+    pub(crate) game_state: GameState,
+    pub(crate) history: History,
+    pub(crate) piece_list: [Piece; NrOf::SQUARES],
+    pub(crate) castling_rooks: [[Square; 2]; Sides::BOTH],
+    pub(crate) chess960: bool,
+    pub(crate) sanitize_fen: bool,
+    castling_masks: [u8; NrOf::SQUARES],
     zr: Arc<ZobristRandoms>,
+    network: Option<Arc<Network>>,
+    accumulators: Vec<Accumulator>,
 }
 
+// The squares the castling rooks start on in standard chess: [side][wing].
+const STANDARD_CASTLING_ROOKS: [[Square; 2]; Sides::BOTH] =
+    [[Squares::H1, Squares::A1], [Squares::H8, Squares::A8]];
+
 // Public functions for use by other modules.
 impl Board {
     // Creates a new board with either the provided FEN, or the starting position.
@@ -64,10 +83,44 @@ impl Board {
             game_state: GameState::new(),
             history: History::new(),
             piece_list: [Pieces::NONE; NrOf::SQUARES],
+            castling_rooks: STANDARD_CASTLING_ROOKS,
+            chess960: false,
+            sanitize_fen: false,
+            castling_masks: [Castling::ALL; NrOf::SQUARES],
             zr: Arc::new(ZobristRandoms::new()),
+            network: None,
+            accumulators: Vec::new(),
+        }
+    }
+
+    // Creates a new board set up with the given FEN-string. On failure, the
+    // error tells which part of the FEN-string could not be read.
+    pub fn from_fen(fen: &str) -> Result<Self, FenError> {
+        let mut board = Board::new();
+        board.fen_read(Some(fen))?;
+        Ok(board)
+    }
+
+    // Use the given NNUE network for evaluation, or go back to the PSQT
+    // evaluation if None is given.
+    pub fn set_network(&mut self, network: Option<Arc<Network>>) {
+        self.network = network;
+        self.init_accumulators();
+    }
+
+    // Returns the network and the current accumulator, if a network is loaded.
+    pub fn nnue(&self) -> Option<(&Network, &Accumulator)> {
+        match (&self.network, self.accumulators.last()) {
+            (Some(network), Some(accumulator)) => Some((network, accumulator)),
+            _ => None,
         }
     }
 
+    // Returns the signature of the Zobrist random numbers used by the board.
+    pub fn zobrist_signature(&self) -> u64 {
+        self.zr.signature()
+    }
+
     // Return a bitboard with locations of a certain piece type for one of the sides.
     pub fn get_pieces(&self, piece: Piece, side: Side) -> Bitboard {
         self.bb_pieces[side][piece]
@@ -93,8 +146,57 @@ impl Board {
         self.bb_pieces[side][Pieces::KING].trailing_zeros() as Square
     }
 
+    // Returns the Zobrist key of the position.
+    pub fn zobrist_key(&self) -> ZobristKey {
+        self.game_state.zobrist_key
+    }
+
+    // Returns the number of half moves since the last capture or pawn move.
+    pub fn halfmove_clock(&self) -> u8 {
+        self.game_state.halfmove_clock
+    }
+
+    // Returns the number of the move, which increases after black moves.
+    pub fn fullmove_number(&self) -> u16 {
+        self.game_state.fullmove_number
+    }
+
+    // Returns the squares the castling rooks start on: [side][wing].
+    pub fn castling_rooks(&self) -> [[Square; 2]; Sides::BOTH] {
+        self.castling_rooks
+    }
+
+    // Chess960 changes how castling rights are read and written in FEN
+    // strings, and how castling moves are written. It is not changed by
+    // reading a FEN, so set it before reading one.
+    pub fn chess960(&self) -> bool {
+        self.chess960
+    }
+
+    pub fn set_chess960(&mut self, chess960: bool) {
+        self.chess960 = chess960;
+    }
+
+    // If set, castling rights and en-passant squares that can't be right
+    // are dropped when reading a FEN, instead of rejecting the position.
+    pub fn set_sanitize_fen(&mut self, sanitize: bool) {
+        self.sanitize_fen = sanitize;
+    }
+
+    // Returns the number of moves played on this board.
+    pub fn history_len(&self) -> usize {
+        self.history.len()
+    }
+
+    // Share the moves played so far with the copies that are made of this
+    // board, so cloning it doesn't copy the game. Do this after setting
+    // up a position that is going to be searched.
+    pub fn share_history(&mut self) {
+        self.history.share();
+    }
+
     // Remove a piece from the board, for the given side, piece, and square.
-    pub fn remove_piece(&mut self, side: Side, piece: Piece, square: Square) {
+    pub(crate) fn remove_piece(&mut self, side: Side, piece: Piece, square: Square) {
         self.bb_pieces[side][piece] ^= BB_SQUARES[square];
         self.bb_side[side] ^= BB_SQUARES[square];
         self.piece_list[square] = Pieces::NONE;
@@ -105,10 +207,11 @@ impl Board {
         let flip = side == Sides::WHITE;
         let s = if flip { FLIP[square] } else { square };
         self.game_state.psqt[side] -= PSQT_MG[piece][s];
+        self.update_accumulator(side, piece, square, false);
     }
 
     // Put a piece onto the board, for the given side, piece, and square.
-    pub fn put_piece(&mut self, side: Side, piece: Piece, square: Square) {
+    pub(crate) fn put_piece(&mut self, side: Side, piece: Piece, square: Square) {
         self.bb_pieces[side][piece] |= BB_SQUARES[square];
         self.bb_side[side] |= BB_SQUARES[square];
         self.piece_list[square] = piece;
@@ -119,43 +222,56 @@ impl Board {
         let flip = side == Sides::WHITE;
         let s = if flip { FLIP[square] } else { square };
         self.game_state.psqt[side] += PSQT_MG[piece][s];
+        self.update_accumulator(side, piece, square, true);
+
+        // If the king moved into another bucket, the inputs of its
+        // perspective have all changed.
+        if piece == Pieces::KING {
+            self.refresh_accumulator(side);
+        }
     }
 
     // Remove a piece from the from-square, and put it onto the to-square.
-    pub fn move_piece(&mut self, side: Side, piece: Piece, from: Square, to: Square) {
+    pub(crate) fn move_piece(&mut self, side: Side, piece: Piece, from: Square, to: Square) {
         self.remove_piece(side, piece, from);
         self.put_piece(side, piece, to);
     }
 
     // Set a square as being the current ep-square.
-    pub fn set_ep_square(&mut self, square: Square) {
+    pub(crate) fn set_ep_square(&mut self, square: Square) {
         self.game_state.zobrist_key ^= self.zr.en_passant(self.game_state.en_passant);
         self.game_state.en_passant = Some(square as u8);
         self.game_state.zobrist_key ^= self.zr.en_passant(self.game_state.en_passant);
     }
 
     // Clear the ep-square. (If the ep-square is None already, nothing changes.)
-    pub fn clear_ep_square(&mut self) {
+    pub(crate) fn clear_ep_square(&mut self) {
         self.game_state.zobrist_key ^= self.zr.en_passant(self.game_state.en_passant);
         self.game_state.en_passant = None;
         self.game_state.zobrist_key ^= self.zr.en_passant(self.game_state.en_passant);
     }
 
     // Swap side from WHITE <==> BLACK
-    pub fn swap_side(&mut self) {
+    pub(crate) fn swap_side(&mut self) {
         self.game_state.zobrist_key ^= self.zr.side(self.game_state.active_color as usize);
         self.game_state.active_color ^= 1;
         self.game_state.zobrist_key ^= self.zr.side(self.game_state.active_color as usize);
     }
 
     // Update castling permissions and take Zobrist-key into account.
-    pub fn update_castling_permissions(&mut self, new_permissions: u8) {
+    pub(crate) fn update_castling_permissions(&mut self, new_permissions: u8) {
         self.game_state.zobrist_key ^= self.zr.castling(self.game_state.castling);
         self.game_state.castling = new_permissions;
         self.game_state.zobrist_key ^= self.zr.castling(self.game_state.castling);
     }
 }
 
+impl Default for Board {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 // Private board functions (for initializating on startup)
 impl Board {
     // Resets/wipes the board. Used by the FEN reader function.
@@ -165,6 +281,9 @@ impl Board {
         self.game_state = GameState::new();
         self.history.clear();
         self.piece_list = [Pieces::NONE; NrOf::SQUARES];
+        self.castling_rooks = STANDARD_CASTLING_ROOKS;
+        self.castling_masks = [Castling::ALL; NrOf::SQUARES];
+        self.accumulators.clear();
     }
 
     // Main initialization function. This is used to initialize the "other"
@@ -179,11 +298,93 @@ impl Board {
         // Initialize the piece list, zobrist key, and material count. These will
         // later be updated incrementally.
         self.piece_list = self.init_piece_list();
+        self.castling_masks = self.init_castling_masks();
         self.game_state.zobrist_key = self.init_zobrist_key();
 
         let psqt = psqt::apply(self);
         self.game_state.psqt[Sides::WHITE] = psqt.0;
         self.game_state.psqt[Sides::BLACK] = psqt.1;
+
+        self.init_accumulators();
+    }
+
+    // Build the NNUE accumulator from scratch, if a network is loaded.
+    // During make() a copy of it is pushed for each move, and it is then
+    // updated incrementally. Unmake() pops it again. The stack is only
+    // allocated when a network is loaded; without one, it stays empty.
+    fn init_accumulators(&mut self) {
+        self.accumulators.clear();
+        match &self.network {
+            Some(network) => {
+                let accumulator = network.accumulator(self);
+                self.accumulators.reserve(MAX_PLY as usize);
+                self.accumulators.push(accumulator);
+            }
+            None => self.accumulators = Vec::new(),
+        }
+    }
+
+    fn update_accumulator(&mut self, side: Side, piece: Piece, square: Square, add: bool) {
+        if let (Some(network), Some(accumulator)) = (&self.network, self.accumulators.last_mut()) {
+            network.update(accumulator, side, piece, square, add);
+        }
+    }
+
+    // Rebuild one perspective of the accumulator if its king is no longer
+    // in the bucket the perspective was built with.
+    fn refresh_accumulator(&mut self, side: Side) {
+        if let (Some(network), Some(accumulator)) = (&self.network, self.accumulators.last()) {
+            let bucket = nnue::bucket(side, self.king_square(side));
+            if bucket != accumulator.bucket[side] {
+                let values = network.perspective(self, side, bucket);
+                if let Some(accumulator) = self.accumulators.last_mut() {
+                    accumulator.values[side] = values;
+                    accumulator.bucket[side] = bucket;
+                }
+            }
+        }
+    }
+
+    // Save the accumulator before a move is made...
+    fn push_accumulator(&mut self) {
+        if let Some(&accumulator) = self.accumulators.last() {
+            self.accumulators.push(accumulator);
+        }
+    }
+
+    // ...and restore it when the move is taken back. If the position was
+    // set up before the network was loaded, there is nothing to restore,
+    // and the accumulator is rebuilt instead.
+    fn pop_accumulator(&mut self) {
+        if self.accumulators.len() > 1 {
+            self.accumulators.pop();
+        } else {
+            self.init_accumulators();
+        }
+    }
+
+    // Determine which castling permissions are lost when a piece moves
+    // from, or is captured on, each of the squares. When the king moves, its
+    // side loses both permissions. When a castling rook moves or is
+    // captured, only the permission for that wing is lost. In Chess960, the
+    // king and rooks can start on any file, so this depends on the position.
+    fn init_castling_masks(&self) -> [u8; NrOf::SQUARES] {
+        let mut masks = [Castling::ALL; NrOf::SQUARES];
+
+        for side in [Sides::WHITE, Sides::BLACK] {
+            let rights = Castling::RIGHTS[side];
+
+            if self.bb_pieces[side][Pieces::KING] > 0 {
+                masks[self.king_square(side)] &=
+                    !(rights[Wing::KINGSIDE] | rights[Wing::QUEENSIDE]);
+            }
+
+            for wing in [Wing::KINGSIDE, Wing::QUEENSIDE] {
+                masks[self.castling_rooks[side][wing]] &= !rights[wing];
+            }
+        }
+
+        masks
     }
 
     // Gather the pieces for each side into their own bitboard.
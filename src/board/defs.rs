@@ -58,6 +58,10 @@ pub struct Files;
 impl Files {
     pub const A: usize = 0;
     pub const B: usize = 1;
+    pub const C: usize = 2;
+    pub const D: usize = 3;
+    pub const E: usize = 4;
+    pub const F: usize = 5;
     pub const G: usize = 6;
     pub const H: usize = 7;
 }
@@ -66,32 +70,27 @@ pub struct Ranks;
 impl Ranks {
     pub const R1: usize = 0;
     pub const R2: usize = 1;
+    pub const R3: usize = 2;
     pub const R4: usize = 3;
     pub const R5: usize = 4;
+    pub const R6: usize = 5;
     pub const R7: usize = 6;
     pub const R8: usize = 7;
 }
 
+// The two sides of the board a king can castle to.
+pub struct Wing;
+impl Wing {
+    pub const KINGSIDE: usize = 0;
+    pub const QUEENSIDE: usize = 1;
+}
+
 pub struct Squares;
 impl Squares {
-    // White side squares that are important for castling
+    // Corner squares where the rooks start in standard chess
     pub const A1: Square = 0;
-    pub const B1: Square = 1;
-    pub const C1: Square = 2;
-    pub const D1: Square = 3;
-    pub const E1: Square = 4;
-    pub const F1: Square = 5;
-    pub const G1: Square = 6;
     pub const H1: Square = 7;
-
-    // Black side squares that are important for castling
     pub const A8: Square = 56;
-    pub const B8: Square = 57;
-    pub const C8: Square = 58;
-    pub const D8: Square = 59;
-    pub const E8: Square = 60;
-    pub const F8: Square = 61;
-    pub const G8: Square = 62;
     pub const H8: Square = 63;
 
     // White EP-squares start/end
@@ -21,23 +21,26 @@ You should have received a copy of the GNU General Public License along
 with this program.  If not, see <http://www.gnu.org/licenses/>.
 ======================================================================= */
 
-// fen.rs reads an FEN-string and converts it into a board position.
-// If the procedure fails, the original position is not changed. Note that
-// checking position legality is not the responsibility of this module. It
-// is perfectly possible to set up a position with two white kings, both
-// kings in check at the same time, or with black in check but white to
-// move.
+// fen.rs reads an FEN-string and converts it into a board position, and
+// writes the position on the board as an FEN-string.
+// If the procedure fails, the original position is not changed. After the
+// FEN-string is read, the position is checked by the validate module, so
+// positions that can't happen in a game, such as one with two white kings,
+// or with black in check but white to move, are rejected.
 
 use super::{
-    defs::{Files, Pieces, Ranks, Squares, BB_SQUARES},
-    Board,
+    defs::{Files, Pieces, Ranks, Squares, Wing, BB_RANKS, BB_SQUARES, SQUARE_NAME},
+    validate, Board,
 };
 use crate::{
-    defs::{Castling, Sides, Square, FEN_START_POSITION, MAX_GAME_MOVES, MAX_MOVE_RULE},
+    defs::{Castling, Sides, Square, FEN_START_POSITION, MAX_MOVE_RULE},
     misc::parse,
 };
 use if_chain::if_chain;
-use std::ops::RangeInclusive;
+use std::{
+    fmt::{self, Display},
+    ops::RangeInclusive,
+};
 
 /** Definitions used by the FEN-reader */
 const NR_OF_FEN_PARTS: usize = 6;
@@ -46,14 +49,61 @@ const LIST_OF_PIECES: &str = "kqrbnpKQRBNP";
 const EP_SQUARES_WHITE: RangeInclusive<Square> = Squares::A3..=Squares::H3;
 const EP_SQUARES_BLACK: RangeInclusive<Square> = Squares::A6..=Squares::H6;
 const WHITE_OR_BLACK: &str = "wb";
-const CASTLING_RIGHTS: &str = "KQkq-";
 const SPLITTER: char = '/';
 const DASH: char = '-';
 const EM_DASH: char = '–';
 const SPACE: char = ' ';
 
 type FenPartParser = fn(board: &mut Board, part: &str) -> bool;
-type FenResult = Result<(), u8>;
+pub type FenResult = Result<(), FenError>;
+
+// The part of the FEN-string that could not be read, or the reason why
+// the position it describes is impossible.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FenError {
+    Parts,
+    Pieces,
+    Color,
+    Castling,
+    EnPassant,
+    HalfmoveClock,
+    FullmoveNumber,
+    Kings,
+    PawnsOnBackRank,
+    CastlingRights,
+    EnPassantPawn,
+    OpponentInCheck,
+}
+
+impl Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            FenError::Parts => "Must have six parts",
+            FenError::Pieces => "Pieces and squares incorrect",
+            FenError::Color => "Color selection incorrect",
+            FenError::Castling => "Castling permissions incorrect",
+            FenError::EnPassant => "En-passant square incorrect",
+            FenError::HalfmoveClock => "Half-move clock incorrect",
+            FenError::FullmoveNumber => "Full-move number incorrect",
+            FenError::Kings => "Each side must have exactly one king",
+            FenError::PawnsOnBackRank => "Pawns on the first or eighth rank",
+            FenError::CastlingRights => "Castling rights without king or rook on its square",
+            FenError::EnPassantPawn => "En-passant square without a pawn that just moved",
+            FenError::OpponentInCheck => "Side not to move is in check",
+        };
+        write!(f, "FEN: {msg}")
+    }
+}
+
+// The error for each of the six parts, in the order they are parsed.
+const FEN_PART_ERRORS: [FenError; NR_OF_FEN_PARTS] = [
+    FenError::Pieces,
+    FenError::Color,
+    FenError::Castling,
+    FenError::EnPassant,
+    FenError::HalfmoveClock,
+    FenError::FullmoveNumber,
+];
 
 impl Board {
     // This function reads a provided FEN-string or uses the default position.
@@ -76,7 +126,11 @@ impl Board {
         let nr_of_parts_ok = fen_parts.len() == NR_OF_FEN_PARTS;
 
         // Set the initial result.
-        let mut result: FenResult = if nr_of_parts_ok { Ok(()) } else { Err(0) };
+        let mut result: FenResult = if nr_of_parts_ok {
+            Ok(())
+        } else {
+            Err(FenError::Parts)
+        };
 
         if nr_of_parts_ok {
             // Create an array of function pointers; one parsing function per part.
@@ -92,10 +146,21 @@ impl Board {
                 let parser = &fen_parsers[i];
                 let part = &fen_parts[i];
                 let part_ok = parser(&mut new_board, part);
-                result = if part_ok { Ok(()) } else { Err(i as u8 + 1) };
+                result = if part_ok {
+                    Ok(())
+                } else {
+                    Err(FEN_PART_ERRORS[i])
+                };
                 i += 1;
             }
 
+            // Check if the position can actually happen in a game. If the
+            // board is set to sanitize, impossible castling rights and
+            // en-passant squares are dropped instead of rejected.
+            if result == Ok(()) {
+                result = validate::position(&mut new_board, self.sanitize_fen);
+            }
+
             // Replace original board with new one if setup was successful.
             if result == Ok(()) {
                 new_board.init();
@@ -105,6 +170,78 @@ impl Board {
 
         result
     }
+
+    // Write the position as an FEN-string. Castling rights are written in
+    // X-FEN: "KQkq" for the outermost rook on a wing, and the rook's file
+    // if there's another rook further out. For standard chess, this is
+    // just normal FEN.
+    pub fn fen_write(&self) -> String {
+        let mut pieces = String::new();
+        for rank in (Ranks::R1..=Ranks::R8).rev() {
+            let mut empty = 0;
+            for file in Files::A..=Files::H {
+                let square = rank * 8 + file;
+                let piece = self.piece_list[square];
+                if piece == Pieces::NONE {
+                    empty += 1;
+                    continue;
+                }
+                if empty > 0 {
+                    pieces.push_str(&empty.to_string());
+                    empty = 0;
+                }
+                let c = LIST_OF_PIECES.as_bytes()[piece] as char;
+                let is_white = self.bb_side[Sides::WHITE] & BB_SQUARES[square] > 0;
+                pieces.push(if is_white { c.to_ascii_uppercase() } else { c });
+            }
+            if empty > 0 {
+                pieces.push_str(&empty.to_string());
+            }
+            if rank > Ranks::R1 {
+                pieces.push(SPLITTER);
+            }
+        }
+
+        let color = if self.us() == Sides::WHITE { "w" } else { "b" };
+
+        let mut castling = String::new();
+        for side in [Sides::WHITE, Sides::BLACK] {
+            for (wing, letter) in [(Wing::KINGSIDE, 'k'), (Wing::QUEENSIDE, 'q')] {
+                if self.game_state.castling & Castling::RIGHTS[side][wing] == 0 {
+                    continue;
+                }
+                let rook = self.castling_rooks[side][wing];
+                let c = if rook == outer_rook(self, side, wing) {
+                    letter
+                } else {
+                    (b'a' + (rook % 8) as u8) as char
+                };
+                castling.push(if side == Sides::WHITE {
+                    c.to_ascii_uppercase()
+                } else {
+                    c
+                });
+            }
+        }
+        if castling.is_empty() {
+            castling.push(DASH);
+        }
+
+        let ep = match self.game_state.en_passant {
+            Some(square) => String::from(SQUARE_NAME[square as usize]),
+            None => DASH.to_string(),
+        };
+
+        format!(
+            "{} {} {} {} {} {}",
+            pieces,
+            color,
+            castling,
+            ep,
+            self.game_state.halfmove_clock,
+            self.game_state.fullmove_number
+        )
+    }
 }
 
 // ===== Private functions =====
@@ -119,6 +256,12 @@ fn pieces(board: &mut Board, part: &str) -> bool {
 
     // Parse each character; it should be a piece, square count, or splitter.
     for c in part.chars() {
+        // A piece beyond the H-file means the rank is too long.
+        if LIST_OF_PIECES.contains(c) && file > Files::H as u8 {
+            result = false;
+            break;
+        }
+
         let square = ((rank * 8) + file) as usize;
         match c {
             'k' => board.bb_pieces[Sides::BLACK][Pieces::KING] |= BB_SQUARES[square],
@@ -137,10 +280,11 @@ fn pieces(board: &mut Board, part: &str) -> bool {
                 if let Some(x) = c.to_digit(10) {
                     file += x as u8;
                 }
+                result = file <= 8;
             }
             SPLITTER => {
-                result = file == 8;
-                rank -= 1;
+                result = file == 8 && rank > Ranks::R1 as u8;
+                rank = rank.saturating_sub(1);
                 file = 0;
             }
             // Unknown character: result becomes false.
@@ -158,7 +302,8 @@ fn pieces(board: &mut Board, part: &str) -> bool {
         }
     }
 
-    result
+    // All eight ranks must have been completely filled.
+    result && rank == Ranks::R1 as u8 && file == 8
 }
 
 // Part 2: Parse color to move: White or Black
@@ -186,7 +331,11 @@ fn color(board: &mut Board, part: &str) -> bool {
     result
 }
 
-// Part 3: Parse castling rights.
+// Part 3: Parse castling rights. Next to the standard "KQkq", this also
+// accepts Shredder-FEN and X-FEN for Chess960. Shredder-FEN gives the file
+// of the castling rook ("HAha"). X-FEN uses "KQkq" for the outermost rook
+// on each wing, and the file letter only if there's another rook further
+// out on the same wing. Parsing is done after the pieces are set up.
 fn castling(board: &mut Board, part: &str) -> bool {
     let length = part.len();
     let mut char_ok = 0;
@@ -196,15 +345,39 @@ fn castling(board: &mut Board, part: &str) -> bool {
     if (1..=4).contains(&length) {
         // Accepts "-" for no castling rights in addition to leaving out letters.
         for c in part.chars() {
-            if CASTLING_RIGHTS.contains(c) {
-                // Count correct characters
+            let side = if c.is_ascii_uppercase() {
+                Sides::WHITE
+            } else {
+                Sides::BLACK
+            };
+
+            let rook_square = match c.to_ascii_lowercase() {
+                'k' => Some(outer_rook(board, side, Wing::KINGSIDE)),
+                'q' => Some(outer_rook(board, side, Wing::QUEENSIDE)),
+                'a'..='h' => {
+                    Some(back_rank(side) * 8 + (c.to_ascii_lowercase() as usize - 'a' as usize))
+                }
+                _ => None,
+            };
+
+            if c == DASH {
                 char_ok += 1;
-                match c {
-                    'K' => board.game_state.castling |= Castling::WK,
-                    'Q' => board.game_state.castling |= Castling::WQ,
-                    'k' => board.game_state.castling |= Castling::BK,
-                    'q' => board.game_state.castling |= Castling::BQ,
-                    _ => (),
+            }
+
+            // The castling rook is on the wing it castles to, as seen from
+            // the king's file. It can't be on the king's file itself.
+            if let Some(square) = rook_square {
+                let rook_file = square % 8;
+                let king_file = king_file(board, side);
+                if rook_file != king_file {
+                    let wing = if rook_file > king_file {
+                        Wing::KINGSIDE
+                    } else {
+                        Wing::QUEENSIDE
+                    };
+                    board.game_state.castling |= Castling::RIGHTS[side][wing];
+                    board.castling_rooks[side][wing] = square;
+                    char_ok += 1;
                 }
             }
         }
@@ -215,6 +388,55 @@ fn castling(board: &mut Board, part: &str) -> bool {
     (length >= 1) && (char_ok == length)
 }
 
+// Returns the rank the pieces of the given side start on.
+fn back_rank(side: usize) -> usize {
+    if side == Sides::WHITE {
+        Ranks::R1
+    } else {
+        Ranks::R8
+    }
+}
+
+// Returns the file of the king, if it's on its back rank. Otherwise the
+// king can't castle anyway, and the standard e-file is assumed.
+fn king_file(board: &Board, side: usize) -> usize {
+    let bb_king = board.bb_pieces[side][Pieces::KING];
+    let bb_back_rank = BB_RANKS[back_rank(side)];
+
+    if bb_king & bb_back_rank > 0 {
+        bb_king.trailing_zeros() as usize % 8
+    } else {
+        Files::E
+    }
+}
+
+// Find the outermost rook on the given wing of the king's back rank. If
+// there is none, the rook is assumed to be in the corner, as in standard
+// chess.
+fn outer_rook(board: &Board, side: usize, wing: usize) -> Square {
+    let rank = back_rank(side);
+    let king_file = king_file(board, side);
+    let bb_rooks = board.bb_pieces[side][Pieces::ROOK];
+    let files: Vec<usize> = if wing == Wing::KINGSIDE {
+        ((king_file + 1)..=Files::H).rev().collect()
+    } else {
+        (Files::A..king_file).collect()
+    };
+
+    let corner = if wing == Wing::KINGSIDE {
+        Files::H
+    } else {
+        Files::A
+    };
+
+    let file = files
+        .into_iter()
+        .find(|f| bb_rooks & BB_SQUARES[rank * 8 + f] > 0)
+        .unwrap_or(corner);
+
+    rank * 8 + file
+}
+
 // Part 4: Parse the en passant square
 fn ep(board: &mut Board, part: &str) -> bool {
     let length = part.len();
@@ -274,7 +496,6 @@ fn fmn(board: &mut Board, part: &str) -> bool {
     if_chain! {
         if length >= 1 || length <= 4;
         if let Ok(x) = part.parse::<u16>();
-        if x <= (MAX_GAME_MOVES as u16);
         then {
             board.game_state.fullmove_number = x;
             result = true;
@@ -283,3 +504,152 @@ fn fmn(board: &mut Board, part: &str) -> bool {
 
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::defs::FEN_KIWIPETE_POSITION;
+
+    fn chess960_board(fen: &str) -> Board {
+        let mut board = Board::new();
+        board.set_chess960(true);
+        assert_eq!(board.fen_read(Some(fen)), Ok(()), "{fen}");
+        board
+    }
+
+    // Write the position and read it back: the FEN and the position,
+    // including its Zobrist key and castling rooks, must stay the same.
+    fn assert_round_trip(board: &Board, fen: &str) {
+        let written = board.fen_write();
+        assert_eq!(written, fen);
+
+        let mut again = Board::new();
+        again.set_chess960(board.chess960());
+        assert_eq!(again.fen_read(Some(&written)), Ok(()));
+        assert_eq!(again.fen_write(), fen);
+        assert_eq!(again.zobrist_key(), board.zobrist_key());
+        assert_eq!(again.castling_rooks(), board.castling_rooks());
+    }
+
+    #[test]
+    fn standard_positions_round_trip() {
+        for fen in [
+            FEN_START_POSITION,
+            FEN_KIWIPETE_POSITION,
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "8/2k5/8/8/8/8/5K2/8 b - - 37 102",
+        ] {
+            let board = Board::from_fen(fen).unwrap_or_else(|e| panic!("{e}: {fen}"));
+            assert_round_trip(&board, fen);
+        }
+    }
+
+    #[test]
+    fn short_fen_gets_default_move_counters() {
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - -").expect("valid FEN");
+        assert_eq!(board.fen_write(), "4k3/8/8/8/8/8/8/4K3 w - - 0 1");
+    }
+
+    #[test]
+    fn chess960_outer_rooks_round_trip_as_kqkq() {
+        let fen = "bqnbrkrn/pppppppp/8/8/8/8/PPPPPPPP/BQNBRKRN w KQkq - 0 1";
+        let board = chess960_board(fen);
+
+        assert_eq!(
+            SQUARE_NAME[board.castling_rooks()[Sides::WHITE][Wing::KINGSIDE]],
+            "g1"
+        );
+        assert_eq!(
+            SQUARE_NAME[board.castling_rooks()[Sides::WHITE][Wing::QUEENSIDE]],
+            "e1"
+        );
+        assert_round_trip(&board, fen);
+    }
+
+    #[test]
+    fn chess960_inner_rook_round_trips_as_its_file() {
+        // With a rook further out on the same wing, the castling rook is
+        // written as its file (X-FEN).
+        let fen = "4k3/8/8/8/8/8/8/RR2K3 w B - 0 1";
+        let board = chess960_board(fen);
+
+        assert_eq!(
+            SQUARE_NAME[board.castling_rooks()[Sides::WHITE][Wing::QUEENSIDE]],
+            "b1"
+        );
+        assert_round_trip(&board, fen);
+    }
+
+    #[test]
+    fn shredder_fen_is_written_as_x_fen() {
+        let board = chess960_board("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w HAha - 0 1");
+        assert_eq!(board.fen_write(), FEN_START_POSITION);
+    }
+
+    #[test]
+    fn invalid_fen_parts_are_reported() {
+        let cases = [
+            ("8/8/8/8/8/8/8/8 w", FenError::Parts),
+            ("4k3/8/8/8/8/8/8/4K2 w - - 0 1", FenError::Pieces),
+            ("4k3/8/8/8/8/8/8/4K3 x - - 0 1", FenError::Color),
+            ("4k3/8/8/8/8/8/8/4K3 w KQkqK - 0 1", FenError::Castling),
+            ("4k3/8/8/8/8/8/8/4K3 w X - 0 1", FenError::Castling),
+            ("4k3/8/8/8/8/8/8/4K3 w - e4 0 1", FenError::EnPassant),
+            ("4k3/8/8/8/8/8/8/4K3 w - - x 1", FenError::HalfmoveClock),
+            ("4k3/8/8/8/8/8/8/4K3 w - - 0 x", FenError::FullmoveNumber),
+        ];
+
+        for (fen, error) in cases {
+            assert_eq!(Board::from_fen(fen).err(), Some(error), "{fen}");
+        }
+    }
+
+    #[test]
+    fn impossible_positions_are_reported() {
+        let cases = [
+            ("8/8/8/8/8/8/8/4K3 w - - 0 1", FenError::Kings),
+            ("4k3/8/8/8/8/8/8/3KK3 w - - 0 1", FenError::Kings),
+            ("P3k3/8/8/8/8/8/8/4K3 w - - 0 1", FenError::PawnsOnBackRank),
+            ("4k3/8/8/8/8/8/8/4K3 w K - 0 1", FenError::CastlingRights),
+            (
+                "4k3/8/8/8/8/8/8/R3K2R w KQk - 0 1",
+                FenError::CastlingRights,
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq e3 0 1",
+                FenError::EnPassantPawn,
+            ),
+            ("4k3/8/8/8/8/8/8/4R1K1 w - - 0 1", FenError::OpponentInCheck),
+        ];
+
+        for (fen, error) in cases {
+            assert_eq!(Board::from_fen(fen).err(), Some(error), "{fen}");
+        }
+    }
+
+    #[test]
+    fn sanitize_drops_impossible_castling_and_en_passant() {
+        let mut board = Board::new();
+        board.set_sanitize_fen(true);
+
+        let fen = "4k3/8/8/8/8/8/8/R3K3 w KQ e6 0 1";
+        assert_eq!(board.fen_read(Some(fen)), Ok(()));
+        assert_eq!(board.fen_write(), "4k3/8/8/8/8/8/8/R3K3 w Q - 0 1");
+
+        // Errors that can't be repaired are still reported.
+        let fen = "4k3/8/8/8/8/8/8/4R1K1 w - - 0 1";
+        assert_eq!(board.fen_read(Some(fen)), Err(FenError::OpponentInCheck));
+    }
+
+    #[test]
+    fn failed_read_leaves_the_board_unchanged() {
+        let mut board = Board::from_fen(FEN_KIWIPETE_POSITION).expect("valid FEN");
+        let key = board.zobrist_key();
+
+        assert!(board
+            .fen_read(Some("4k3/8/8/8/8/8/8/4K3 w K - 0 1"))
+            .is_err());
+        assert_eq!(board.fen_write(), FEN_KIWIPETE_POSITION);
+        assert_eq!(board.zobrist_key(), key);
+    }
+}
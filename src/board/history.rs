@@ -22,59 +22,95 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 ======================================================================= */
 
 use super::gamestate::GameState;
-use crate::defs::MAX_GAME_MOVES;
+use crate::defs::MAX_PLY;
+use std::sync::Arc;
 
-// The history struct is basically an array holding the values of the game
-// states at each move. If a move is made in make(), this function pushes the
-// current game state into this array. In unmake(), that game state can then be
-// popped and restored. It is faster than a vector, because:
+// The history struct holds the game states at each move. If a move is made
+// in make(), that function pushes the current game state into the history.
+// In unmake(), that game state can then be popped and restored. It is up to
+// the caller to check if the history is empty before popping (if necessary,
+// such as during console play: the chess engine will always have one push
+// for every pop during search.)
 //
-// - It is stored on the stack (a vector is stored on the heap)
-// - It doesn't do any error checking. It is up to the caller to check if the
-//   history array is either full or empty, before pushing or popping (if
-//   necessary, such as during console play: the chess engine will always have
-//   one push for every pop during search.)
+// The history is split in two parts:
+//
+// - The game states of the moves played in the game so far. These are
+//   stored behind an Arc, so a board can be cloned for the search without
+//   copying the entire game. The search never changes them; it only reads
+//   them to detect repetitions. They are only popped when taking back moves
+//   during console play.
+// - The game states pushed after that. These are stored in a vector that
+//   grows when needed, so there is no limit on the length of a game.
+//
+// Calling share() moves the game states of the second part into the first.
+// The engine does this after setting up a new position.
 
 #[derive(Clone)]
 pub struct History {
-    list: [GameState; MAX_GAME_MOVES],
-    count: usize,
+    shared: Arc<Vec<GameState>>,
+    shared_count: usize,
+    list: Vec<GameState>,
 }
 
 impl History {
-    // Create a new history array containing game states.
+    // Create a new, empty history.
     pub fn new() -> Self {
         Self {
-            list: [GameState::new(); MAX_GAME_MOVES],
-            count: 0,
+            shared: Arc::new(Vec::new()),
+            shared_count: 0,
+            list: Vec::with_capacity(MAX_PLY as usize),
         }
     }
 
-    // Wipe the entire array.
+    // Wipe the entire history.
     pub fn clear(&mut self) {
-        self.list = [GameState::new(); MAX_GAME_MOVES];
-        self.count = 0;
+        self.shared = Arc::new(Vec::new());
+        self.shared_count = 0;
+        self.list.clear();
     }
 
-    // Put a new game state into the array.
+    // Put a new game state into the history.
     pub fn push(&mut self, g: GameState) {
-        self.list[self.count] = g;
-        self.count += 1;
+        self.list.push(g);
     }
 
-    // Return the last game state and decremnt the counter. The game state is
-    // not deleted from the array. If necessary, another game state will just
-    // overwrite it.
+    // Return the last game state and remove it from the history. If all the
+    // game states pushed since share() are gone, the shared ones are next.
+    // These are not deleted, because other boards may still be using them;
+    // this history just stops counting them.
     pub fn pop(&mut self) -> GameState {
-        self.count -= 1;
-        self.list[self.count]
+        match self.list.pop() {
+            Some(g) => g,
+            None => {
+                self.shared_count -= 1;
+                self.shared[self.shared_count]
+            }
+        }
     }
 
     pub fn get_ref(&self, index: usize) -> &GameState {
-        &self.list[index]
+        if index < self.shared_count {
+            &self.shared[index]
+        } else {
+            &self.list[index - self.shared_count]
+        }
     }
 
     pub fn len(&self) -> usize {
-        self.count
+        self.shared_count + self.list.len()
+    }
+
+    // Move all the game states into the shared part of the history, so
+    // cloning the board after this doesn't copy them.
+    pub fn share(&mut self) {
+        if self.list.is_empty() && self.shared_count == self.shared.len() {
+            return;
+        }
+
+        let mut shared = Vec::with_capacity(self.len());
+        shared.extend_from_slice(&self.shared[..self.shared_count]);
+        shared.append(&mut self.list);
+        self.shared_count = shared.len();
+        self.shared = Arc::new(shared);
     }
 }
@@ -24,49 +24,46 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 // playmove.rs contains make() and unamke() for move execution and reversal.
 
 use super::{
-    defs::{Pieces, Squares, BB_SQUARES},
+    defs::{Pieces, BB_SQUARES},
     Board,
 };
 use crate::{
-    defs::{Castling, NrOf, Piece, Side, Sides, Square},
+    defs::{Piece, Side, Sides, Square},
     movegen::{defs::Move, MoveGenerator},
 };
 
-// Castling Permissions Per Square
-type CPSquare = [u8; NrOf::SQUARES];
-const CASTLING_PERMS: CPSquare = castling_permissions_per_square();
-const fn castling_permissions_per_square() -> CPSquare {
-    // First set all squares grant all castling permissions. This means
-    // moving a piece on such square doesn't have any effect on castling
-    // permissions.
-    let mut cp: CPSquare = [Castling::ALL; NrOf::SQUARES];
-
-    // Now disable castling permissions when moving pieces on certain
-    // squares. For example, when the piece (rook) on A1 moves, disable
-    // white castling to the queenside.
-    cp[Squares::A1] &= !Castling::WQ;
-    cp[Squares::E1] &= !Castling::WK & !Castling::WQ;
-    cp[Squares::H1] &= !Castling::WK;
-    cp[Squares::A8] &= !Castling::BQ;
-    cp[Squares::E8] &= !Castling::BK & !Castling::BQ;
-    cp[Squares::H8] &= !Castling::BK;
-
-    cp
-}
-
 /*** ================================================================================ ***/
 
 // Make() executes the given move and checks if it is legal. If it's not legal,
 // the move is immediately reversed using unmake(), and the board is not changed.
+// Make_legal() executes a move that is already known to be legal, such as a
+// move provided by the legal move generator, and skips the verification.
 
 impl Board {
     #[cfg_attr(debug_assertions, inline(never))]
     #[cfg_attr(not(debug_assertions), inline(always))]
     pub fn make(&mut self, m: Move, mg: &MoveGenerator) -> bool {
+        let us = self.us();
+        self.make_legal(m);
+
+        /*** Validating move: see if "us" is in check. If so, undo everything. ***/
+        let is_legal = !mg.square_attacked(self, us ^ 1, self.king_square(us));
+        if !is_legal {
+            self.unmake();
+        }
+
+        // Report if the move was legal or not.
+        is_legal
+    }
+
+    #[cfg_attr(debug_assertions, inline(never))]
+    #[cfg_attr(not(debug_assertions), inline(always))]
+    pub fn make_legal(&mut self, m: Move) {
         // Create the unmake info and store it.
         let mut current_game_state = self.game_state;
         current_game_state.next_move = m;
         self.history.push(current_game_state);
+        self.push_accumulator();
 
         // Set "us" and "opponent"
         let us = self.us();
@@ -101,12 +98,25 @@ impl Board {
             self.game_state.halfmove_clock = 0;
             // Change castling permissions on rook capture in the corner.
             if captured == Pieces::ROOK && has_permissions {
-                self.update_castling_permissions(self.game_state.castling & CASTLING_PERMS[to]);
+                self.update_castling_permissions(
+                    self.game_state.castling & self.castling_masks[to],
+                );
             }
         }
 
-        // Make the move. Just move the piece if it's not a pawn.
-        if piece != Pieces::PAWN {
+        // Make the move. When castling, the king and rook are both removed
+        // before they are put on their new squares. In Chess960, one of them
+        // can land on the square the other one started on.
+        if castling {
+            let wing = Board::castling_wing(m);
+            let rook_from = self.castling_rooks[us][wing];
+            let (king_to, rook_to) = Board::castling_squares(us, wing);
+            self.remove_piece(us, Pieces::KING, from);
+            self.remove_piece(us, Pieces::ROOK, rook_from);
+            self.put_piece(us, Pieces::KING, king_to);
+            self.put_piece(us, Pieces::ROOK, rook_to);
+        } else if piece != Pieces::PAWN {
+            // Just move the piece if it's not a pawn.
             self.move_piece(us, piece, from, to);
         } else {
             // It's a pawn move. Take promotion into account and reset halfmove_clock.
@@ -128,18 +138,7 @@ impl Board {
         // Remove castling permissions if king/rook leaves from starting square.
         // (This will also adjust permissions when castling, because the king moves.)
         if (piece == Pieces::KING || piece == Pieces::ROOK) && has_permissions {
-            self.update_castling_permissions(self.game_state.castling & CASTLING_PERMS[from]);
-        }
-
-        // If the king is castling, then also move the rook.
-        if castling {
-            match to {
-                Squares::G1 => self.move_piece(us, Pieces::ROOK, Squares::H1, Squares::F1),
-                Squares::C1 => self.move_piece(us, Pieces::ROOK, Squares::A1, Squares::D1),
-                Squares::G8 => self.move_piece(us, Pieces::ROOK, Squares::H8, Squares::F8),
-                Squares::C8 => self.move_piece(us, Pieces::ROOK, Squares::A8, Squares::D8),
-                _ => panic!("Error moving rook during castling."),
-            }
+            self.update_castling_permissions(self.game_state.castling & self.castling_masks[from]);
         }
 
         // Swap the side to move.
@@ -150,18 +149,9 @@ impl Board {
             self.game_state.fullmove_number += 1;
         }
 
-        /*** Validating move: see if "us" is in check. If so, undo everything. ***/
-        let is_legal = !mg.square_attacked(self, opponent, self.king_square(us));
-        if !is_legal {
-            self.unmake();
-        }
-
         // When running in debug mode, check the incrementally updated
         // values such as Zobrist key and meterial count.
         debug_assert!(check_incrementals(self));
-
-        // Report if the move was legal or not.
-        is_legal
     }
 }
 
@@ -189,26 +179,23 @@ impl Board {
         let castling = m.castling();
         let en_passant = m.en_passant();
 
-        // Moving backwards...
-        if promoted == Pieces::NONE {
+        // Moving backwards... When castling, take both the king and the
+        // rook off the board before putting them back.
+        if castling {
+            let wing = Board::castling_wing(m);
+            let rook_from = self.castling_rooks[us][wing];
+            let (king_to, rook_to) = Board::castling_squares(us, wing);
+            remove_piece(self, us, Pieces::KING, king_to);
+            remove_piece(self, us, Pieces::ROOK, rook_to);
+            put_piece(self, us, Pieces::KING, from);
+            put_piece(self, us, Pieces::ROOK, rook_from);
+        } else if promoted == Pieces::NONE {
             reverse_move(self, us, piece, to, from);
         } else {
             remove_piece(self, us, promoted, to);
             put_piece(self, us, Pieces::PAWN, from);
         }
 
-        // The king's move was already undone as a normal move.
-        // Now undo the correct castling rook move.
-        if castling {
-            match to {
-                Squares::G1 => reverse_move(self, us, Pieces::ROOK, Squares::F1, Squares::H1),
-                Squares::C1 => reverse_move(self, us, Pieces::ROOK, Squares::D1, Squares::A1),
-                Squares::G8 => reverse_move(self, us, Pieces::ROOK, Squares::F8, Squares::H8),
-                Squares::C8 => reverse_move(self, us, Pieces::ROOK, Squares::D8, Squares::A8),
-                _ => panic!("Error: Reversing castling rook move."),
-            };
-        }
-
         // If a piece was captured, put it back onto the to-square
         if captured != Pieces::NONE {
             put_piece(self, opponent, captured, to);
@@ -218,6 +205,9 @@ impl Board {
         if en_passant {
             put_piece(self, opponent, Pieces::PAWN, to ^ 8);
         }
+
+        // The NNUE accumulator is kept on its own stack.
+        self.pop_accumulator();
     }
 }
 
@@ -279,5 +269,13 @@ fn check_incrementals(board: &Board) -> bool {
         result = false;
     };
 
+    if let Some((network, accumulator)) = board.nnue() {
+        let from_scratch = network.accumulator(board);
+        if result && from_scratch.values != accumulator.values {
+            println!("Check Incrementals: Error in NNUE accumulator.");
+            result = false;
+        }
+    }
+
     result
 }
@@ -23,8 +23,9 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use super::{defs::Location, Board};
 use crate::{
-    board::defs::Ranks,
+    board::defs::{Files, Ranks, Wing},
     defs::{Side, Sides, Square},
+    movegen::defs::Move,
 };
 
 impl Board {
@@ -57,4 +58,33 @@ impl Board {
             Ranks::R1
         }
     }
+
+    // Returns the squares the king and the rook end up on after castling
+    // to the given wing. These are the same in standard chess and Chess960.
+    pub fn castling_squares(side: Side, wing: usize) -> (Square, Square) {
+        let rank = if side == Sides::WHITE {
+            Ranks::R1
+        } else {
+            Ranks::R8
+        };
+        let (king_file, rook_file) = if wing == Wing::KINGSIDE {
+            (Files::G, Files::F)
+        } else {
+            (Files::C, Files::D)
+        };
+
+        (rank * 8 + king_file, rank * 8 + rook_file)
+    }
+
+    // Returns the wing a castling move goes to. A castling move is either
+    // encoded as the king moving two squares, or as the king capturing its
+    // own rook. In both cases, the to-square is on the same side of the king
+    // as the wing it castles to.
+    pub fn castling_wing(m: Move) -> usize {
+        if m.to() > m.from() {
+            Wing::KINGSIDE
+        } else {
+            Wing::QUEENSIDE
+        }
+    }
 }
@@ -0,0 +1,252 @@
+/* =======================================================================
+Rustic is a chess playing engine.
+Copyright (C) 2019-2024, Marcel Vanthoor
+https://rustic-chess.org/
+
+Rustic is written in the Rust programming language. It is an original
+work, not derived from any engine that came before it. However, it does
+use a lot of concepts which are well-known and are in use by most if not
+all classical alpha/beta-based chess engines.
+
+Rustic is free software: you can redistribute it and/or modify it under
+the terms of the GNU General Public License version 3 as published by
+the Free Software Foundation.
+
+Rustic is distributed in the hope that it will be useful, but WITHOUT
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+for more details.
+
+You should have received a copy of the GNU General Public License along
+with this program.  If not, see <http://www.gnu.org/licenses/>.
+======================================================================= */
+
+// validate.rs checks if a position read by the FEN-reader can actually
+// happen in a game. The FEN-reader itself only checks if each part of the
+// FEN-string is correctly written; this module checks if the pieces and
+// the game state make sense together. Positions that are impossible, such
+// as a missing king or the side not to move being in check, would trip
+// the assumptions in make() and unmake() later on.
+
+// When sanitizing, castling rights and an en-passant square that can't be
+// correct are dropped instead of being reported. The other errors can't
+// be repaired without guessing at the position, so they are always
+// reported.
+
+use super::{
+    defs::{Pieces, Ranks, BB_RANKS, BB_SQUARES},
+    fen::{FenError, FenResult},
+    Board, STANDARD_CASTLING_ROOKS,
+};
+use crate::{
+    board::defs::Wing,
+    defs::{Bitboard, Castling, Side, Sides, Square},
+};
+
+type Step = (i8, i8);
+
+// Steps as (file, rank) for the pieces that jump, and directions for the
+// pieces that slide.
+const KNIGHT_STEPS: [Step; 8] = [
+    (1, 2),
+    (2, 1),
+    (2, -1),
+    (1, -2),
+    (-1, -2),
+    (-2, -1),
+    (-2, 1),
+    (-1, 2),
+];
+const KING_STEPS: [Step; 8] = [
+    (0, 1),
+    (1, 1),
+    (1, 0),
+    (1, -1),
+    (0, -1),
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+];
+const ROOK_DIRECTIONS: [Step; 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+const BISHOP_DIRECTIONS: [Step; 4] = [(1, 1), (1, -1), (-1, -1), (-1, 1)];
+
+// Check the position the FEN-reader has set up. The board is not yet
+// initialized at this point, so only the piece bitboards and the game
+// state can be used.
+pub fn position(board: &mut Board, sanitize: bool) -> FenResult {
+    kings(board)?;
+    pawns(board)?;
+    castling(board, sanitize)?;
+    en_passant(board, sanitize)?;
+    opponent_in_check(board)
+}
+
+// Each side must have exactly one king.
+fn kings(board: &Board) -> FenResult {
+    let one_king = [Sides::WHITE, Sides::BLACK]
+        .iter()
+        .all(|&side| board.bb_pieces[side][Pieces::KING].count_ones() == 1);
+
+    if one_king {
+        Ok(())
+    } else {
+        Err(FenError::Kings)
+    }
+}
+
+// Pawns can't stand on the first rank, and they promote on the last.
+fn pawns(board: &Board) -> FenResult {
+    let bb_pawns =
+        board.bb_pieces[Sides::WHITE][Pieces::PAWN] | board.bb_pieces[Sides::BLACK][Pieces::PAWN];
+    let bb_back_ranks = BB_RANKS[Ranks::R1] | BB_RANKS[Ranks::R8];
+
+    if bb_pawns & bb_back_ranks == 0 {
+        Ok(())
+    } else {
+        Err(FenError::PawnsOnBackRank)
+    }
+}
+
+// A side can only have a castling right if its king is still on the back
+// rank, and the castling rook is on its starting square. The FEN-reader
+// already made sure the rook is on the correct wing of the king.
+fn castling(board: &mut Board, sanitize: bool) -> FenResult {
+    for side in [Sides::WHITE, Sides::BLACK] {
+        let back_rank = if side == Sides::WHITE {
+            Ranks::R1
+        } else {
+            Ranks::R8
+        };
+        let king_home = board.bb_pieces[side][Pieces::KING] & BB_RANKS[back_rank] > 0;
+
+        for wing in [Wing::KINGSIDE, Wing::QUEENSIDE] {
+            let right = Castling::RIGHTS[side][wing];
+            let rook_square = board.castling_rooks[side][wing];
+            let rook_home = board.bb_pieces[side][Pieces::ROOK] & BB_SQUARES[rook_square] > 0;
+
+            if board.game_state.castling & right == 0 || (king_home && rook_home) {
+                continue;
+            }
+
+            if !sanitize {
+                return Err(FenError::CastlingRights);
+            }
+
+            board.game_state.castling &= !right;
+            board.castling_rooks[side][wing] = STANDARD_CASTLING_ROOKS[side][wing];
+        }
+    }
+
+    Ok(())
+}
+
+// The en-passant square is the square a pawn of the opponent just passed
+// over by moving two squares forward. That pawn must be right in front of
+// it, and both the en-passant square and the square the pawn came from
+// must be empty.
+fn en_passant(board: &mut Board, sanitize: bool) -> FenResult {
+    let square = match board.game_state.en_passant {
+        Some(s) => s as Square,
+        None => return Ok(()),
+    };
+
+    let us = board.game_state.active_color as usize;
+    let opponent = us ^ 1;
+    let occupancy = occupancy(board);
+    let (ep_rank, pawn, origin) = if us == Sides::WHITE {
+        (Ranks::R6, square - 8, square + 8)
+    } else {
+        (Ranks::R3, square + 8, square - 8)
+    };
+
+    let possible = square / 8 == ep_rank
+        && board.bb_pieces[opponent][Pieces::PAWN] & BB_SQUARES[pawn] > 0
+        && occupancy & (BB_SQUARES[square] | BB_SQUARES[origin]) == 0;
+
+    match (possible, sanitize) {
+        (true, _) => Ok(()),
+        (false, true) => {
+            board.game_state.en_passant = None;
+            Ok(())
+        }
+        (false, false) => Err(FenError::EnPassantPawn),
+    }
+}
+
+// The side that is not to move can't be in check: the side to move would
+// be able to capture the king.
+fn opponent_in_check(board: &Board) -> FenResult {
+    let us = board.game_state.active_color as usize;
+    let opponent = us ^ 1;
+    let king = board.bb_pieces[opponent][Pieces::KING].trailing_zeros() as Square;
+
+    if attacked(board, king, us) {
+        Err(FenError::OpponentInCheck)
+    } else {
+        Ok(())
+    }
+}
+
+// Returns true if the square is attacked by the given side. The move
+// generator's attack tables can't be used here, because the board does not
+// have one, so the attacks are found by walking outward from the square.
+fn attacked(board: &Board, square: Square, attacker: Side) -> bool {
+    let pieces = &board.bb_pieces[attacker];
+    let occupancy = occupancy(board);
+    let file = (square % 8) as i8;
+    let rank = (square / 8) as i8;
+
+    // Returns the square at the given distance, if it's on the board.
+    let target = |(f, r): Step| -> Option<Bitboard> {
+        let (f, r) = (file + f, rank + r);
+        if (0..8).contains(&f) && (0..8).contains(&r) {
+            Some(BB_SQUARES[(r * 8 + f) as usize])
+        } else {
+            None
+        }
+    };
+
+    let jumps = |steps: &[Step], bb_attackers: Bitboard| {
+        steps
+            .iter()
+            .filter_map(|&step| target(step))
+            .any(|bb_square| bb_square & bb_attackers > 0)
+    };
+
+    let slides = |directions: &[Step], bb_attackers: Bitboard| {
+        directions.iter().any(|&(f, r)| {
+            let mut distance = 1;
+            while let Some(bb_square) = target((f * distance, r * distance)) {
+                if bb_square & bb_attackers > 0 {
+                    return true;
+                }
+                if bb_square & occupancy > 0 {
+                    break;
+                }
+                distance += 1;
+            }
+            false
+        })
+    };
+
+    // A white pawn attacks upward, so it stands one rank below the square
+    // it attacks. For black, this is the other way around.
+    let pawn_rank = if attacker == Sides::WHITE { -1 } else { 1 };
+    let bb_rooks = pieces[Pieces::ROOK] | pieces[Pieces::QUEEN];
+    let bb_bishops = pieces[Pieces::BISHOP] | pieces[Pieces::QUEEN];
+
+    jumps(&[(-1, pawn_rank), (1, pawn_rank)], pieces[Pieces::PAWN])
+        || jumps(&KNIGHT_STEPS, pieces[Pieces::KNIGHT])
+        || jumps(&KING_STEPS, pieces[Pieces::KING])
+        || slides(&ROOK_DIRECTIONS, bb_rooks)
+        || slides(&BISHOP_DIRECTIONS, bb_bishops)
+}
+
+// All the pieces on the board. The side bitboards are not set up yet.
+fn occupancy(board: &Board) -> Bitboard {
+    board
+        .bb_pieces
+        .iter()
+        .flatten()
+        .fold(0, |occupancy, bb| occupancy | bb)
+}
@@ -97,4 +97,19 @@ impl ZobristRandoms {
             None => self.rnd_en_passant[NrOf::SQUARES],
         }
     }
+
+    // Combine all the random numbers into one. If the random numbers or
+    // the order in which they are generated ever change, the signature
+    // changes as well. Keys stored on disk by an engine with a different
+    // signature can't be used.
+    pub fn signature(&self) -> u64 {
+        self.rnd_pieces
+            .iter()
+            .flatten()
+            .flatten()
+            .chain(self.rnd_castling.iter())
+            .chain(self.rnd_sides.iter())
+            .chain(self.rnd_en_passant.iter())
+            .fold(0, |signature, rnd| signature.rotate_left(1) ^ rnd)
+    }
 }
@@ -26,12 +26,16 @@ pub mod uci;
 
 use crate::{
     board::Board,
+    defs::Bitboard,
     engine::defs::{EngineOption, Information},
     movegen::defs::Move,
     search::defs::{SearchCurrentMove, SearchStats, SearchSummary},
 };
 use crossbeam_channel::Sender;
-use std::sync::{Arc, Mutex};
+use std::{
+    fmt::{self, Display},
+    sync::{Arc, Mutex},
+};
 use uci::UciReport;
 
 // These are the types of communication the engine is capable of.
@@ -66,13 +70,45 @@ pub enum CommControl {
     SearchStats(SearchStats),          // Transmit search Statistics.
     InfoString(String),                // Transmit general information.
     BestMove(Move),                    // Transmit the engine's best move.
+    PerftDivide(Vec<(Move, u64)>),     // Transmit perft leaf nodes per move.
 
     // Output to screen when running in a terminal window.
     PrintBoard,
+    PrintPosition(PositionInfo),
     PrintHistory,
     PrintHelp,
 }
 
+// Information about the position on the board that the Comm module can't
+// determine by itself, because it needs the move generator or evaluation.
+// The position itself is included as a diagram and as FEN, both made from
+// the engine's board, so it can't change between the engine collecting the
+// information and the Comm module printing it.
+#[derive(PartialEq)]
+pub struct PositionInfo {
+    pub diagram: String,
+    pub fen: String,
+    pub checkers: Bitboard,
+    pub eval: i16,
+    pub legal_moves: u8,
+}
+
+// Reasons why the Comm module can't receive commands anymore.
+#[derive(Debug)]
+pub enum CommError {
+    Eof,        // Input was closed.
+    Io(String), // Input could not be read.
+}
+
+impl Display for CommError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CommError::Eof => write!(f, "Input closed."),
+            CommError::Io(e) => write!(f, "Reading input failed: {e}"),
+        }
+    }
+}
+
 // These are the commands a Comm module can create and send back to the
 // engine in the main thread.
 #[derive(PartialEq, Clone)]
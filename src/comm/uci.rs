@@ -23,16 +23,15 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 // This file implements the UCI communication module.
 
-use super::{CommControl, CommReport, CommType, IComm};
+use super::{CommControl, CommError, CommReport, CommType, IComm, PositionInfo};
 use crate::{
-    board::Board,
+    board::{defs::SQUARE_NAME, Board},
     defs::{About, FEN_START_POSITION},
     engine::defs::{EngineOption, EngineOptionName, ErrFatal, Information, UiElement},
-    misc::print,
+    misc::{bits, print},
     movegen::defs::Move,
-    search::defs::{
-        GameTime, SearchCurrentMove, SearchStats, SearchSummary, CHECKMATE, CHECKMATE_THRESHOLD,
-    },
+    search::defs::{GameTime, SearchCurrentMove, SearchStats, SearchSummary},
+    tools::bench::{BENCH_DEPTH_DEFAULT, BENCH_HASH_DEFAULT},
 };
 use crossbeam_channel::{self, Sender};
 use std::{
@@ -56,11 +55,14 @@ pub enum UciReport {
     GoMoveTime(u128),
     GoNodes(usize),
     GoGameTime(GameTime),
+    GoPerft(i8),
     Stop,
+    Bench(i8, usize),
     Quit,
 
     // Custom commands
     Board,
+    Display,
     History,
     Eval,
     Help,
@@ -141,13 +143,18 @@ impl Uci {
 
             // Keep running as long as 'quit' is not detected.
             while !quit {
-                // Get data from stdin.
-                io::stdin()
-                    .read_line(&mut t_incoming_data)
-                    .expect(ErrFatal::READ_IO);
-
-                // Create a report from the incoming data.
-                let new_report = Uci::create_report(&t_incoming_data);
+                // Get data from stdin, and create a report from it. If
+                // stdin is closed or can't be read anymore, no further
+                // commands can arrive, so this is handled as "quit".
+                let new_report = match Uci::read_line(&mut t_incoming_data) {
+                    Ok(()) => Uci::create_report(&t_incoming_data),
+                    Err(e) => {
+                        if let CommError::Io(_) = e {
+                            println!("info string {e}");
+                        }
+                        CommReport::Uci(UciReport::Quit)
+                    }
+                };
 
                 // Check if the created report is valid, so it is something
                 // the engine will understand.
@@ -202,9 +209,11 @@ impl Uci {
                     CommControl::SearchStats(stats) => Uci::search_stats(&stats),
                     CommControl::InfoString(msg) => Uci::info_string(&msg),
                     CommControl::BestMove(bm) => Uci::best_move(&bm),
+                    CommControl::PerftDivide(result) => Uci::perft_divide(&result),
 
                     // Custom prints for use in the console.
                     CommControl::PrintBoard => Uci::print_board(&t_board),
+                    CommControl::PrintPosition(info) => Uci::print_position(&info),
                     CommControl::PrintHistory => Uci::print_history(&t_board),
                     CommControl::PrintHelp => Uci::print_help(),
 
@@ -222,6 +231,15 @@ impl Uci {
 
 // Private functions for this module.
 impl Uci {
+    // Read one line from stdin. Zero bytes read means stdin was closed.
+    fn read_line(buffer: &mut String) -> Result<(), CommError> {
+        match io::stdin().read_line(buffer) {
+            Ok(0) => Err(CommError::Eof),
+            Ok(_) => Ok(()),
+            Err(e) => Err(CommError::Io(e.to_string())),
+        }
+    }
+
     // This function turns the incoming data into UciReports which the
     // engine is able to understand and react to.
     fn create_report(input: &str) -> CommReport {
@@ -242,15 +260,34 @@ impl Uci {
 
             // Custom commands
             cmd if cmd == "board" => CommReport::Uci(UciReport::Board),
+            cmd if cmd == "d" => CommReport::Uci(UciReport::Display),
             cmd if cmd == "history" => CommReport::Uci(UciReport::History),
             cmd if cmd == "eval" => CommReport::Uci(UciReport::Eval),
             cmd if cmd == "help" => CommReport::Uci(UciReport::Help),
+            cmd if cmd == "bench" || cmd.starts_with("bench ") => Uci::parse_bench(&cmd),
 
             // Everything else is ignored.
             _ => CommReport::Uci(UciReport::Unknown),
         }
     }
 
+    // Parse "bench [depth] [hash]". Missing values are set to the
+    // defaults; values that are not numbers make the command invalid.
+    fn parse_bench(cmd: &str) -> CommReport {
+        let parts: Vec<&str> = cmd.split_whitespace().collect();
+        let depth = parts
+            .get(1)
+            .map_or(Ok(BENCH_DEPTH_DEFAULT), |d| d.parse::<i8>());
+        let hash = parts
+            .get(2)
+            .map_or(Ok(BENCH_HASH_DEFAULT), |h| h.parse::<usize>());
+
+        match (depth, hash) {
+            (Ok(d), Ok(h)) => CommReport::Uci(UciReport::Bench(d, h)),
+            _ => CommReport::Uci(UciReport::Unknown),
+        }
+    }
+
     fn parse_position(cmd: &str) -> CommReport {
         enum Tokens {
             Nothing,
@@ -298,6 +335,7 @@ impl Uci {
             WInc,
             BInc,
             MovesToGo,
+            Perft,
         }
 
         let parts: Vec<String> = cmd.split_whitespace().map(|s| s.to_string()).collect();
@@ -317,6 +355,7 @@ impl Uci {
                 t if t == "winc" => token = Tokens::WInc,
                 t if t == "binc" => token = Tokens::BInc,
                 t if t == "movestogo" => token = Tokens::MovesToGo,
+                t if t == "perft" => token = Tokens::Perft,
                 _ => match token {
                     Tokens::Nothing => (),
                     Tokens::Depth => {
@@ -338,12 +377,11 @@ impl Uci {
                     Tokens::BTime => game_time.btime = p.parse::<u128>().unwrap_or(0),
                     Tokens::WInc => game_time.winc = p.parse::<u128>().unwrap_or(0),
                     Tokens::BInc => game_time.binc = p.parse::<u128>().unwrap_or(0),
-                    Tokens::MovesToGo => {
-                        game_time.moves_to_go = if let Ok(x) = p.parse::<usize>() {
-                            Some(x)
-                        } else {
-                            None
-                        }
+                    Tokens::MovesToGo => game_time.moves_to_go = p.parse::<usize>().ok(),
+                    Tokens::Perft => {
+                        let depth = p.parse::<i8>().unwrap_or(1);
+                        report = CommReport::Uci(UciReport::GoPerft(depth));
+                        break; // break for-loop: nothing more to do.
                     }
                 }, // end match token
             } // end match p
@@ -383,7 +421,7 @@ impl Uci {
                 t if t == "value" => token = Tokens::Value,
                 _ => match token {
                     Tokens::Name => name = format!("{name} {p}"),
-                    Tokens::Value => value = p.to_lowercase(),
+                    Tokens::Value => value = format!("{value} {p}"),
                     Tokens::Nothing => (),
                 },
             }
@@ -391,12 +429,7 @@ impl Uci {
 
         // Determine which engine option name to send.
         if !name.is_empty() {
-            name = name.to_lowercase().trim().to_string();
-            match &name[..] {
-                "hash" => eon = EngineOptionName::Hash(value),
-                "clear hash" => eon = EngineOptionName::ClearHash,
-                _ => (),
-            }
+            eon = EngineOptionName::new(&name, &value);
         }
 
         // Send the engine option name with value to the engine thread.
@@ -418,6 +451,8 @@ impl Uci {
             let ui_element = match o.ui_element {
                 UiElement::Spin => String::from("type spin"),
                 UiElement::Button => String::from("type button"),
+                UiElement::Check => String::from("type check"),
+                UiElement::String => String::from("type string"),
             };
 
             let value_default = if let Some(v) = &o.default {
@@ -456,24 +491,9 @@ impl Uci {
 
     fn search_summary(s: &SearchSummary) {
         // If mate found, report this; otherwise report normal score.
-        let score = if (s.cp.abs() >= CHECKMATE_THRESHOLD) && (s.cp.abs() < CHECKMATE) {
-            // Number of plies to mate.
-            let ply = CHECKMATE - s.cp.abs();
-
-            // Check if the number of ply's is odd
-            let is_odd = ply % 2 == 1;
-
-            // Calculate number of moves to mate
-            let moves = if is_odd { (ply + 1) / 2 } else { ply / 2 };
-
-            // If the engine is being mated itself, flip the score.
-            let flip = if s.cp < 0 { -1 } else { 1 };
-
-            // Report the mate
-            format!("mate {}", moves * flip)
-        } else {
-            // Report the normal score if there's no mate detected.
-            format!("cp {}", s.cp)
+        let score = match s.moves_to_mate() {
+            Some(moves) => format!("mate {moves}"),
+            None => format!("cp {}", s.cp),
         };
 
         // Report depth and seldepth (if available).
@@ -533,24 +553,50 @@ impl Uci {
 // implements handling of custom commands. These are mostly used when using
 // the UCI protocol directly in a terminal window.
 impl Uci {
+    // Print the perft leaf nodes per root move, followed by the total, in
+    // the same format as most other engines use. This makes it easy to
+    // compare the output when debugging move generation.
+    fn perft_divide(result: &[(Move, u64)]) {
+        let mut total: u64 = 0;
+
+        for (m, leaf_nodes) in result.iter() {
+            println!("{}: {}", m.as_string(), leaf_nodes);
+            total += leaf_nodes;
+        }
+
+        println!();
+        println!("Nodes searched: {total}");
+    }
+
     fn print_board(board: &Arc<Mutex<Board>>) {
         print::position(&board.lock().expect(ErrFatal::LOCK), None);
     }
 
-    fn print_history(board: &Arc<Mutex<Board>>) {
-        let mtx_board = board.lock().expect(ErrFatal::LOCK);
-        let length = mtx_board.history.len();
-
-        if length == 0 {
-            println!("No history available.");
+    // Print the board, followed by everything needed to check or reproduce
+    // the position: FEN, pieces giving check, static evaluation, and the
+    // number of legal moves.
+    fn print_position(info: &PositionInfo) {
+        let mut checkers: Vec<&str> = Vec::new();
+        let mut bb_checkers = info.checkers;
+        while bb_checkers > 0 {
+            checkers.push(SQUARE_NAME[bits::next(&mut bb_checkers)]);
         }
+        let checkers = if checkers.is_empty() {
+            String::from("-")
+        } else {
+            checkers.join(" ")
+        };
 
-        for i in 0..length {
-            let h = mtx_board.history.get_ref(i);
-            println!("{:<3}| ply: {} {}", i, i + 1, h.as_string());
-        }
+        print!("{}", info.diagram);
+        println!("{:<20}{}", "FEN:", info.fen);
+        println!("{:<20}{}", "Checkers:", checkers);
+        println!("{:<20}{} (side to move)", "Static eval:", info.eval);
+        println!("{:<20}{}", "Legal moves:", info.legal_moves);
+        println!();
+    }
 
-        std::mem::drop(mtx_board);
+    fn print_history(board: &Arc<Mutex<Board>>) {
+        print::history(&board.lock().expect(ErrFatal::LOCK));
     }
 
     fn print_help() {
@@ -562,8 +608,11 @@ impl Uci {
         println!("================================================================");
         println!("help      :   This help information.");
         println!("board     :   Print the current board state.");
+        println!("d         :   Print the board with FEN, checkers, eval and moves.");
         println!("history   :   Print a list of past board states.");
         println!("eval      :   Print evaluation for side to move.");
+        println!("go perft n:   Print perft leaf nodes per move for depth n.");
+        println!("bench d h :   Run the search benchmark (optional depth and hash).");
         println!("exit      :   Quit/Exit the engine.");
         println!();
     }
@@ -67,23 +67,28 @@ impl Castling {
     pub const BK: u8 = 4;
     pub const BQ: u8 = 8;
     pub const ALL: u8 = 15;
+
+    // Castling permission for each side and wing: [side][wing].
+    pub const RIGHTS: [[u8; 2]; Sides::BOTH] = [[Self::WK, Self::WQ], [Self::BK, Self::BQ]];
 }
 
 pub const EMPTY: u64 = 0;
-pub const MAX_GAME_MOVES: usize = 2048;
 pub const MAX_LEGAL_MOVES: u8 = 255;
 pub const MAX_PLY: i8 = 125;
 pub const MAX_MOVE_RULE: u8 = 100; // 50/75 move rule
 
-// Define errors
-pub type EngineRunResult = Result<(), u8>;
-pub const ENGINE_RUN_ERRORS: [&str; 8] = [
-    "FEN: Must have six parts",
-    "FEN: Pieces and squares incorrect",
-    "FEN: Color selection incorrect",
-    "FEN: Castling permissions incorrect",
-    "FEN: En-passant square incorrect",
-    "FEN: Half-move clock incorrect",
-    "FEN: Full-move number incorrect",
-    "XBoard not yet implemented.",
-];
+// This struct holds messages that are reported on fatal engine errors.
+// These should never happen; if they do the engine is in an unknown state,
+// and it will panic without trying any recovery whatsoever.
+pub struct ErrFatal;
+impl ErrFatal {
+    pub const CREATE_COMM: &'static str = "Comm creation failed.";
+    pub const NEW_GAME: &'static str = "Setting up new game failed.";
+    pub const LOCK: &'static str = "Lock failed.";
+    pub const WRITE_IO: &'static str = "Writing I/O failed.";
+    pub const HANDLE: &'static str = "Broken handle.";
+    pub const THREAD: &'static str = "Thread has failed.";
+    pub const CHANNEL: &'static str = "Broken channel.";
+    pub const NO_INFO_RX: &'static str = "No incoming Info channel.";
+    pub const TT_SIZE: &'static str = "TT too large.";
+}
@@ -24,26 +24,30 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 mod about;
 mod comm_reports;
 pub mod defs;
+mod epd_solve;
 mod main_loop;
 mod search_reports;
-mod transposition;
 mod utils;
 
 use crate::{
     board::Board,
-    comm::{uci::Uci, CommControl, CommType, IComm},
-    defs::EngineRunResult,
+    comm::{
+        uci::{Uci, UciReport},
+        CommControl, CommType, IComm,
+    },
     engine::defs::{
-        EngineOption, EngineOptionDefaults, EngineOptionName, ErrFatal, Information, Settings,
-        UiElement,
+        EngineError, EngineOption, EngineOptionDefaults, EngineOptionName, EngineRunResult,
+        ErrFatal, Information, PerftData, SearchData, Settings, UiElement, TT,
+    },
+    movegen::{defs::MoveGenMode, MoveGenerator},
+    search::{
+        defs::{SearchControl, SearchReport},
+        Search,
     },
-    misc::{cmdline::CmdLine, perft},
-    movegen::MoveGenerator,
-    search::{defs::SearchControl, Search},
+    tools::{bench, cmdline::CmdLine, perft},
 };
 use crossbeam_channel::Receiver;
 use std::sync::{Arc, Mutex};
-use transposition::{PerftData, SearchData, TT};
 
 #[cfg(feature = "extra")]
 use crate::{
@@ -54,18 +58,22 @@ use crate::{
 // This struct holds the chess engine and its functions, so they are not
 // all seperate entities in the global space.
 pub struct Engine {
-    quit: bool,                             // Flag that will quit the main thread.
-    settings: Settings,                     // Struct holding all the settings.
-    options: Arc<Vec<EngineOption>>,        // Engine options exported to the GUI
-    cmdline: CmdLine,                       // Command line interpreter.
-    comm: Box<dyn IComm>,                   // Communications (active).
-    board: Arc<Mutex<Board>>,               // This is the main engine board.
-    tt_perft: Arc<Mutex<TT<PerftData>>>,    // TT for running perft.
-    tt_search: Arc<Mutex<TT<SearchData>>>,  // TT for search information.
-    mg: Arc<MoveGenerator>,                 // Move Generator.
-    info_rx: Option<Receiver<Information>>, // Receiver for incoming information.
-    search: Search,                         // Search object (active).
-    tmp_no_xboard: bool,                    // Temporary variable to disable xBoard
+    quit: bool,                                // Flag that will quit the main thread.
+    settings: Settings,                        // Struct holding all the settings.
+    options: Arc<Vec<EngineOption>>,           // Engine options exported to the GUI
+    cmdline: CmdLine,                          // Command line interpreter.
+    comm: Box<dyn IComm>,                      // Communications (active).
+    board: Arc<Mutex<Board>>,                  // This is the main engine board.
+    tt_perft: Arc<Mutex<TT<PerftData>>>,       // TT for running perft.
+    tt_search: Arc<Mutex<TT<SearchData>>>,     // TT for search information.
+    mg: Arc<MoveGenerator>,                    // Move Generator.
+    info_rx: Option<Receiver<Information>>,    // Receiver for incoming information.
+    search_rx: Option<Receiver<SearchReport>>, // Receiver for search reports.
+    search: Search,                            // Search object (active).
+    searching: bool,                           // A search is running.
+    search_id: usize,                          // Id of the last search started.
+    deferred: Vec<UciReport>,                  // Commands waiting for the search.
+    tmp_no_xboard: bool,                       // Temporary variable to disable xBoard
 }
 
 impl Engine {
@@ -114,6 +122,48 @@ impl Engine {
                 None,
                 None,
             ),
+            EngineOption::new(
+                EngineOptionName::HASH_FILE,
+                UiElement::String,
+                Some(EngineOptionDefaults::HASH_FILE_NONE.to_string()),
+                None,
+                None,
+            ),
+            EngineOption::new(
+                EngineOptionName::SAVE_HASH,
+                UiElement::Button,
+                None,
+                None,
+                None,
+            ),
+            EngineOption::new(
+                EngineOptionName::LOAD_HASH,
+                UiElement::Button,
+                None,
+                None,
+                None,
+            ),
+            EngineOption::new(
+                EngineOptionName::CHESS960,
+                UiElement::Check,
+                Some(String::from("false")),
+                None,
+                None,
+            ),
+            EngineOption::new(
+                EngineOptionName::SANITIZE_FEN,
+                UiElement::Check,
+                Some(String::from("false")),
+                None,
+                None,
+            ),
+            EngineOption::new(
+                EngineOptionName::EVAL_FILE,
+                UiElement::String,
+                Some(EngineOptionDefaults::EVAL_FILE_NONE.to_string()),
+                None,
+                None,
+            ),
         ];
 
         // Initialize correct TT.
@@ -134,6 +184,7 @@ impl Engine {
                 threads,
                 quiet,
                 tt_size,
+                hash_file: String::new(),
             },
             options: Arc::new(options),
             cmdline,
@@ -143,7 +194,11 @@ impl Engine {
             tt_perft,
             tt_search,
             info_rx: None,
+            search_rx: None,
             search: Search::new(),
+            searching: false,
+            search_id: 0,
+            deferred: Vec::new(),
             tmp_no_xboard: is_xboard,
         }
     }
@@ -153,7 +208,7 @@ impl Engine {
         // This is temporary. Quit the engine immediately if anyone tries
         // to start it in XBoard mode, as this is not implemented yet.
         if self.tmp_no_xboard {
-            return Err(7);
+            return Err(EngineError::XBoard);
         }
 
         self.print_ascii_logo();
@@ -166,16 +221,68 @@ impl Engine {
         // Run a specific action if requested...
         let mut action_requested = false;
 
-        // Run perft if requested.
+        // Run perft if requested, with the selected move generator, or
+        // cross-validate both generators against each other.
         if self.cmdline.perft() > 0 {
             action_requested = true;
-            perft::run(
-                self.board.clone(),
-                self.cmdline.perft(),
-                Arc::clone(&self.mg),
-                Arc::clone(&self.tt_perft),
-                self.settings.tt_size > 0,
-            );
+            match &self.cmdline.movegen()[..] {
+                MoveGenMode::COMPARE => perft::run_compare(
+                    self.board.clone(),
+                    self.cmdline.perft(),
+                    Arc::clone(&self.mg),
+                ),
+                _ => perft::run(
+                    self.board.clone(),
+                    self.cmdline.perft(),
+                    Arc::clone(&self.mg),
+                    Arc::clone(&self.tt_perft),
+                    self.settings.tt_size > 0,
+                    self.movegen_mode(),
+                    self.settings.threads,
+                ),
+            }
+        }
+
+        // Run the search benchmark if requested.
+        if let Some((depth, hash)) = self.cmdline.bench() {
+            action_requested = true;
+            bench::run(depth, hash, Arc::clone(&self.mg));
+        }
+
+        // Play a self-play match if requested.
+        if let Some(args) = self.cmdline.selfplay() {
+            action_requested = true;
+            self.selfplay(args)?;
+        }
+
+        // Play a gauntlet against external UCI engines if requested.
+        if let Some(args) = self.cmdline.gauntlet() {
+            action_requested = true;
+            self.gauntlet(args)?;
+        }
+
+        // Generate training data if requested.
+        if let Some(args) = self.cmdline.datagen() {
+            action_requested = true;
+            self.datagen(args)?;
+        }
+
+        // Tune the evaluation if requested.
+        if let Some(args) = self.cmdline.tune() {
+            action_requested = true;
+            self.tune(args)?;
+        }
+
+        // Run a perft test suite from an EPD file if requested.
+        if let Some(file) = self.cmdline.perft_suite() {
+            action_requested = true;
+            self.perft_suite(&file)?;
+        }
+
+        // Run a tactical test suite from an EPD file if requested.
+        if let Some(file) = self.cmdline.epd_solve() {
+            action_requested = true;
+            self.epd_solve(&file, self.cmdline.movetime())?;
         }
 
         // === Only available with "extra" features enabled. ===
@@ -201,7 +308,13 @@ impl Engine {
                 .expect(ErrFatal::LOCK)
                 .resize(self.settings.tt_size);
             self.tt_search.lock().expect(ErrFatal::LOCK).resize(0);
-            testsuite::run(Arc::clone(&self.tt_perft), self.settings.tt_size > 0);
+            testsuite::run(
+                Arc::clone(&self.tt_perft),
+                self.settings.tt_size > 0,
+                self.movegen_mode(),
+                self.cmdline.movegen() == MoveGenMode::COMPARE,
+                self.settings.threads,
+            );
         }
         // =====================================================
 
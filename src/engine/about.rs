@@ -53,7 +53,7 @@ impl Engine {
         let threads = if s.threads == 1 {
             String::from("1")
         } else {
-            format!("{} (unused, always 1)", s.threads)
+            format!("{} (perft only)", s.threads)
         };
 
         println!("{:<10} {} {}", "Engine:", About::ENGINE, About::VERSION);
@@ -63,6 +63,7 @@ impl Engine {
         println!("{:<10} {bits}-bit", "Type:");
         println!("{:<10} {hash}", "Hash:");
         println!("{:<10} {threads}", "Threads:");
+        println!("{:<10} {}", "Protocol:", self.comm.get_protocol_name());
 
         #[cfg(debug_assertions)]
         println!("{NOTICE_DEBUG_MODE}");
@@ -23,15 +23,19 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use super::{
     defs::{ErrFatal, ErrNormal},
-    Engine,
+    utils, Engine,
 };
 use crate::{
-    comm::{uci::UciReport, CommControl, CommReport},
+    comm::{uci::UciReport, CommControl, CommReport, PositionInfo},
     defs::FEN_START_POSITION,
-    engine::defs::EngineOptionName,
-    evaluation::evaluate_position,
+    engine::defs::{EngineOptionDefaults, EngineOptionName},
+    evaluation::{evaluate_position, nnue::Network},
+    misc::print,
+    movegen::defs::{MoveList, MoveType},
     search::defs::{SearchControl, SearchMode, SearchParams, OVERHEAD},
+    tools::{bench, perft},
 };
+use std::sync::Arc;
 
 // This block implements handling of incoming information, which will be in
 // the form of either Comm or Search reports.
@@ -45,6 +49,36 @@ impl Engine {
 
     // Handles "Uci" Comm reports sent by the UCI-module.
     fn comm_reports_uci(&mut self, u: &UciReport) {
+        // While searching, the search thread is using the TT. Changing
+        // options or starting a new game would resize or clear it under the
+        // search's feet, and a new search or benchmark can't start before
+        // the running one is done, so these commands wait until the search
+        // has finished. Once a command is waiting, all commands after it
+        // wait as well, so they are still handled in the order they came
+        // in. "stop" and "quit" are never held back, as they are needed to
+        // end the search. "isready" is answered immediately during a search,
+        // unless there are commands waiting: then it waits behind them, so
+        // "readyok" is only sent after pending resizes and clears are done.
+        let must_wait = match u {
+            UciReport::Stop | UciReport::Quit => false,
+            _ if !self.deferred.is_empty() => true,
+            UciReport::SetOption(_)
+            | UciReport::UciNewGame
+            | UciReport::GoInfinite
+            | UciReport::GoDepth(_)
+            | UciReport::GoMoveTime(_)
+            | UciReport::GoNodes(_)
+            | UciReport::GoGameTime(_)
+            | UciReport::GoPerft(_)
+            | UciReport::Bench(..) => self.searching,
+            _ => false,
+        };
+
+        if must_wait {
+            self.deferred.push(u.clone());
+            return;
+        }
+
         // Setup default variables.
         let mut sp = SearchParams::new();
         sp.quiet = self.settings.quiet;
@@ -78,65 +112,183 @@ impl Engine {
                         self.tt_search.lock().expect(ErrFatal::LOCK).clear()
                     }
 
-                    EngineOptionName::Nothing => (),
-                };
-            }
+                    EngineOptionName::HashFile(value) => {
+                        self.settings.hash_file = if value == EngineOptionDefaults::HASH_FILE_NONE {
+                            String::new()
+                        } else {
+                            value.clone()
+                        };
+                    }
 
-            UciReport::Position(fen, moves) => {
-                let fen_result = self.board.lock().expect(ErrFatal::LOCK).fen_read(Some(fen));
+                    // The hash is saved to, or loaded from, the file set
+                    // in the HashFile option. Loading it gives the TT the
+                    // size it had when it was saved.
+                    EngineOptionName::SaveHash | EngineOptionName::LoadHash => {
+                        let file = &self.settings.hash_file;
+                        let signature =
+                            self.board.lock().expect(ErrFatal::LOCK).zobrist_signature();
+                        let mut tt = self.tt_search.lock().expect(ErrFatal::LOCK);
+
+                        let msg = if file.is_empty() {
+                            String::from(ErrNormal::NO_HASH_FILE)
+                        } else if *option == EngineOptionName::SaveHash {
+                            match tt.save(file, signature) {
+                                Ok(()) => format!("Hash saved to {file}."),
+                                Err(e) => format!("{e}. Hash not saved."),
+                            }
+                        } else {
+                            match tt.load(file, signature) {
+                                Ok(()) => {
+                                    format!("Hash loaded from {file} ({} MB).", tt.megabytes())
+                                }
+                                Err(e) => format!("{e}. Hash not loaded."),
+                            }
+                        };
 
-                if fen_result.is_ok() {
-                    for m in moves.iter() {
-                        let ok = self.execute_move(m.clone());
-                        if !ok {
-                            let msg = format!("{}: {}", m, ErrNormal::NOT_LEGAL);
+                        std::mem::drop(tt);
+                        self.comm.send(CommControl::InfoString(msg));
+                    }
+
+                    // In Chess960 mode, castling moves are sent and received
+                    // as the king capturing its own rook.
+                    EngineOptionName::Chess960(value) => {
+                        if let Ok(v) = value.parse::<bool>() {
+                            self.board.lock().expect(ErrFatal::LOCK).set_chess960(v);
+                        } else {
+                            let msg = String::from(ErrNormal::NOT_BOOL);
                             self.comm.send(CommControl::InfoString(msg));
-                            break;
                         }
                     }
-                }
 
-                if fen_result.is_err() {
-                    let msg = ErrNormal::FEN_FAILED.to_string();
+                    // When sanitizing, castling rights and en-passant
+                    // squares in a position that can't be correct are
+                    // dropped, instead of the position being rejected.
+                    EngineOptionName::SanitizeFen(value) => {
+                        if let Ok(v) = value.parse::<bool>() {
+                            self.board.lock().expect(ErrFatal::LOCK).set_sanitize_fen(v);
+                        } else {
+                            let msg = String::from(ErrNormal::NOT_BOOL);
+                            self.comm.send(CommControl::InfoString(msg));
+                        }
+                    }
+
+                    // Load an NNUE network. If no file is given, or it
+                    // can't be loaded, the engine uses the PSQT evaluation.
+                    EngineOptionName::EvalFile(value) => {
+                        let network =
+                            if value.is_empty() || value == EngineOptionDefaults::EVAL_FILE_NONE {
+                                None
+                            } else {
+                                match Network::load(value) {
+                                    Ok(network) => Some(Arc::new(network)),
+                                    Err(e) => {
+                                        let msg = format!("{e}. {}", ErrNormal::EVAL_FILE);
+                                        self.comm.send(CommControl::InfoString(msg));
+                                        None
+                                    }
+                                }
+                            };
+
+                        self.board
+                            .lock()
+                            .expect(ErrFatal::LOCK)
+                            .set_network(network);
+
+                        // The static evaluations in the TT came from the
+                        // previous evaluation, so they can't be used anymore.
+                        self.tt_search.lock().expect(ErrFatal::LOCK).clear();
+                    }
+
+                    EngineOptionName::Nothing => (),
+                };
+            }
+
+            // The position is set up all at once or not at all: if the FEN
+            // or one of the moves is wrong, the previous position is put
+            // back, so the engine is never left with a half-applied game.
+            // The moves of the game are shared between the engine's board
+            // and the copy the search makes of it.
+            UciReport::Position(fen, moves) => {
+                let mut board = self.board.lock().expect(ErrFatal::LOCK);
+                let result = utils::set_position(&mut board, &self.mg, fen, moves);
+                std::mem::drop(board);
+
+                if let Err(msg) = result {
                     self.comm.send(CommControl::InfoString(msg));
                 }
             }
 
             UciReport::GoInfinite => {
                 sp.search_mode = SearchMode::Infinite;
-                self.search.send(SearchControl::Start(sp));
+                self.start_search(sp);
             }
 
             UciReport::GoDepth(depth) => {
                 sp.depth = *depth;
                 sp.search_mode = SearchMode::Depth;
-                self.search.send(SearchControl::Start(sp));
+                self.start_search(sp);
             }
 
             UciReport::GoMoveTime(msecs) => {
                 sp.move_time = *msecs - (OVERHEAD as u128);
                 sp.search_mode = SearchMode::MoveTime;
-                self.search.send(SearchControl::Start(sp));
+                self.start_search(sp);
             }
 
             UciReport::GoNodes(nodes) => {
                 sp.nodes = *nodes;
                 sp.search_mode = SearchMode::Nodes;
-                self.search.send(SearchControl::Start(sp));
+                self.start_search(sp);
             }
 
             UciReport::GoGameTime(gt) => {
                 sp.game_time = *gt;
                 sp.search_mode = SearchMode::GameTime;
-                self.search.send(SearchControl::Start(sp));
+                self.start_search(sp);
             }
 
+            // Perft runs on a copy of the current position. The perft TT
+            // is not allocated in UCI mode, so it is not used.
+            UciReport::GoPerft(depth) => {
+                let mut board = self.board.lock().expect(ErrFatal::LOCK).clone();
+                let depth = (*depth).max(1);
+                let mode = self.movegen_mode();
+                let threads = self.settings.threads;
+                let tt = &self.tt_perft;
+                let result = perft::divide(&mut board, depth, &self.mg, tt, false, mode, threads);
+                self.comm.send(CommControl::PerftDivide(result));
+            }
+
+            // The benchmark uses its own board and TT, and runs in the
+            // engine thread. The engine doesn't react to commands until
+            // it is done.
+            UciReport::Bench(depth, hash) => bench::run(*depth, *hash, Arc::clone(&self.mg)),
+
             UciReport::Stop => self.search.send(SearchControl::Stop),
             UciReport::Quit => self.quit(),
 
             // Custom commands
             UciReport::Board => self.comm.send(CommControl::PrintBoard),
             UciReport::History => self.comm.send(CommControl::PrintHistory),
+            UciReport::Display => {
+                let board = self.board.lock().expect(ErrFatal::LOCK);
+                let us = board.us();
+                let king_square = board.king_square(us);
+                let occupancy = board.occupancy();
+                let mut ml = MoveList::new();
+                self.mg.generate_legal_moves(&board, &mut ml, MoveType::All);
+
+                let info = PositionInfo {
+                    diagram: print::position_as_string(&board, None),
+                    fen: board.fen_write(),
+                    checkers: self.mg.attackers_to(&board, us ^ 1, king_square, occupancy),
+                    eval: evaluate_position(&board),
+                    legal_moves: ml.len(),
+                };
+                std::mem::drop(board);
+
+                self.comm.send(CommControl::PrintPosition(info));
+            }
             UciReport::Eval => {
                 let e = evaluate_position(&self.board.lock().expect(ErrFatal::LOCK));
                 let msg = format!("Evaluation: {e} centipawns");
@@ -146,4 +298,29 @@ impl Engine {
             UciReport::Unknown => (),
         }
     }
+
+    // Start a search. The engine keeps track of it, so it knows which
+    // commands have to wait until the search is finished. Each search gets
+    // its own id, which the search reports back when it finishes.
+    fn start_search(&mut self, sp: SearchParams) {
+        self.search_id += 1;
+        self.searching = true;
+        self.search.send(SearchControl::Start(self.search_id, sp));
+    }
+
+    // A search has finished. If it is the one that was started last,
+    // handle the commands that came in while it was running, in the order
+    // they were received. One of those may start a new search; then the
+    // commands after it wait again. A report from an older search is
+    // ignored, so it can't end the current one.
+    pub fn search_finished(&mut self, id: usize) {
+        if id != self.search_id {
+            return;
+        }
+
+        self.searching = false;
+        for u in std::mem::take(&mut self.deferred) {
+            self.comm_reports_uci(&u);
+        }
+    }
 }
@@ -21,29 +21,20 @@ You should have received a copy of the GNU General Public License along
 with this program.  If not, see <http://www.gnu.org/licenses/>.
 ======================================================================= */
 
-pub use crate::engine::transposition::{HashFlag, PerftData, SearchData, TT};
-use crate::{comm::CommReport, search::defs::SearchReport};
-
-// This struct holds messages that are reported on fatal engine errors.
-// These should never happen; if they do the engine is in an unknown state,
-// and it will panic without trying any recovery whatsoever.
-pub struct ErrFatal;
-impl ErrFatal {
-    pub const CREATE_COMM: &'static str = "Comm creation failed.";
-    pub const NEW_GAME: &'static str = "Setting up new game failed.";
-    pub const LOCK: &'static str = "Lock failed.";
-    pub const READ_IO: &'static str = "Reading I/O failed.";
-    pub const HANDLE: &'static str = "Broken handle.";
-    pub const THREAD: &'static str = "Thread has failed.";
-    pub const CHANNEL: &'static str = "Broken channel.";
-    pub const NO_INFO_RX: &'static str = "No incoming Info channel.";
-}
+use crate::{board::FenError, comm::CommReport, search::defs::SearchReport};
+pub use crate::{
+    defs::ErrFatal,
+    transposition::{PerftData, SearchData, TT},
+};
+use std::fmt::{self, Display};
 
 pub struct ErrNormal;
 impl ErrNormal {
-    pub const NOT_LEGAL: &'static str = "This is not a legal move in this position.";
     pub const NOT_INT: &'static str = "The value given was not an integer.";
-    pub const FEN_FAILED: &'static str = "Setting up FEN failed. Board not changed.";
+    pub const NOT_BOOL: &'static str = "The value given was not true or false.";
+    pub const NOT_CHANGED: &'static str = "Board not changed.";
+    pub const EVAL_FILE: &'static str = "Using the PSQT evaluation.";
+    pub const NO_HASH_FILE: &'static str = "No hash file was set.";
 }
 
 // This struct holds the engine's settings.
@@ -51,6 +42,7 @@ pub struct Settings {
     pub threads: usize,
     pub quiet: bool,
     pub tt_size: usize,
+    pub hash_file: String,
 }
 
 // This enum provides informatin to the engine, with regard to incoming
@@ -64,6 +56,8 @@ pub enum Information {
 pub enum UiElement {
     Spin,
     Button,
+    Check,
+    String,
 }
 
 pub struct EngineOption {
@@ -96,11 +90,41 @@ impl EngineOption {
 pub enum EngineOptionName {
     Hash(String),
     ClearHash,
+    HashFile(String),
+    SaveHash,
+    LoadHash,
+    Chess960(String),
+    SanitizeFen(String),
+    EvalFile(String),
     Nothing,
 }
 impl EngineOptionName {
     pub const HASH: &'static str = "Hash";
     pub const CLEAR_HASH: &'static str = "Clear Hash";
+    pub const HASH_FILE: &'static str = "HashFile";
+    pub const SAVE_HASH: &'static str = "Save Hash";
+    pub const LOAD_HASH: &'static str = "Load Hash";
+    pub const CHESS960: &'static str = "UCI_Chess960";
+    pub const SANITIZE_FEN: &'static str = "SanitizeFEN";
+    pub const EVAL_FILE: &'static str = "EvalFile";
+
+    // Find the option by its name, which is not case sensitive, and attach
+    // the value to it. File names are case sensitive, so the value is only
+    // lowercased for options that need it. Unknown options give Nothing.
+    pub fn new(name: &str, value: &str) -> Self {
+        let value = value.trim().to_string();
+        match &name.trim().to_lowercase()[..] {
+            "hash" => EngineOptionName::Hash(value),
+            "clear hash" => EngineOptionName::ClearHash,
+            "hashfile" => EngineOptionName::HashFile(value),
+            "save hash" => EngineOptionName::SaveHash,
+            "load hash" => EngineOptionName::LoadHash,
+            "uci_chess960" => EngineOptionName::Chess960(value.to_lowercase()),
+            "sanitizefen" => EngineOptionName::SanitizeFen(value.to_lowercase()),
+            "evalfile" => EngineOptionName::EvalFile(value),
+            _ => EngineOptionName::Nothing,
+        }
+    }
 }
 
 pub struct EngineOptionDefaults;
@@ -109,4 +133,64 @@ impl EngineOptionDefaults {
     pub const HASH_MIN: usize = 0;
     pub const HASH_MAX_64_BIT: usize = 65536;
     pub const HASH_MAX_32_BIT: usize = 2048;
+    pub const EVAL_FILE_NONE: &'static str = "<empty>";
+    pub const HASH_FILE_NONE: &'static str = "<empty>";
+}
+
+// Define errors
+pub type EngineRunResult = Result<(), EngineError>;
+
+// The reasons the engine can stop with an error, instead of quitting
+// normally. Where there is an underlying error, its message is included.
+#[derive(Debug)]
+pub enum EngineError {
+    Fen(FenError),
+    XBoard,
+    PerftSuiteRead(String),
+    PerftSuiteSelect,
+    PerftSuiteFailed,
+    Openings(String),
+    TimeControl,
+    PlayerConfig(String),
+    Sprt,
+    PgnFile(String),
+    GauntletEngine(String),
+    TunerRead(String),
+    TunerNoPositions,
+    TunerWrite(String),
+    DatagenOutput(String),
+    EpdSolveRead(String),
+}
+
+impl Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EngineError::Fen(e) => write!(f, "{e}"),
+            EngineError::XBoard => write!(f, "XBoard not yet implemented."),
+            EngineError::PerftSuiteRead(e) => write!(f, "Perft suite: File could not be read: {e}"),
+            EngineError::PerftSuiteSelect => write!(f, "Perft suite: Test selection incorrect"),
+            EngineError::PerftSuiteFailed => write!(f, "Perft suite: One or more tests failed"),
+            EngineError::Openings(e) => write!(f, "Self-play: Openings could not be read: {e}"),
+            EngineError::TimeControl => write!(f, "Self-play: Time control incorrect"),
+            EngineError::PlayerConfig(e) => {
+                write!(f, "Self-play: Engine configuration incorrect: {e}")
+            }
+            EngineError::Sprt => write!(f, "Self-play: SPRT settings incorrect"),
+            EngineError::PgnFile(e) => write!(f, "Self-play: PGN file could not be created: {e}"),
+            EngineError::GauntletEngine(e) => write!(f, "Gauntlet: {e}"),
+            EngineError::TunerRead(e) => write!(f, "Tuner: Data file could not be read: {e}"),
+            EngineError::TunerNoPositions => write!(f, "Tuner: No usable positions in data file"),
+            EngineError::TunerWrite(e) => write!(f, "Tuner: Output file could not be written: {e}"),
+            EngineError::DatagenOutput(e) => {
+                write!(f, "Datagen: Output file could not be created: {e}")
+            }
+            EngineError::EpdSolveRead(e) => write!(f, "EPD solve: File could not be read: {e}"),
+        }
+    }
+}
+
+impl From<FenError> for EngineError {
+    fn from(e: FenError) -> Self {
+        EngineError::Fen(e)
+    }
 }
@@ -0,0 +1,188 @@
+/* =======================================================================
+Rustic is a chess playing engine.
+Copyright (C) 2019-2024, Marcel Vanthoor
+https://rustic-chess.org/
+
+Rustic is written in the Rust programming language. It is an original
+work, not derived from any engine that came before it. However, it does
+use a lot of concepts which are well-known and are in use by most if not
+all classical alpha/beta-based chess engines.
+
+Rustic is free software: you can redistribute it and/or modify it under
+the terms of the GNU General Public License version 3 as published by
+the Free Software Foundation.
+
+Rustic is distributed in the hope that it will be useful, but WITHOUT
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+for more details.
+
+You should have received a copy of the GNU General Public License along
+with this program.  If not, see <http://www.gnu.org/licenses/>.
+======================================================================= */
+
+// epd_solve.rs runs a tactical test suite such as WAC or STS. Each
+// position in the EPD file is searched for a fixed amount of time, and
+// the engine's best move is checked against the "bm" (best move) and
+// "am" (avoid move) operations of that position.
+
+use super::{
+    defs::{EngineError, EngineRunResult, ErrFatal},
+    Engine,
+};
+use crate::{
+    board::Board,
+    movegen::{defs::Move, MoveGenerator},
+    search::defs::{SearchControl, SearchMode, SearchParams, SearchReport, SearchSummary},
+    tools::{
+        epd::{Epd, EPD_AVOID_MOVE, EPD_BEST_MOVE, EPD_ID},
+        san,
+    },
+};
+use std::sync::Arc;
+
+impl Engine {
+    pub fn epd_solve(&mut self, file: &str, move_time: u128) -> EngineRunResult {
+        let text =
+            std::fs::read_to_string(file).map_err(|e| EngineError::EpdSolveRead(e.to_string()))?;
+
+        // Lines that are empty or start with '#' are skipped.
+        let lines: Vec<&str> = text
+            .lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .collect();
+
+        // The search thread reports back to this function, instead of to
+        // the main loop, as there is no communication module running.
+        let (report_tx, report_rx) = crossbeam_channel::unbounded::<SearchReport>();
+        self.search.init(
+            report_tx,
+            Arc::clone(&self.board),
+            Arc::clone(&self.mg),
+            Arc::clone(&self.tt_search),
+            self.settings.tt_size > 0,
+        );
+
+        println!("Solving {} positions, {} ms each\n", lines.len(), move_time);
+        let mut solved = 0;
+        let mut total = 0;
+
+        for (i, line) in lines.iter().enumerate() {
+            let nr = i + 1;
+            let epd = match Epd::parse(line) {
+                Ok(epd) => epd,
+                Err(_) => {
+                    println!("{}: cannot parse EPD: {}", nr, line);
+                    continue;
+                }
+            };
+            let id = String::from(epd.operand(EPD_ID).unwrap_or("-"));
+
+            // Set up the position, and convert the expected moves.
+            let mut board = self.board.lock().expect(ErrFatal::LOCK);
+            if board.fen_read(Some(&epd.fen)).is_err() {
+                println!("{} {}: invalid FEN: {}", nr, id, epd.fen);
+                continue;
+            }
+            let best_moves = expected_moves(&board, &self.mg, &epd, EPD_BEST_MOVE);
+            let avoid_moves = expected_moves(&board, &self.mg, &epd, EPD_AVOID_MOVE);
+            std::mem::drop(board);
+
+            if best_moves.is_empty() && avoid_moves.is_empty() {
+                println!("{} {}: no valid bm or am", nr, id);
+                continue;
+            }
+
+            // Search the position and wait for the best move.
+            self.tt_search.lock().expect(ErrFatal::LOCK).clear();
+            let mut sp = SearchParams::new();
+            sp.move_time = move_time;
+            sp.search_mode = SearchMode::MoveTime;
+            sp.quiet = true;
+            self.search.send(SearchControl::Start(0, sp));
+
+            let mut summary: Option<SearchSummary> = None;
+            let best_move = loop {
+                match report_rx.recv().expect(ErrFatal::CHANNEL) {
+                    SearchReport::Finished(_, m) => break m,
+                    SearchReport::SearchSummary(s) => summary = Some(s),
+                    _ => (),
+                }
+            };
+
+            // The position is solved if the engine finds one of the best
+            // moves (if any), and none of the moves to avoid.
+            let found = best_move.get_move();
+            let is_best = best_moves.is_empty() || best_moves.iter().any(|m| m.get_move() == found);
+            let is_avoided = avoid_moves.iter().all(|m| m.get_move() != found);
+            let is_solved = is_best && is_avoided;
+
+            total += 1;
+            if is_solved {
+                solved += 1;
+            }
+
+            let mut board = self.board.lock().expect(ErrFatal::LOCK);
+            let found_san = if found != 0 {
+                san::move_to_san(&mut board, &self.mg, best_move)
+            } else {
+                String::from("none")
+            };
+            std::mem::drop(board);
+
+            let (depth, score) = match summary {
+                Some(s) => match s.moves_to_mate() {
+                    Some(moves) => (s.depth, format!("mate {}", moves)),
+                    None => (s.depth, format!("cp {}", s.cp)),
+                },
+                None => (0, String::from("cp 0")),
+            };
+            let expected = [(EPD_BEST_MOVE, &best_moves), (EPD_AVOID_MOVE, &avoid_moves)]
+                .iter()
+                .filter(|(_, moves)| !moves.is_empty())
+                .map(|(opcode, _)| {
+                    format!(
+                        "{} {}",
+                        opcode,
+                        epd.operands(opcode).unwrap_or(&[]).join(" ")
+                    )
+                })
+                .collect::<Vec<String>>()
+                .join(", ");
+
+            println!(
+                "{} {}: {} (depth {}, {}) - expected {} - {}",
+                nr,
+                id,
+                found_san,
+                depth,
+                score,
+                expected,
+                if is_solved { "OK" } else { "Fail" }
+            );
+        }
+
+        let percentage = if total > 0 {
+            solved as f64 * 100.0 / total as f64
+        } else {
+            0.0
+        };
+        println!("\nSolved: {}/{} ({:.1}%)", solved, total, percentage);
+
+        self.search.send(SearchControl::Quit);
+        self.search.wait_for_shutdown();
+
+        Ok(())
+    }
+}
+
+// Convert the moves of the given opcode from SAN into the engine's own
+// moves. Moves that can't be found in the position are left out.
+fn expected_moves(board: &Board, mg: &MoveGenerator, epd: &Epd, opcode: &str) -> Vec<Move> {
+    epd.operands(opcode)
+        .unwrap_or(&[])
+        .iter()
+        .filter_map(|m| san::san_to_move(board, mg, m).ok())
+        .collect()
+}
@@ -25,25 +25,25 @@ use super::{
     defs::{ErrFatal, Information},
     Engine,
 };
-use crate::comm::CommControl;
+use crate::{comm::CommControl, search::defs::SearchReport};
+use crossbeam_channel::select;
 use std::sync::Arc;
 
 impl Engine {
     pub fn main_loop(&mut self) {
-        // Set up a channel for incoming information.
+        // Set up channels for incoming information from Comm and Search.
         let (info_tx, info_rx) = crossbeam_channel::unbounded::<Information>();
+        let (search_tx, search_rx) = crossbeam_channel::unbounded::<SearchReport>();
 
-        // Store the information receiver in the engine for use in other functions.
+        // Store the receivers in the engine for use in other functions.
         self.info_rx = Some(info_rx);
+        self.search_rx = Some(search_rx);
 
         // Initialize Communications and Search modules.
-        self.comm.init(
-            info_tx.clone(),
-            Arc::clone(&self.board),
-            Arc::clone(&self.options),
-        );
+        self.comm
+            .init(info_tx, Arc::clone(&self.board), Arc::clone(&self.options));
         self.search.init(
-            info_tx,
+            search_tx,
             Arc::clone(&self.board),
             Arc::clone(&self.mg),
             Arc::clone(&self.tt_search),
@@ -68,11 +68,15 @@ impl Engine {
         self.search.wait_for_shutdown();
     }
 
-    // This is the main engine thread Information receiver.
+    // This is the main engine thread Information receiver. It waits for
+    // whichever of Comm and Search reports first.
     fn info_rx(&mut self) -> Information {
-        match &self.info_rx {
-            Some(i) => i.recv().expect(ErrFatal::CHANNEL),
-            None => panic!("{}", ErrFatal::NO_INFO_RX),
+        match (&self.info_rx, &self.search_rx) {
+            (Some(i), Some(s)) => select! {
+                recv(i) -> info => info.expect(ErrFatal::CHANNEL),
+                recv(s) -> report => Information::Search(report.expect(ErrFatal::CHANNEL)),
+            },
+            _ => panic!("{}", ErrFatal::NO_INFO_RX),
         }
     }
 }
@@ -27,9 +27,10 @@ use crate::{comm::CommControl, search::defs::SearchReport};
 impl Engine {
     pub fn search_reports(&mut self, search_report: &SearchReport) {
         match search_report {
-            SearchReport::Finished(m) => {
+            SearchReport::Finished(id, m) => {
                 self.comm.send(CommControl::BestMove(*m));
                 self.comm.send(CommControl::Update);
+                self.search_finished(*id);
             }
 
             SearchReport::SearchCurrentMove(curr_move) => {
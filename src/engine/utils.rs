@@ -21,19 +21,31 @@ You should have received a copy of the GNU General Public License along
 with this program.  If not, see <http://www.gnu.org/licenses/>.
 ======================================================================= */
 
-use super::{defs::ErrFatal, Engine};
+use super::{
+    defs::{EngineError, EngineRunResult, ErrFatal, ErrNormal},
+    Engine,
+};
 use crate::{
     board::Board,
-    defs::{EngineRunResult, FEN_KIWIPETE_POSITION},
+    defs::{About, FEN_KIWIPETE_POSITION},
     misc::parse,
-    misc::parse::PotentialMove,
+    misc::parse::{MoveParseError, PotentialMove},
     movegen::{
-        defs::{Move, MoveList, MoveType},
+        defs::{Move, MoveGenMode, MoveList, MoveType},
         MoveGenerator,
     },
+    tools::{
+        cmdline::{DatagenArgs, MatchArgs, TuneArgs},
+        datagen::{self, DatagenOptions},
+        gauntlet,
+        perft_suite::{self, SuiteOptions},
+        selfplay::{self, MatchOptions, PlayerConfig, Sprt, TimeControl},
+        tuner,
+        uci_client::UciClient,
+    },
 };
 use if_chain::if_chain;
-use std::sync::Mutex;
+use std::{fs::File, sync::Arc};
 
 impl Engine {
     // This function sets up a position using a given FEN-string.
@@ -53,51 +65,299 @@ impl Engine {
         Ok(())
     }
 
-    // This function executes a move on the internal board, if it legal to
-    // do so in the given position.
-    pub fn execute_move(&mut self, m: String) -> bool {
-        // Prepare shorthand variables.
-        let empty = (0usize, 0usize, 0usize);
-        let potential_move = parse::algebraic_move_to_number(&m[..]).unwrap_or(empty);
-        let is_pseudo_legal = self.pseudo_legal(potential_move, &self.board, &self.mg);
-        let mut is_legal = false;
+    // Run the perft suite in the given file, with the depth, selection and
+    // failure handling given on the command line. As with the built-in
+    // suite, the perft TT is sized here, because the engine only creates
+    // it when running perft with -p.
+    pub fn perft_suite(&mut self, file: &str) -> EngineRunResult {
+        let tests =
+            perft_suite::read_file(file).map_err(|e| EngineError::PerftSuiteRead(e.to_string()))?;
+        let mut options = SuiteOptions::new();
+        options.max_depth = self.cmdline.suite_depth();
+        options.keep_going = self.cmdline.has_keep_going();
+        options.compare = self.cmdline.movegen() == MoveGenMode::COMPARE;
+        if let Some(select) = self.cmdline.suite_select() {
+            options.select =
+                SuiteOptions::parse_select(&select).map_err(|_| EngineError::PerftSuiteSelect)?;
+        }
+
+        self.tt_perft
+            .lock()
+            .expect(ErrFatal::LOCK)
+            .resize(self.settings.tt_size);
+        self.tt_search.lock().expect(ErrFatal::LOCK).resize(0);
+
+        let tests: Vec<&str> = tests.iter().map(|t| t.as_str()).collect();
+        let passed = perft_suite::run(
+            &tests,
+            &options,
+            Arc::clone(&self.tt_perft),
+            self.settings.tt_size > 0,
+            self.movegen_mode(),
+            self.settings.threads,
+        );
+
+        if passed {
+            Ok(())
+        } else {
+            Err(EngineError::PerftSuiteFailed)
+        }
+    }
+
+    // Play a self-play match with the settings given on the command line.
+    // The players each create their own TT, so the engine's own search TT
+    // is not needed while the match runs.
+    pub fn selfplay(&mut self, args: MatchArgs) -> EngineRunResult {
+        let mut options = self.match_options(&args)?;
+        let configs = [
+            PlayerConfig::parse(&args.configs[0], "Engine 1").map_err(EngineError::PlayerConfig)?,
+            PlayerConfig::parse(&args.configs[1], "Engine 2").map_err(EngineError::PlayerConfig)?,
+        ];
+        options.sprt = match &args.sprt {
+            Some(bounds) => {
+                Some(Sprt::parse(bounds, args.alpha, args.beta).map_err(|_| EngineError::Sprt)?)
+            }
+            None => None,
+        };
+
+        self.tt_search.lock().expect(ErrFatal::LOCK).resize(0);
+        selfplay::run(&mut options, &configs, Arc::clone(&self.mg));
+
+        Ok(())
+    }
+
+    // Play a gauntlet against the external engines given on the command
+    // line. All of them are started before the first game, so a wrong
+    // path is found out immediately.
+    pub fn gauntlet(&mut self, args: MatchArgs) -> EngineRunResult {
+        let mut options = self.match_options(&args)?;
+        options.event = String::from("Gauntlet");
+        let config = PlayerConfig::parse(&args.configs[0], About::ENGINE)
+            .map_err(EngineError::PlayerConfig)?;
+
+        let mut opponents: Vec<UciClient> = Vec::new();
+        for engine in args.engines.iter() {
+            let client =
+                UciClient::start(engine).map_err(|e| EngineError::GauntletEngine(e.to_string()))?;
+            opponents.push(client);
+        }
+
+        self.tt_search.lock().expect(ErrFatal::LOCK).resize(0);
+        gauntlet::run(&mut options, &config, opponents, Arc::clone(&self.mg));
+
+        Ok(())
+    }
 
-        if let Ok(ips) = is_pseudo_legal {
-            is_legal = self.board.lock().expect(ErrFatal::LOCK).make(ips, &self.mg);
+    // Tune the evaluation on the data file given on the command line, and
+    // write the tuned tables to the output file.
+    pub fn tune(&mut self, args: TuneArgs) -> EngineRunResult {
+        let entries = tuner::load(&args.data, Arc::clone(&self.mg))
+            .map_err(|e| EngineError::TunerRead(e.to_string()))?;
+        if entries.is_empty() {
+            return Err(EngineError::TunerNoPositions);
         }
-        is_legal
-    }
-
-    // After the engine receives an incoming move, it checks if this move
-    // is actually in the list of pseudo-legal moves for this position.
-    pub fn pseudo_legal(
-        &self,
-        m: PotentialMove,
-        board: &Mutex<Board>,
-        mg: &MoveGenerator,
-    ) -> Result<Move, ()> {
-        let mut result = Err(());
-
-        // Get the pseudo-legal move list for this position.
-        let mut ml = MoveList::new();
-        let mtx_board = board.lock().expect(ErrFatal::LOCK);
-        mg.generate_moves(&mtx_board, &mut ml, MoveType::All);
-        std::mem::drop(mtx_board);
-
-        // Determine if the potential move is pseudo-legal. make() wil
-        // determine final legality when executing the move.
-        for i in 0..ml.len() {
-            let current = ml.get_move(i);
-            if_chain! {
-                if m.0 == current.from();
-                if m.1 == current.to();
-                if m.2 == current.promoted();
-                then {
-                    result = Ok(current);
-                    break;
+
+        let params = tuner::tune(&entries, args.iterations, args.rate);
+        std::fs::write(&args.out, tuner::to_source(&params))
+            .map_err(|e| EngineError::TunerWrite(e.to_string()))?;
+        println!("Tuned tables written to {}", args.out);
+
+        Ok(())
+    }
+
+    // Generate training data with the settings given on the command line.
+    // With more than one thread, each thread writes to its own file, named
+    // after the output file with the thread number added.
+    pub fn datagen(&mut self, args: DatagenArgs) -> EngineRunResult {
+        let threads = args.threads.max(1);
+        let mut files: Vec<File> = Vec::new();
+        for i in 0..threads {
+            let name = if threads == 1 {
+                args.out.clone()
+            } else {
+                match args.out.rsplit_once('.') {
+                    Some((stem, ext)) => format!("{}_{}.{}", stem, i, ext),
+                    None => format!("{}_{}", args.out, i),
                 }
+            };
+            files.push(File::create(&name).map_err(|e| EngineError::DatagenOutput(e.to_string()))?);
+        }
+
+        let options = DatagenOptions {
+            games: args.games,
+            nodes: args.nodes,
+            random_plies: args.random_plies,
+            hash: args.hash,
+        };
+
+        self.tt_search.lock().expect(ErrFatal::LOCK).resize(0);
+        datagen::run(&options, files, Arc::clone(&self.mg));
+
+        Ok(())
+    }
+
+    // Check and convert the match settings that self-play and gauntlet
+    // have in common.
+    fn match_options(&self, args: &MatchArgs) -> Result<MatchOptions, EngineError> {
+        let openings = selfplay::read_openings(args.openings.as_deref(), &self.mg)
+            .map_err(EngineError::Openings)?;
+        let time_control = TimeControl::parse(&args.tc).map_err(|_| EngineError::TimeControl)?;
+        let pgn_out = match &args.pgn_out {
+            Some(file) => {
+                Some(File::create(file).map_err(|e| EngineError::PgnFile(e.to_string()))?)
+            }
+            None => None,
+        };
+
+        Ok(MatchOptions {
+            event: String::from("Self-play"),
+            openings,
+            games: args.games,
+            time_control,
+            sprt: None,
+            pgn_out,
+        })
+    }
+
+    // Returns the move generator selected on the command line. When
+    // comparing both generators, the legal one is the one under test.
+    pub fn movegen_mode(&self) -> MoveGenMode {
+        match &self.cmdline.movegen()[..] {
+            MoveGenMode::PSEUDO => MoveGenMode::Pseudo,
+            _ => MoveGenMode::Legal,
+        }
+    }
+}
+
+// Set up a position from a FEN-string, and play the given moves in it. The
+// moves must be in coordinate notation, as the UCI protocol requires. If
+// the FEN-string or one of the moves is invalid, the board is put back the
+// way it was, and the returned error describes what was wrong.
+pub fn set_position(
+    board: &mut Board,
+    mg: &MoveGenerator,
+    fen: &str,
+    moves: &[String],
+) -> Result<(), String> {
+    let backup = board.clone();
+    let result = match board.fen_read(Some(fen)) {
+        Ok(()) => moves.iter().try_for_each(|m| {
+            execute_move(board, mg, m)
+                .map_err(|e| format!("{}: {} {}", m, e, ErrNormal::NOT_CHANGED))
+        }),
+        Err(e) => Err(format!("{}. {}", e, ErrNormal::NOT_CHANGED)),
+    };
+
+    match result {
+        Ok(()) => board.share_history(),
+        Err(_) => *board = backup,
+    }
+
+    result
+}
+
+// This function executes a move on the board, if it legal to do so in the
+// given position.
+fn execute_move(board: &mut Board, mg: &MoveGenerator, m: &str) -> Result<(), MoveParseError> {
+    let potential_move = parse::algebraic_move_to_number(m)?;
+    let pseudo_legal = pseudo_legal(potential_move, board, mg)?;
+
+    if board.make(pseudo_legal, mg) {
+        Ok(())
+    } else {
+        Err(MoveParseError::Illegal)
+    }
+}
+
+// After the engine receives an incoming move, it checks if this move is
+// actually in the list of pseudo-legal moves for this position.
+fn pseudo_legal(
+    m: PotentialMove,
+    board: &Board,
+    mg: &MoveGenerator,
+) -> Result<Move, MoveParseError> {
+    let mut result = Err(MoveParseError::Illegal);
+
+    // Get the pseudo-legal move list for this position.
+    let mut ml = MoveList::new();
+    mg.generate_moves(board, &mut ml, MoveType::All);
+
+    // Determine if the potential move is pseudo-legal. make() wil
+    // determine final legality when executing the move.
+    for i in 0..ml.len() {
+        let current = ml.get_move(i);
+        if_chain! {
+            if m.0 == current.from();
+            if m.1 == current.to();
+            if m.2 == current.promoted();
+            then {
+                result = Ok(current);
+                break;
             }
         }
-        result
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::defs::FEN_START_POSITION;
+
+    fn moves(list: &str) -> Vec<String> {
+        list.split_whitespace().map(String::from).collect()
+    }
+
+    // Set up a position on a board that already holds a game, and return
+    // the error, if any. The board must be unchanged after an error.
+    fn set_up(fen: &str, list: &str) -> (Board, Result<(), String>) {
+        let mg = MoveGenerator::new();
+        let mut board = Board::from_fen(FEN_KIWIPETE_POSITION).expect("valid FEN");
+        assert_eq!(
+            set_position(&mut board, &mg, FEN_KIWIPETE_POSITION, &moves("e1g1")),
+            Ok(())
+        );
+        let before = (board.fen_write(), board.zobrist_key(), board.history_len());
+
+        let result = set_position(&mut board, &mg, fen, &moves(list));
+        if result.is_err() {
+            let after = (board.fen_write(), board.zobrist_key(), board.history_len());
+            assert_eq!(after, before, "board changed after an error");
+        }
+
+        (board, result)
+    }
+
+    #[test]
+    fn moves_are_played_after_the_fen() {
+        let (board, result) = set_up(FEN_START_POSITION, "e2e4 e7e5 g1f3");
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            board.fen_write(),
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2"
+        );
+        assert_eq!(board.history_len(), 3);
+    }
+
+    #[test]
+    fn bad_move_rolls_back_the_whole_position() {
+        // An illegal move after legal ones.
+        let (_, result) = set_up(FEN_START_POSITION, "e2e4 e7e5 e1e3");
+        assert!(result.is_err_and(|e| e.starts_with("e1e3:")));
+
+        // A move that leaves the king in check.
+        let fen = "4k3/8/8/8/8/8/4r3/4K3 w - - 0 1";
+        let (_, result) = set_up(fen, "e1d2");
+        assert!(result.is_err_and(|e| e.starts_with("e1d2:")));
+
+        // SAN is not accepted.
+        let (_, result) = set_up(FEN_START_POSITION, "e4");
+        assert!(result.is_err_and(|e| e.starts_with("e4:")));
+    }
+
+    #[test]
+    fn bad_fen_rolls_back_the_position() {
+        let (_, result) = set_up("4k3/8/8/8/8/8/8/4K3 w K - 0 1", "");
+        assert!(result.is_err_and(|e| e.ends_with(ErrNormal::NOT_CHANGED)));
     }
 }
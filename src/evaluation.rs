@@ -22,6 +22,7 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 ======================================================================= */
 
 pub mod defs;
+pub mod nnue;
 pub mod psqt;
 
 use crate::{board::Board, defs::Sides};
@@ -30,6 +31,13 @@ use psqt::KING_EDGE;
 pub fn evaluate_position(board: &Board) -> i16 {
     const KING_ONLY: i16 = 300; // PSQT-points
     let side = board.game_state.active_color as usize;
+
+    // If a network is loaded, it replaces the PSQT evaluation. The network
+    // already returns the value from the side to move's point of view.
+    if let Some((network, accumulator)) = board.nnue() {
+        return network.evaluate(accumulator, side);
+    }
+
     let w_psqt = board.game_state.psqt[Sides::WHITE];
     let b_psqt = board.game_state.psqt[Sides::BLACK];
     let mut value = w_psqt - b_psqt;
@@ -0,0 +1,286 @@
+/* =======================================================================
+Rustic is a chess playing engine.
+Copyright (C) 2019-2024, Marcel Vanthoor
+https://rustic-chess.org/
+
+Rustic is written in the Rust programming language. It is an original
+work, not derived from any engine that came before it. However, it does
+use a lot of concepts which are well-known and are in use by most if not
+all classical alpha/beta-based chess engines.
+
+Rustic is free software: you can redistribute it and/or modify it under
+the terms of the GNU General Public License version 3 as published by
+the Free Software Foundation.
+
+Rustic is distributed in the hope that it will be useful, but WITHOUT
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+for more details.
+
+You should have received a copy of the GNU General Public License along
+with this program.  If not, see <http://www.gnu.org/licenses/>.
+======================================================================= */
+
+// This file implements an optional NNUE evaluation. The network is small:
+// king-relative piece-square inputs feed a quantized i16 accumulator (one
+// per perspective), followed by two dense hidden layers and one output.
+//
+// The accumulator is what makes NNUE fast: instead of running the first
+// layer from scratch for each position, the board adds or subtracts the
+// weights of a feature whenever a piece is put onto or removed from a
+// square, the same way the incremental PSQT value is kept. Only when a
+// king moves into another bucket does that side's perspective have to be
+// rebuilt from scratch.
+//
+// Network file layout (all values little-endian, row-major [out][in]):
+//
+// magic               4 bytes, "RNN1"
+// feature weights     i16 [INPUTS][L1]
+// feature biases      i16 [L1]
+// hidden 1 weights    i8  [L2][2 * L1]
+// hidden 1 biases     i32 [L2]
+// hidden 2 weights    i8  [L3][L2]
+// hidden 2 biases     i32 [L3]
+// output weights      i8  [L3]
+// output bias         i32
+
+use crate::{
+    board::Board,
+    defs::{NrOf, Piece, Side, Sides, Square},
+    misc::bits,
+};
+use std::{fs, io};
+
+const MAGIC: &[u8; 4] = b"RNN1";
+
+// Network dimensions. The inputs are one piece-square table per king
+// bucket, for each of the 12 pieces (6 types, own and opponent).
+pub const KING_BUCKETS: usize = 4;
+pub const INPUTS: usize = KING_BUCKETS * 2 * NrOf::PIECE_TYPES * NrOf::SQUARES;
+pub const L1: usize = 128;
+pub const L2: usize = 16;
+pub const L3: usize = 16;
+
+// Quantization. Activations are clipped to 0..=QA, which represents 0.0 to
+// 1.0. Dense weights are scaled by 2^WEIGHT_SHIFT. The output is in the
+// same scale as the training target: OUTPUT_SCALE centipawns per 1.0.
+const QA: i32 = 127;
+const WEIGHT_SHIFT: i32 = 6;
+const OUTPUT_SCALE: i32 = 400;
+
+// Keep NNUE scores well away from the mate range.
+const EVAL_LIMIT: i32 = 10_000;
+
+// The first layer output for both perspectives, and the king bucket each
+// perspective was built with.
+#[derive(Clone, Copy)]
+pub struct Accumulator {
+    pub values: [[i16; L1]; Sides::BOTH],
+    pub bucket: [usize; Sides::BOTH],
+}
+
+pub struct Network {
+    feature_weights: Vec<i16>,
+    feature_biases: Vec<i16>,
+    hidden1_weights: Vec<i8>,
+    hidden1_biases: Vec<i32>,
+    hidden2_weights: Vec<i8>,
+    hidden2_biases: Vec<i32>,
+    output_weights: Vec<i8>,
+    output_bias: i32,
+}
+
+impl Network {
+    // Load a network file. The file must have the exact size of the
+    // architecture above; anything else is rejected.
+    pub fn load(path: &str) -> io::Result<Network> {
+        let data = fs::read(path)?;
+        let expected =
+            MAGIC.len() + (INPUTS * L1 + L1) * 2 + L2 * 2 * L1 + L2 * 4 + L3 * L2 + L3 * 4 + L3 + 4;
+
+        if data.len() != expected || &data[0..MAGIC.len()] != MAGIC {
+            let msg = format!("{path}: not a valid network file for this engine");
+            return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+        }
+
+        let mut reader = Reader {
+            data: &data,
+            pos: MAGIC.len(),
+        };
+
+        Ok(Network {
+            feature_weights: reader.i16s(INPUTS * L1),
+            feature_biases: reader.i16s(L1),
+            hidden1_weights: reader.i8s(L2 * 2 * L1),
+            hidden1_biases: reader.i32s(L2),
+            hidden2_weights: reader.i8s(L3 * L2),
+            hidden2_biases: reader.i32s(L3),
+            output_weights: reader.i8s(L3),
+            output_bias: reader.i32s(1)[0],
+        })
+    }
+
+    // Build the accumulator for both perspectives from scratch.
+    pub fn accumulator(&self, board: &Board) -> Accumulator {
+        let mut acc = Accumulator {
+            values: [[0; L1]; Sides::BOTH],
+            bucket: [0; Sides::BOTH],
+        };
+
+        for perspective in [Sides::WHITE, Sides::BLACK] {
+            acc.bucket[perspective] = bucket(perspective, board.king_square(perspective));
+            acc.values[perspective] = self.perspective(board, perspective, acc.bucket[perspective]);
+        }
+
+        acc
+    }
+
+    // Calculate the accumulator values for one perspective from scratch.
+    pub fn perspective(&self, board: &Board, perspective: Side, bucket: usize) -> [i16; L1] {
+        let mut values = [0; L1];
+        values.copy_from_slice(&self.feature_biases);
+
+        for side in [Sides::WHITE, Sides::BLACK] {
+            for (piece, bb) in board.bb_pieces[side].iter().enumerate() {
+                let mut pieces = *bb;
+                while pieces > 0 {
+                    let square = bits::next(&mut pieces);
+                    let f = feature(perspective, bucket, side, piece, square);
+                    let weights = &self.feature_weights[f * L1..(f + 1) * L1];
+                    for (v, w) in values.iter_mut().zip(weights) {
+                        *v += *w;
+                    }
+                }
+            }
+        }
+
+        values
+    }
+
+    // Add (or subtract) the weights of a piece on a square, for both
+    // perspectives, using the king bucket the perspective was built with.
+    pub fn update(
+        &self,
+        acc: &mut Accumulator,
+        side: Side,
+        piece: Piece,
+        square: Square,
+        add: bool,
+    ) {
+        for perspective in [Sides::WHITE, Sides::BLACK] {
+            let f = feature(perspective, acc.bucket[perspective], side, piece, square);
+            let weights = &self.feature_weights[f * L1..(f + 1) * L1];
+            let values = &mut acc.values[perspective];
+
+            if add {
+                for (v, w) in values.iter_mut().zip(weights) {
+                    *v += *w;
+                }
+            } else {
+                for (v, w) in values.iter_mut().zip(weights) {
+                    *v -= *w;
+                }
+            }
+        }
+    }
+
+    // Run the rest of the network. The side to move's perspective comes
+    // first, so the result is from that side's point of view, as the
+    // search expects.
+    pub fn evaluate(&self, acc: &Accumulator, side: Side) -> i16 {
+        let mut input = [0; 2 * L1];
+        for i in 0..L1 {
+            input[i] = (acc.values[side][i] as i32).clamp(0, QA);
+            input[L1 + i] = (acc.values[side ^ 1][i] as i32).clamp(0, QA);
+        }
+
+        let hidden1: [i32; L2] = dense(&self.hidden1_weights, &self.hidden1_biases, &input);
+        let hidden2: [i32; L3] = dense(&self.hidden2_weights, &self.hidden2_biases, &hidden1);
+
+        let mut output = self.output_bias;
+        for (w, x) in self.output_weights.iter().zip(hidden2.iter()) {
+            output += *w as i32 * x;
+        }
+
+        let value = output * OUTPUT_SCALE / (QA << WEIGHT_SHIFT);
+        value.clamp(-EVAL_LIMIT, EVAL_LIMIT) as i16
+    }
+}
+
+// Determine the king bucket for a perspective: the board is split into
+// four areas, as seen from that side: queen side or king side, and the
+// first two ranks or the rest of the board.
+pub fn bucket(perspective: Side, king_square: Square) -> usize {
+    let square = relative(perspective, king_square);
+    let far = (square / 8 >= 2) as usize;
+    let king_side = (square % 8 >= 4) as usize;
+
+    far * 2 + king_side
+}
+
+// Input index for a piece of a side on a square, as seen from a
+// perspective: squares are flipped and colors are swapped for black, so
+// the network always sees the position from the "own" side.
+fn feature(perspective: Side, bucket: usize, side: Side, piece: Piece, square: Square) -> usize {
+    let color = (side != perspective) as usize;
+    let square = relative(perspective, square);
+
+    ((bucket * 2 + color) * NrOf::PIECE_TYPES + piece) * NrOf::SQUARES + square
+}
+
+fn relative(perspective: Side, square: Square) -> Square {
+    if perspective == Sides::WHITE {
+        square
+    } else {
+        square ^ 56
+    }
+}
+
+// A dense layer with clipped ReLU activation.
+fn dense<const OUT: usize>(weights: &[i8], biases: &[i32], input: &[i32]) -> [i32; OUT] {
+    let mut output = [0; OUT];
+
+    for (i, out) in output.iter_mut().enumerate() {
+        let row = &weights[i * input.len()..(i + 1) * input.len()];
+        let mut sum = biases[i];
+        for (w, x) in row.iter().zip(input.iter()) {
+            sum += *w as i32 * x;
+        }
+        *out = (sum >> WEIGHT_SHIFT).clamp(0, QA);
+    }
+
+    output
+}
+
+// Reads little-endian values from the network file. The file size has
+// already been checked, so reading can't run past the end.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, bytes: usize) -> &[u8] {
+        let slice = &self.data[self.pos..self.pos + bytes];
+        self.pos += bytes;
+        slice
+    }
+
+    fn i8s(&mut self, n: usize) -> Vec<i8> {
+        self.take(n).iter().map(|b| *b as i8).collect()
+    }
+
+    fn i16s(&mut self, n: usize) -> Vec<i16> {
+        self.take(n * 2)
+            .chunks_exact(2)
+            .map(|c| i16::from_le_bytes([c[0], c[1]]))
+            .collect()
+    }
+
+    fn i32s(&mut self, n: usize) -> Vec<i32> {
+        self.take(n * 4)
+            .chunks_exact(4)
+            .map(|c| i32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+            .collect()
+    }
+}
@@ -0,0 +1,100 @@
+/* =======================================================================
+Rustic is a chess playing engine.
+Copyright (C) 2019-2024, Marcel Vanthoor
+https://rustic-chess.org/
+
+Rustic is written in the Rust programming language. It is an original
+work, not derived from any engine that came before it. However, it does
+use a lot of concepts which are well-known and are in use by most if not
+all classical alpha/beta-based chess engines.
+
+Rustic is free software: you can redistribute it and/or modify it under
+the terms of the GNU General Public License version 3 as published by
+the Free Software Foundation.
+
+Rustic is distributed in the hope that it will be useful, but WITHOUT
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+for more details.
+
+You should have received a copy of the GNU General Public License along
+with this program.  If not, see <http://www.gnu.org/licenses/>.
+======================================================================= */
+
+//! Rustic's board representation, move generator and search, as a library.
+//!
+//! The UCI engine in `main.rs` is a front-end over this library. Other
+//! tools can use the same building blocks:
+//!
+//! - [`Board`]: set up a position with [`Board::from_fen`], and play or take
+//!   back moves with [`Board::make`] and [`Board::unmake`].
+//! - [`MoveGenerator`]: holds the attack tables. It is expensive to create,
+//!   so create it once and share it; [`MoveGenerator::generate_legal_moves`]
+//!   lists the legal moves in a position.
+//! - [`search()`]: search a position synchronously and get the best move.
+//! - [`TT`]: the transposition table. A `TT<SearchData>` can be kept and
+//!   passed to [`Search::run()`], so it is reused from one search to the
+//!   next.
+//!
+//! ```no_run
+//! use rustic_alpha::{search, Board, MoveList, MoveType, SearchMode, SearchParams};
+//!
+//! let board = Board::from_fen("8/8/8/4k3/8/8/3QK3/8 w - - 0 1").unwrap();
+//!
+//! let mut moves = MoveList::new();
+//! rustic_alpha::move_generator().generate_legal_moves(&board, &mut moves, MoveType::All);
+//! println!("{} legal moves", moves.len());
+//!
+//! let mut limits = SearchParams::new();
+//! limits.search_mode = SearchMode::Depth;
+//! limits.depth = 6;
+//! let outcome = search(&board, &limits);
+//! println!("best move: {}", outcome.best_move.as_string());
+//! ```
+//!
+//! The engine's front-end (communication, engine thread, command line and
+//! the tools such as self-play and the tuner) is not part of the library; it
+//! lives in the binary.
+
+pub mod board;
+pub mod defs;
+pub mod evaluation;
+pub mod misc;
+pub mod movegen;
+pub mod search;
+pub mod transposition;
+
+pub use board::{Board, FenError};
+pub use movegen::{
+    defs::{Move, MoveList, MoveType},
+    MoveGenerator,
+};
+pub use search::{
+    defs::{GameTime, SearchMode, SearchParams, SearchSummary},
+    SearchOutcome,
+};
+
+pub use transposition::{SearchData, TT};
+
+use search::Search;
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Hash size in MB used by [`search()`].
+pub const SEARCH_HASH_DEFAULT: usize = 16;
+
+/// Returns the move generator shared by this library. It is created on
+/// first use.
+pub fn move_generator() -> &'static Arc<MoveGenerator> {
+    static MOVE_GENERATOR: OnceLock<Arc<MoveGenerator>> = OnceLock::new();
+    MOVE_GENERATOR.get_or_init(|| Arc::new(MoveGenerator::new()))
+}
+
+/// Search the position on the board within the given limits, and return
+/// the best move found. This blocks until the search is done. Each call
+/// uses a new transposition table of [`SEARCH_HASH_DEFAULT`] MB; to keep
+/// the table between searches, create a [`TT`] of [`SearchData`] and use
+/// [`Search::run()`] with it.
+pub fn search(board: &Board, limits: &SearchParams) -> SearchOutcome {
+    let tt = Arc::new(Mutex::new(TT::<SearchData>::new(SEARCH_HASH_DEFAULT)));
+    Search::run(board, move_generator(), &tt, limits)
+}
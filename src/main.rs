@@ -21,28 +21,29 @@ You should have received a copy of the GNU General Public License along
 with this program.  If not, see <http://www.gnu.org/licenses/>.
 ======================================================================= */
 
-mod board;
+// The board, move generator, evaluation and search live in the library
+// (lib.rs). The binary is the front-end: communication, the engine thread,
+// the command line and the tools built on top of the library.
 mod comm;
-mod defs;
 mod engine;
-mod evaluation;
-mod misc;
-mod movegen;
-mod search;
+mod tools;
 
 #[cfg(feature = "extra")]
 mod extra;
 
-// use interface::console;
-use defs::ENGINE_RUN_ERRORS;
+// Bring the library's modules into the crate root, so the front-end can
+// refer to them as crate::board, crate::search, and so on.
+use rustic_alpha::{board, defs, evaluation, misc, movegen, search, transposition};
+
 use engine::Engine;
+use std::process;
 
 fn main() {
     let mut engine = Engine::new();
     let result = engine.run();
 
-    match result {
-        Ok(()) => (),
-        Err(e) => println!("Error code {}: {}", e, ENGINE_RUN_ERRORS[e as usize]),
-    };
+    if let Err(e) = result {
+        println!("Error: {e}");
+        process::exit(1);
+    }
 }
@@ -22,7 +22,5 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 ======================================================================= */
 
 pub mod bits;
-pub mod cmdline;
 pub mod parse;
-pub mod perft;
 pub mod print;
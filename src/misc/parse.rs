@@ -24,16 +24,36 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 use crate::board::defs::{Pieces, SQUARE_NAME};
 use crate::defs::{Piece, Square};
 use if_chain::if_chain;
+use std::fmt::{self, Display};
 
 pub type PotentialMove = (Square, Square, Piece);
-pub type ParseMoveResult = Result<PotentialMove, ()>;
+pub type ParseMoveResult = Result<PotentialMove, MoveParseError>;
+
+// Reasons why a move given as a string could not be played.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveParseError {
+    Notation,  // Not a move in coordinate notation (or SAN).
+    Illegal,   // A move, but not a legal one in this position.
+    Ambiguous, // SAN that matches more than one legal move.
+}
+
+impl Display for MoveParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            MoveParseError::Notation => "This is not a move in a known notation.",
+            MoveParseError::Illegal => "This is not a legal move in this position.",
+            MoveParseError::Ambiguous => "This move is ambiguous in this position.",
+        };
+        write!(f, "{msg}")
+    }
+}
 
 pub fn algebraic_move_to_number(m: &str) -> ParseMoveResult {
     let lower_case_move = m.to_ascii_lowercase();
     let mut potential_move: PotentialMove = (0, 0, Pieces::NONE);
 
     // Assume parsing the move will fail.
-    let mut parse_move_result: ParseMoveResult = Err(());
+    let mut parse_move_result: ParseMoveResult = Err(MoveParseError::Notation);
 
     // Get the "from" and "to" squares from the move stirng.
     if m.len() == 4 || m.len() == 5 {
@@ -53,9 +73,9 @@ pub fn algebraic_move_to_number(m: &str) -> ParseMoveResult {
     }
 
     // If Ok and there are 5 characters, keep parsing...
-    if parse_move_result != Err(()) && m.len() == 5 {
+    if parse_move_result.is_ok() && m.len() == 5 {
         // Again, assume that parsing will fail.
-        parse_move_result = Err(());
+        parse_move_result = Err(MoveParseError::Notation);
 
         // Get the promotion piece character.
         let c = lower_case_move.chars().last().unwrap_or('-');
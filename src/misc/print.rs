@@ -29,6 +29,7 @@ use crate::{
     defs::{Bitboard, Castling, NrOf, Sides},
     movegen::defs::{Move, MoveList},
 };
+use std::fmt::Write;
 
 type AsciiBoard = [char; NrOf::SQUARES];
 
@@ -48,11 +49,19 @@ const CHAR_BP: char = 'i';
 
 // Prints the current position to the screen.
 pub fn position(board: &Board, mark_square: Option<u8>) {
+    print!("{}", position_as_string(board, mark_square));
+}
+
+// Creates the text that position() prints, so it can be printed later,
+// when the board may already have changed.
+pub fn position_as_string(board: &Board, mark_square: Option<u8>) -> String {
     let mut ascii_board: AsciiBoard = [CHAR_ES; NrOf::SQUARES];
+    let mut text = String::new();
 
     bitboards_to_ascii(board, &mut ascii_board);
-    to_console(&ascii_board, mark_square);
-    metadata(board);
+    to_text(&ascii_board, mark_square, &mut text);
+    metadata(board, &mut text);
+    text
 }
 
 // Create a printable ASCII-board out of bitboards.
@@ -100,42 +109,42 @@ fn put_character_on_square(bitboard: Bitboard, ascii_board: &mut AsciiBoard, cha
     }
 }
 
-// Print the generated ASCII-board to the console. Optionally mark one square.
-fn to_console(ascii_board: &AsciiBoard, mark_square: Option<u8>) {
+// Write the generated ASCII-board as text. Optionally mark one square.
+// Writing to a String can't fail, so the results of writeln! are ignored.
+fn to_text(ascii_board: &AsciiBoard, mark_square: Option<u8>, text: &mut String) {
     let coordinate_alpha: &str = "ABCDEFGH";
     let mut coordinate_digit = NrOf::FILES;
 
-    println!();
+    text.push('\n');
     for current_rank in RangeOf::RANKS.rev() {
-        print!("{coordinate_digit}   ");
+        let _ = write!(text, "{coordinate_digit}   ");
         for current_file in RangeOf::FILES {
             let square = (current_rank as usize * NrOf::FILES) + current_file as usize;
             let character = ascii_board[square];
             if let Some(m) = mark_square {
                 if m == (square as u8) {
                     // \x1b[0;35m is magenta
-                    print!("\x1b[0;35m{character} \x1b[0m");
+                    let _ = write!(text, "\x1b[0;35m{character} \x1b[0m");
                 } else {
-                    print!("{character} ");
+                    let _ = write!(text, "{character} ");
                 }
             } else {
-                print!("{character} ");
+                let _ = write!(text, "{character} ");
             }
         }
-        println!();
+        text.push('\n');
         coordinate_digit -= 1;
     }
-    println!();
-    print!("    ");
+    text.push('\n');
+    text.push_str("    ");
     for c in coordinate_alpha.chars() {
-        print!("{c} ");
+        let _ = write!(text, "{c} ");
     }
-    println!();
-    println!();
+    text.push_str("\n\n");
 }
 
-// This function prints all of the metadata about the position.
-fn metadata(board: &Board) {
+// This function writes all of the metadata about the position.
+fn metadata(board: &Board, text: &mut String) {
     let is_white = (board.game_state.active_color as usize) == Sides::WHITE;
     let active_color = if is_white { "White" } else { "Black" };
     let castling = castling_as_string(board.game_state.castling);
@@ -146,13 +155,31 @@ fn metadata(board: &Board) {
     let hmc = board.game_state.halfmove_clock;
     let fmn = board.game_state.fullmove_number;
 
-    println!("{:<20}{:x}", "Zobrist key:", board.game_state.zobrist_key);
-    println!("{:<20}{}", "Active Color:", active_color);
-    println!("{:<20}{}", "Castling:", castling);
-    println!("{:<20}{}", "En Passant:", en_passant);
-    println!("{:<20}{}", "Half-move clock:", hmc);
-    println!("{:<20}{}", "Full-move number:", fmn);
-    println!();
+    let _ = writeln!(
+        text,
+        "{:<20}{:x}",
+        "Zobrist key:", board.game_state.zobrist_key
+    );
+    let _ = writeln!(text, "{:<20}{}", "Active Color:", active_color);
+    let _ = writeln!(text, "{:<20}{}", "Castling:", castling);
+    let _ = writeln!(text, "{:<20}{}", "En Passant:", en_passant);
+    let _ = writeln!(text, "{:<20}{}", "Half-move clock:", hmc);
+    let _ = writeln!(text, "{:<20}{}", "Full-move number:", fmn);
+    text.push('\n');
+}
+
+// Prints the game state before each of the moves played on the board.
+pub fn history(board: &Board) {
+    let length = board.history.len();
+
+    if length == 0 {
+        println!("No history available.");
+    }
+
+    for i in 0..length {
+        let h = board.history.get_ref(i);
+        println!("{:<3}| ply: {} {}", i, i + 1, h.as_string());
+    }
 }
 
 // Converts castling permissions to a string.
@@ -180,7 +207,9 @@ pub fn bitboard(bitboard: Bitboard, mark_square: Option<u8>) {
     const SQUARE_OCCUPIED: char = '1';
     let mut ascii_board: AsciiBoard = [CHAR_ES; 64];
     put_character_on_square(bitboard, &mut ascii_board, SQUARE_OCCUPIED);
-    to_console(&ascii_board, mark_square);
+    let mut text = String::new();
+    to_text(&ascii_board, mark_square, &mut text);
+    print!("{text}");
 }
 
 // Prints a given movelist to the screen.
@@ -24,12 +24,13 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 mod create;
 pub mod defs;
 mod init;
+mod legal;
 mod magics;
 mod movelist;
 
 use crate::{
     board::{
-        defs::{Pieces, Squares, BB_RANKS, BB_SQUARES},
+        defs::{Files, Pieces, Wing, BB_RANKS, BB_SQUARES},
         Board,
     },
     defs::{Bitboard, Castling, NrOf, Piece, Side, Sides, Square, EMPTY},
@@ -47,9 +48,12 @@ const PROMOTION_PIECES: [usize; 4] = [Pieces::QUEEN, Pieces::ROOK, Pieces::BISHO
 // or a bishop.
 pub const ROOK_TABLE_SIZE: usize = 102_400; // Total permutations of all rook blocker boards.
 pub const BISHOP_TABLE_SIZE: usize = 5_248; // Total permutations of all bishop blocker boards.
+pub const LINE_TABLE_SIZE: usize = NrOf::SQUARES * NrOf::SQUARES; // One entry per square pair.
 
 // The move generator struct holds the attack table for each piece, and the
-// tables with magic numbers for the rook and bishop.
+// tables with magic numbers for the rook and bishop. The "between" and
+// "line" tables are used by the legal move generator to handle pins and
+// check evasions.
 pub struct MoveGenerator {
     king: [Bitboard; NrOf::SQUARES],
     knight: [Bitboard; NrOf::SQUARES],
@@ -58,6 +62,8 @@ pub struct MoveGenerator {
     bishop: Vec<Bitboard>,
     rook_magics: [Magic; NrOf::SQUARES],
     bishop_magics: [Magic; NrOf::SQUARES],
+    between: Vec<Bitboard>,
+    line: Vec<Bitboard>,
 }
 
 impl MoveGenerator {
@@ -72,12 +78,15 @@ impl MoveGenerator {
             bishop: vec![EMPTY; BISHOP_TABLE_SIZE],
             rook_magics: [magics; NrOf::SQUARES],
             bishop_magics: [magics; NrOf::SQUARES],
+            between: vec![EMPTY; LINE_TABLE_SIZE],
+            line: vec![EMPTY; LINE_TABLE_SIZE],
         };
         mg.init_king();
         mg.init_knight();
         mg.init_pawns();
         mg.init_magics(Pieces::ROOK);
         mg.init_magics(Pieces::BISHOP);
+        mg.init_lines();
         mg
     }
 
@@ -134,6 +143,24 @@ impl MoveGenerator {
     pub fn get_pawn_attacks(&self, side: Side, square: Square) -> Bitboard {
         self.pawns[side][square]
     }
+
+    // Return the squares strictly between two squares on the same rank,
+    // file or diagonal. If the squares are not aligned, this is empty.
+    pub fn get_between(&self, a: Square, b: Square) -> Bitboard {
+        self.between[a * NrOf::SQUARES + b]
+    }
+
+    // Return the entire rank, file or diagonal going through both
+    // squares. If the squares are not aligned, this is empty.
+    pub fn get_line(&self, a: Square, b: Square) -> Bitboard {
+        self.line[a * NrOf::SQUARES + b]
+    }
+}
+
+impl Default for MoveGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 // *** === Getting the actual pseudo-legal moves. === *** //
@@ -174,6 +201,18 @@ impl MoveGenerator {
     }
 
     pub fn pawns(&self, board: &Board, list: &mut MoveList, mt: MoveType) {
+        let mut bb_pawns = board.get_pieces(Pieces::PAWN, board.us());
+
+        // As long as there are pawns, generate moves for each of them.
+        while bb_pawns > 0 {
+            let from = bits::next(&mut bb_pawns);
+            let bb_moves = self.pawn_targets(board, from, mt);
+            self.add_move(board, Pieces::PAWN, from, bb_moves, list);
+        }
+    }
+
+    // Returns the squares the pawn on the given square can move to.
+    fn pawn_targets(&self, board: &Board, from: Square, mt: MoveType) -> Bitboard {
         const UP: i8 = 8;
         const DOWN: i8 = -8;
 
@@ -184,112 +223,100 @@ impl MoveGenerator {
         let bb_fourth = BB_RANKS[Board::fourth_rank(us)];
         let direction = if us == Sides::WHITE { UP } else { DOWN };
         let rotation_count = (NrOf::SQUARES as i8 + direction) as u32;
-        let mut bb_pawns = board.get_pieces(Pieces::PAWN, us);
-
-        // As long as there are pawns, generate moves for each of them.
-        while bb_pawns > 0 {
-            let from = bits::next(&mut bb_pawns);
-            let to = (from as i8 + direction) as usize;
-            let mut bb_moves = 0;
-
-            // Generate pawn pushes
-            if mt == MoveType::All || mt == MoveType::Quiet {
-                let bb_push = BB_SQUARES[to];
-                let bb_one_step = bb_push & bb_empty;
-                let bb_two_step = bb_one_step.rotate_left(rotation_count) & bb_empty & bb_fourth;
-                bb_moves |= bb_one_step | bb_two_step;
-            }
+        let to = (from as i8 + direction) as usize;
+        let mut bb_moves = 0;
 
-            // Generate pawn captures
-            if mt == MoveType::All || mt == MoveType::Capture {
-                let bb_targets = self.get_pawn_attacks(us, from);
-                let bb_captures = bb_targets & bb_opponent_pieces;
-                let bb_ep_capture = match board.game_state.en_passant {
-                    Some(ep) => bb_targets & BB_SQUARES[ep as usize],
-                    None => 0,
-                };
-                bb_moves |= bb_captures | bb_ep_capture;
-            }
+        // Generate pawn pushes
+        if mt == MoveType::All || mt == MoveType::Quiet {
+            let bb_push = BB_SQUARES[to];
+            let bb_one_step = bb_push & bb_empty;
+            let bb_two_step = bb_one_step.rotate_left(rotation_count) & bb_empty & bb_fourth;
+            bb_moves |= bb_one_step | bb_two_step;
+        }
 
-            self.add_move(board, Pieces::PAWN, from, bb_moves, list);
+        // Generate pawn captures
+        if mt == MoveType::All || mt == MoveType::Capture {
+            let bb_targets = self.get_pawn_attacks(us, from);
+            let bb_captures = bb_targets & bb_opponent_pieces;
+            let bb_ep_capture = match board.game_state.en_passant {
+                Some(ep) => bb_targets & BB_SQUARES[ep as usize],
+                None => 0,
+            };
+            bb_moves |= bb_captures | bb_ep_capture;
         }
+
+        bb_moves
     }
 
     pub fn castling(&self, board: &Board, list: &mut MoveList) {
         // Create shorthand variables.
         let us = board.us();
         let opponent = board.opponent();
-        let castle_perms_white = (board.game_state.castling & (Castling::WK | Castling::WQ)) > 0;
-        let castle_perms_black = (board.game_state.castling & (Castling::BK | Castling::BQ)) > 0;
         let bb_occupancy = board.occupancy();
-        let mut bb_king = board.get_pieces(Pieces::KING, us);
-        let from = bits::next(&mut bb_king);
-
-        // Generate castling moves for white.
-        if us == Sides::WHITE && castle_perms_white {
-            // Kingside
-            if board.game_state.castling & Castling::WK > 0 {
-                let bb_kingside_blockers = BB_SQUARES[Squares::F1] | BB_SQUARES[Squares::G1];
-                let is_kingside_blocked = (bb_occupancy & bb_kingside_blockers) > 0;
-
-                if !is_kingside_blocked
-                    && !self.square_attacked(board, opponent, Squares::E1)
-                    && !self.square_attacked(board, opponent, Squares::F1)
-                {
-                    let to = BB_SQUARES[from] << 2;
-                    self.add_move(board, Pieces::KING, from, to, list);
-                }
+        let king_from = board.king_square(us);
+
+        // This works for both standard chess and Chess960. The king and
+        // the rook may start on any file, but they always end up on the
+        // same squares as they do in standard chess.
+        for wing in [Wing::KINGSIDE, Wing::QUEENSIDE] {
+            let rook_from = board.castling_rooks[us][wing];
+            let (king_to, rook_to) = Board::castling_squares(us, wing);
+            let has_permission = (board.game_state.castling & Castling::RIGHTS[us][wing]) > 0;
+            let has_rook = (board.get_pieces(Pieces::ROOK, us) & BB_SQUARES[rook_from]) > 0;
+
+            if !has_permission || !has_rook {
+                continue;
             }
 
-            if board.game_state.castling & Castling::WQ > 0 {
-                // Queenside
-                let bb_queenside_blockers =
-                    BB_SQUARES[Squares::B1] | BB_SQUARES[Squares::C1] | BB_SQUARES[Squares::D1];
-                let is_queenside_blocked = (bb_occupancy & bb_queenside_blockers) > 0;
-
-                if !is_queenside_blocked
-                    && !self.square_attacked(board, opponent, Squares::E1)
-                    && !self.square_attacked(board, opponent, Squares::D1)
-                {
-                    let to = BB_SQUARES[from] >> 2;
-                    self.add_move(board, Pieces::KING, from, to, list);
-                }
+            // All the squares the king and rook move over or to must be
+            // empty, except for the squares of the king and rook themselves.
+            let bb_king_path = self.get_between(king_from, king_to) | BB_SQUARES[king_to];
+            let bb_rook_path = self.get_between(rook_from, rook_to) | BB_SQUARES[rook_to];
+            let bb_castling_pieces = BB_SQUARES[king_from] | BB_SQUARES[rook_from];
+            let is_blocked =
+                ((bb_king_path | bb_rook_path) & !bb_castling_pieces & bb_occupancy) > 0;
+
+            // The king may not be in check, or pass through an attacked
+            // square. The square the king lands on is checked by make().
+            let mut bb_king_squares = (bb_king_path & !BB_SQUARES[king_to]) | BB_SQUARES[king_from];
+            let mut is_attacked = false;
+            while bb_king_squares > 0 && !is_attacked {
+                let square = bits::next(&mut bb_king_squares);
+                is_attacked = self.square_attacked(board, opponent, square);
             }
-        }
 
-        // Generate castling moves for black.
-        if us == Sides::BLACK && castle_perms_black {
-            // Kingside
-            if board.game_state.castling & Castling::BK > 0 {
-                let bb_kingside_blockers = BB_SQUARES[Squares::F8] | BB_SQUARES[Squares::G8];
-                let is_kingside_blocked = (bb_occupancy & bb_kingside_blockers) > 0;
-
-                if !is_kingside_blocked
-                    && !self.square_attacked(board, opponent, Squares::E8)
-                    && !self.square_attacked(board, opponent, Squares::F8)
-                {
-                    let to = BB_SQUARES[from] << 2;
-                    self.add_move(board, Pieces::KING, from, to, list);
-                }
-            }
-
-            // Queenside
-            if board.game_state.castling & Castling::BQ > 0 {
-                let bb_queenside_blockers =
-                    BB_SQUARES[Squares::B8] | BB_SQUARES[Squares::C8] | BB_SQUARES[Squares::D8];
-                let is_queenside_blocked = (bb_occupancy & bb_queenside_blockers) > 0;
-
-                if !is_queenside_blocked
-                    && !self.square_attacked(board, opponent, Squares::E8)
-                    && !self.square_attacked(board, opponent, Squares::D8)
-                {
-                    let to = BB_SQUARES[from] >> 2;
-                    self.add_move(board, Pieces::KING, from, to, list);
-                }
+            if !is_blocked && !is_attacked {
+                self.add_castling_move(board, king_from, king_to, rook_from, list);
             }
         }
     }
 
+    // Castling moves are encoded as the king moving two squares, as in the
+    // UCI notation for standard chess. In Chess960, the king can start on
+    // the square it castles to, so castling moves are encoded as the king
+    // capturing its own rook, which is the UCI notation for Chess960. This
+    // notation is also used if the king doesn't start on the e-file, so the
+    // direction of the move always shows which wing the king castles to.
+    fn add_castling_move(
+        &self,
+        board: &Board,
+        king_from: Square,
+        king_to: Square,
+        rook_from: Square,
+        list: &mut MoveList,
+    ) {
+        let is_standard = !board.chess960 && king_from % 8 == Files::E;
+        let to = if is_standard { king_to } else { rook_from };
+        let move_data = Pieces::KING
+            | king_from << Shift::FROM_SQ
+            | to << Shift::TO_SQ
+            | Pieces::NONE << Shift::CAPTURE
+            | Pieces::NONE << Shift::PROMOTION
+            | 1 << Shift::CASTLING;
+
+        list.push(Move::new(move_data));
+    }
+
     // Add the generated moves to the move list.
     pub fn add_move(
         &self,
@@ -316,7 +343,6 @@ impl MoveGenerator {
             };
             let promotion = is_pawn && Board::square_on_rank(to_square, promotion_rank);
             let double_step = is_pawn && ((to_square as i8 - from as i8).abs() == 16);
-            let castling = (piece == Pieces::KING) && ((to_square as i8 - from as i8).abs() == 2);
 
             // Gather all data for this move into one 64-bit integer.
             let mut move_data = (piece)
@@ -324,8 +350,7 @@ impl MoveGenerator {
                 | to_square << Shift::TO_SQ
                 | capture << Shift::CAPTURE
                 | (en_passant as usize) << Shift::EN_PASSANT
-                | (double_step as usize) << Shift::DOUBLE_STEP
-                | (castling as usize) << Shift::CASTLING;
+                | (double_step as usize) << Shift::DOUBLE_STEP;
 
             // Push the move to the piece list...
             if !promotion {
@@ -343,6 +368,40 @@ impl MoveGenerator {
 }
 
 impl MoveGenerator {
+    // Determine if a move is pseudo-legal in the current position, without
+    // generating all the moves. This is used to check moves that come from
+    // elsewhere, such as the TT or the killer list. Only the moves for the
+    // piece on the from-square are generated, and the given move must be
+    // exactly equal to one of them.
+    pub fn is_pseudo_legal(&self, board: &Board, m: Move) -> bool {
+        let us = board.us();
+        let piece = m.piece();
+        let from = m.from();
+        let mut list = MoveList::new();
+
+        // The moving piece must be ours, and it must be on the from-square.
+        if piece >= Pieces::NONE || (board.get_pieces(piece, us) & BB_SQUARES[from]) == 0 {
+            return false;
+        }
+
+        if m.castling() {
+            self.castling(board, &mut list);
+        } else {
+            let bb_own_pieces = board.bb_side[us];
+            let bb_target = match piece {
+                Pieces::KING | Pieces::KNIGHT => self.get_non_slider_attacks(piece, from),
+                Pieces::QUEEN | Pieces::ROOK | Pieces::BISHOP => {
+                    self.get_slider_attacks(piece, from, board.occupancy())
+                }
+                _ => self.pawn_targets(board, from, MoveType::All),
+            };
+            let bb_to = bb_target & !bb_own_pieces & BB_SQUARES[m.to()];
+            self.add_move(board, piece, from, bb_to, &mut list);
+        }
+
+        (0..list.len()).any(|i| list.get_move(i).get_move() == m.get_move())
+    }
+
     #[cfg_attr(debug_assertions, inline(never))]
     #[cfg_attr(not(debug_assertions), inline(always))]
     // Determine if a square is attacked by 'attacker', on the given board.
@@ -371,4 +430,26 @@ impl MoveGenerator {
             || (bb_knight & attackers[Pieces::KNIGHT] > 0)
             || (bb_pawns & attackers[Pieces::PAWN] > 0)
     }
+
+    // Return a bitboard with all the pieces of 'attacker' that attack the
+    // given square. This uses the super-piece method as above, but with a
+    // custom occupancy, so pieces can be removed from or added to the board
+    // without actually making a move.
+    pub fn attackers_to(
+        &self,
+        board: &Board,
+        attacker: Side,
+        square: Square,
+        occupancy: Bitboard,
+    ) -> Bitboard {
+        let attackers = board.bb_pieces[attacker];
+        let bb_rook = self.get_slider_attacks(Pieces::ROOK, square, occupancy);
+        let bb_bishop = self.get_slider_attacks(Pieces::BISHOP, square, occupancy);
+
+        (self.get_non_slider_attacks(Pieces::KING, square) & attackers[Pieces::KING])
+            | (self.get_non_slider_attacks(Pieces::KNIGHT, square) & attackers[Pieces::KNIGHT])
+            | (self.get_pawn_attacks(attacker ^ 1, square) & attackers[Pieces::PAWN])
+            | (bb_rook & (attackers[Pieces::ROOK] | attackers[Pieces::QUEEN]))
+            | (bb_bishop & (attackers[Pieces::BISHOP] | attackers[Pieces::QUEEN]))
+    }
 }
@@ -96,6 +96,20 @@ pub enum MoveType {
     All,
 }
 
+// Selects the move generator. "Pseudo" generates pseudo-legal moves which
+// must be verified by make(). "Legal" generates only legal moves, which
+// can be executed by make_legal().
+#[derive(Copy, Clone, PartialEq)]
+pub enum MoveGenMode {
+    Pseudo,
+    Legal,
+}
+impl MoveGenMode {
+    pub const PSEUDO: &'static str = "pseudo";
+    pub const LEGAL: &'static str = "legal";
+    pub const COMPARE: &'static str = "compare";
+}
+
 /* This struct contains the move data. It's a struct so it can be instantiated, and then
  * it can provide all of the methods associated with it to easily decode the move data. */
 #[derive(Copy, Clone, PartialEq)]
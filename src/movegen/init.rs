@@ -26,8 +26,9 @@ use super::{
     MoveGenerator, BISHOP_TABLE_SIZE, ROOK_TABLE_SIZE,
 };
 use crate::{
-    board::defs::{Files, Pieces, RangeOf, Ranks, BB_FILES, BB_RANKS, BB_SQUARES},
-    defs::{Piece, Sides, EMPTY},
+    board::defs::{Direction, Files, Pieces, RangeOf, Ranks, BB_FILES, BB_RANKS, BB_SQUARES},
+    defs::{NrOf, Piece, Sides, EMPTY},
+    misc::bits,
 };
 
 impl MoveGenerator {
@@ -94,6 +95,48 @@ impl MoveGenerator {
         }
     }
 
+    /**
+     * Generate the "between" and "line" tables for each pair of squares.
+     * For each square and each pair of opposite directions, walk both rays
+     * on an empty board. Every square hit by one of those rays is aligned
+     * with the starting square. The squares between the two are the ray up
+     * to (but not including) the square that was hit. The line is the entire
+     * rank, file or diagonal through both squares. Pairs of squares that are
+     * not aligned keep an empty bitboard in both tables.
+     */
+    pub fn init_lines(&mut self) {
+        const DIRECTIONS: [(Direction, Direction); 4] = [
+            (Direction::Up, Direction::Down),
+            (Direction::Right, Direction::Left),
+            (Direction::UpLeft, Direction::DownRight),
+            (Direction::UpRight, Direction::DownLeft),
+        ];
+
+        for sq in RangeOf::SQUARES {
+            for (forward, backward) in DIRECTIONS.iter() {
+                let bb_forward = MoveGenerator::bb_ray(EMPTY, sq, *forward);
+                let bb_backward = MoveGenerator::bb_ray(EMPTY, sq, *backward);
+                let bb_line = bb_forward | bb_backward | BB_SQUARES[sq];
+                let mut bb_targets = bb_forward | bb_backward;
+
+                while bb_targets > 0 {
+                    let target = bits::next(&mut bb_targets);
+                    let bb_target = BB_SQUARES[target];
+                    let direction = if bb_forward & bb_target > 0 {
+                        *forward
+                    } else {
+                        *backward
+                    };
+                    let bb_ray = MoveGenerator::bb_ray(bb_target, sq, direction);
+                    let index = sq * NrOf::SQUARES + target;
+
+                    self.between[index] = bb_ray & !bb_target;
+                    self.line[index] = bb_line;
+                }
+            }
+        }
+    }
+
     /** This is the main part of the module: it indexes all of the atack boards
      * using the magic numbers from the "magics" module. This builds an attack database
      * for sliding pieces, for each square and each combination of blocker boards. A
@@ -0,0 +1,252 @@
+/* =======================================================================
+Rustic is a chess playing engine.
+Copyright (C) 2019-2024, Marcel Vanthoor
+https://rustic-chess.org/
+
+Rustic is written in the Rust programming language. It is an original
+work, not derived from any engine that came before it. However, it does
+use a lot of concepts which are well-known and are in use by most if not
+all classical alpha/beta-based chess engines.
+
+Rustic is free software: you can redistribute it and/or modify it under
+the terms of the GNU General Public License version 3 as published by
+the Free Software Foundation.
+
+Rustic is distributed in the hope that it will be useful, but WITHOUT
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+for more details.
+
+You should have received a copy of the GNU General Public License along
+with this program.  If not, see <http://www.gnu.org/licenses/>.
+======================================================================= */
+
+// legal.rs implements the fully legal move generator. The pseudo-legal
+// generator leaves it to make() to find out if a move leaves the king in
+// check. This generator determines the checking pieces and the pinned
+// pieces once per position, and then only generates moves that are legal.
+// Moves from this generator can be executed with Board::make_legal().
+
+use super::{
+    defs::{MoveList, MoveType},
+    MoveGenerator,
+};
+use crate::{
+    board::{
+        defs::{Pieces, BB_RANKS, BB_SQUARES},
+        Board,
+    },
+    defs::{Bitboard, NrOf, Side, Sides, Square},
+    misc::bits,
+};
+use if_chain::if_chain;
+
+// The sliding and jumping pieces are handled in one loop, in the same
+// order as the pseudo-legal generator uses.
+const OTHER_PIECES: [usize; 4] = [Pieces::KNIGHT, Pieces::ROOK, Pieces::BISHOP, Pieces::QUEEN];
+
+impl MoveGenerator {
+    // Generates only legal moves for the side that is to move. The
+    // MoveType parameter works the same as for generate_moves().
+    pub fn generate_legal_moves(&self, board: &Board, ml: &mut MoveList, mt: MoveType) {
+        // Create shorthand variables.
+        let us = board.us();
+        let opponent = board.opponent();
+        let king_square = board.king_square(us);
+        let bb_occupancy = board.occupancy();
+        let bb_checkers = self.attackers_to(board, opponent, king_square, bb_occupancy);
+        let bb_pinned = self.pinned_pieces(board, us, king_square);
+        let nr_of_checkers = bb_checkers.count_ones();
+
+        // Squares the pieces are allowed to move to, according to move type.
+        let bb_move_type = match mt {
+            MoveType::All => !board.bb_side[us],
+            MoveType::Quiet => !bb_occupancy,
+            MoveType::Capture => board.bb_side[opponent],
+        };
+
+        // The king can't move to a square that is attacked. The king itself
+        // is removed from the occupancy, so it can't hide behind itself when
+        // moving away from a slider along the checking ray.
+        let bb_no_king = bb_occupancy & !BB_SQUARES[king_square];
+        let mut bb_king_targets = self.get_non_slider_attacks(Pieces::KING, king_square);
+        let mut bb_king_moves = 0;
+        bb_king_targets &= bb_move_type;
+        while bb_king_targets > 0 {
+            let to = bits::next(&mut bb_king_targets);
+            if self.attackers_to(board, opponent, to, bb_no_king) == 0 {
+                bb_king_moves |= BB_SQUARES[to];
+            }
+        }
+        self.add_move(board, Pieces::KING, king_square, bb_king_moves, ml);
+
+        // In double check, only the king can move.
+        if nr_of_checkers > 1 {
+            return;
+        }
+
+        // If in check by a single piece, the other pieces must either
+        // capture the checker or block the check.
+        let bb_evasion = if nr_of_checkers == 1 {
+            let checker = bb_checkers.trailing_zeros() as Square;
+            bb_checkers | self.get_between(king_square, checker)
+        } else {
+            !0
+        };
+
+        // Generate moves for knights and sliders. A pinned piece may only
+        // move along the line through the king and itself.
+        for piece in OTHER_PIECES {
+            let mut bb_pieces = board.get_pieces(piece, us);
+            while bb_pieces > 0 {
+                let from = bits::next(&mut bb_pieces);
+                let mut bb_moves = match piece {
+                    Pieces::KNIGHT => self.get_non_slider_attacks(piece, from),
+                    _ => self.get_slider_attacks(piece, from, bb_occupancy),
+                };
+
+                bb_moves &= bb_move_type & bb_evasion;
+                if bb_pinned & BB_SQUARES[from] > 0 {
+                    bb_moves &= self.get_line(king_square, from);
+                }
+
+                self.add_move(board, piece, from, bb_moves, ml);
+            }
+        }
+
+        self.legal_pawns(board, ml, mt, bb_evasion, bb_pinned);
+
+        // Castling is not possible while in check.
+        if nr_of_checkers == 0 && (mt == MoveType::All || mt == MoveType::Quiet) {
+            self.legal_castling(board, ml);
+        }
+    }
+
+    // Determine the pieces of the given side that are pinned to their king.
+    // Find the opponent's sliders that would attack the king on an empty
+    // board. If there is exactly one piece between such a slider and the
+    // king, and that piece is ours, it is pinned.
+    pub fn pinned_pieces(&self, board: &Board, side: Side, king_square: Square) -> Bitboard {
+        let opponent = side ^ 1;
+        let bb_occupancy = board.occupancy();
+        let bb_queens = board.get_pieces(Pieces::QUEEN, opponent);
+        let bb_rooks = board.get_pieces(Pieces::ROOK, opponent) | bb_queens;
+        let bb_bishops = board.get_pieces(Pieces::BISHOP, opponent) | bb_queens;
+        let mut bb_pinned = 0;
+
+        let mut bb_snipers = (self.get_slider_attacks(Pieces::ROOK, king_square, 0) & bb_rooks)
+            | (self.get_slider_attacks(Pieces::BISHOP, king_square, 0) & bb_bishops);
+
+        while bb_snipers > 0 {
+            let sniper = bits::next(&mut bb_snipers);
+            let bb_blockers = self.get_between(king_square, sniper) & bb_occupancy;
+            if bb_blockers.count_ones() == 1 && (bb_blockers & board.bb_side[side]) > 0 {
+                bb_pinned |= bb_blockers;
+            }
+        }
+
+        bb_pinned
+    }
+
+    // Generate legal pawn moves. These follow the same rules as the other
+    // pieces with regard to check evasion and pins, except for en-passant.
+    fn legal_pawns(
+        &self,
+        board: &Board,
+        list: &mut MoveList,
+        mt: MoveType,
+        bb_evasion: Bitboard,
+        bb_pinned: Bitboard,
+    ) {
+        const UP: i8 = 8;
+        const DOWN: i8 = -8;
+
+        // Create shorthand variables.
+        let us = board.us();
+        let king_square = board.king_square(us);
+        let bb_opponent_pieces = board.bb_side[board.opponent()];
+        let bb_empty = !board.occupancy();
+        let bb_fourth = BB_RANKS[Board::fourth_rank(us)];
+        let direction = if us == Sides::WHITE { UP } else { DOWN };
+        let rotation_count = (NrOf::SQUARES as i8 + direction) as u32;
+        let mut bb_pawns = board.get_pieces(Pieces::PAWN, us);
+
+        while bb_pawns > 0 {
+            let from = bits::next(&mut bb_pawns);
+            let to = (from as i8 + direction) as usize;
+            let mut bb_moves = 0;
+
+            // Generate pawn pushes
+            if mt == MoveType::All || mt == MoveType::Quiet {
+                let bb_push = BB_SQUARES[to];
+                let bb_one_step = bb_push & bb_empty;
+                let bb_two_step = bb_one_step.rotate_left(rotation_count) & bb_empty & bb_fourth;
+                bb_moves |= bb_one_step | bb_two_step;
+            }
+
+            // Generate pawn captures, without en-passant.
+            let bb_targets = self.get_pawn_attacks(us, from);
+            if mt == MoveType::All || mt == MoveType::Capture {
+                bb_moves |= bb_targets & bb_opponent_pieces;
+            }
+
+            bb_moves &= bb_evasion;
+            if bb_pinned & BB_SQUARES[from] > 0 {
+                bb_moves &= self.get_line(king_square, from);
+            }
+
+            // En-passant removes two pawns from the same rank at once, which
+            // can expose the king in ways the pin detection doesn't see.
+            // Therefore, it is verified by checking the king's safety on the
+            // board as it would be after the capture.
+            if_chain! {
+                if mt == MoveType::All || mt == MoveType::Capture;
+                if let Some(ep) = board.game_state.en_passant;
+                if bb_targets & BB_SQUARES[ep as usize] > 0;
+                if self.en_passant_is_legal(board, from, ep as usize);
+                then {
+                    bb_moves |= BB_SQUARES[ep as usize];
+                }
+            }
+
+            self.add_move(board, Pieces::PAWN, from, bb_moves, list);
+        }
+    }
+
+    // Determine if capturing en-passant leaves our own king in check.
+    fn en_passant_is_legal(&self, board: &Board, from: Square, ep: Square) -> bool {
+        let us = board.us();
+        let captured = ep ^ 8;
+        let bb_occupancy =
+            (board.occupancy() & !BB_SQUARES[from] & !BB_SQUARES[captured]) | BB_SQUARES[ep];
+        let bb_attackers = self.attackers_to(board, us ^ 1, board.king_square(us), bb_occupancy);
+
+        // The captured pawn is gone after the capture, so it can't attack.
+        (bb_attackers & !BB_SQUARES[captured]) == 0
+    }
+
+    // Generate castling moves. The pseudo-legal castling generator already
+    // verifies that the king is not in check and doesn't pass through an
+    // attacked square. The only thing left to check is the square the king
+    // lands on. In Chess960, the castling rook may be shielding that square,
+    // so the king and rook are both removed from the occupancy.
+    fn legal_castling(&self, board: &Board, list: &mut MoveList) {
+        let us = board.us();
+        let opponent = board.opponent();
+        let mut castling_moves = MoveList::new();
+        self.castling(board, &mut castling_moves);
+
+        for i in 0..castling_moves.len() {
+            let m = castling_moves.get_move(i);
+            let wing = Board::castling_wing(m);
+            let (king_to, _) = Board::castling_squares(us, wing);
+            let bb_castling_pieces =
+                BB_SQUARES[m.from()] | BB_SQUARES[board.castling_rooks[us][wing]];
+            let bb_occupancy = board.occupancy() & !bb_castling_pieces;
+
+            if self.attackers_to(board, opponent, king_to, bb_occupancy) == 0 {
+                list.push(m);
+            }
+        }
+    }
+}
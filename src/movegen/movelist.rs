@@ -71,6 +71,10 @@ impl MoveList {
         self.count
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
     // Return the move at the given index. If out of bounds, the program crashes.
     pub fn get_move(&self, index: u8) -> Move {
         self.list[index as usize]
@@ -91,3 +95,9 @@ impl MoveList {
         }
     }
 }
+
+impl Default for MoveList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
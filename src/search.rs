@@ -26,6 +26,7 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 mod alpha_beta;
 pub mod defs;
 mod iter_deep;
+mod picker;
 mod qsearch;
 mod sorting;
 mod time;
@@ -33,9 +34,10 @@ mod utils;
 
 use crate::{
     board::Board,
-    engine::defs::{ErrFatal, Information},
-    engine::defs::{SearchData, TT},
-    movegen::MoveGenerator,
+    defs::ErrFatal,
+    movegen::{defs::Move, MoveGenerator},
+    search::defs::SearchMode,
+    transposition::{SearchData, TT},
 };
 use crossbeam_channel::Sender;
 use defs::{
@@ -52,6 +54,13 @@ pub struct Search {
     control_tx: Option<Sender<SearchControl>>,
 }
 
+/// The outcome of a synchronous search: the best move, and the summary of
+/// the last depth that was completed (if any).
+pub struct SearchOutcome {
+    pub best_move: Move,
+    pub summary: Option<SearchSummary>,
+}
+
 impl Search {
     pub fn new() -> Self {
         Self {
@@ -62,9 +71,9 @@ impl Search {
 
     pub fn init(
         &mut self,
-        report_tx: Sender<Information>, // Used to send information to engine.
-        board: Arc<Mutex<Board>>,       // Arc pointer to engine's board.
-        mg: Arc<MoveGenerator>,         // Arc pointer to engine's move generator.
+        report_tx: Sender<SearchReport>, // Used to send reports to the engine.
+        board: Arc<Mutex<Board>>,        // Arc pointer to engine's board.
+        mg: Arc<MoveGenerator>,          // Arc pointer to engine's move generator.
         tt: Arc<Mutex<TT<SearchData>>>,
         tt_enabled: bool,
     ) {
@@ -81,6 +90,7 @@ impl Search {
             let arc_mg = Arc::clone(&mg);
             let arc_tt = Arc::clone(&tt);
             let mut search_params = SearchParams::new();
+            let mut search_id = 0;
 
             let mut quit = false;
             let mut halt = true;
@@ -92,7 +102,8 @@ impl Search {
 
                 // And react accordingly.
                 match cmd {
-                    SearchControl::Start(sp) => {
+                    SearchControl::Start(id, sp) => {
+                        search_id = id;
                         search_params = sp;
                         halt = false; // This will start the search.
                     }
@@ -127,8 +138,8 @@ impl Search {
                     let (best_move, terminate) = Search::iterative_deepening(&mut search_refs);
 
                     // Inform the engine that the search has finished.
-                    let information = Information::Search(SearchReport::Finished(best_move));
-                    t_report_tx.send(information).expect(ErrFatal::CHANNEL);
+                    let report = SearchReport::Finished(search_id, best_move);
+                    t_report_tx.send(report).expect(ErrFatal::CHANNEL);
 
                     // If the search was finished due to a Stop or Quit
                     // command then either halt or quit the search.
@@ -165,4 +176,60 @@ impl Search {
             h.join().expect(ErrFatal::THREAD);
         }
     }
+
+    /// Search the given position in the calling thread, and return when
+    /// the search is done. The board is not changed. The search limits
+    /// are given in `params`. Nothing can stop an infinite search here, so
+    /// SearchMode::Infinite searches up to `params.depth` instead. The
+    /// transposition table belongs to the caller, so it can be reused
+    /// between searches; a TT of 0 MB disables it.
+    pub fn run(
+        board: &Board,
+        mg: &Arc<MoveGenerator>,
+        tt: &Arc<Mutex<TT<SearchData>>>,
+        params: &SearchParams,
+    ) -> SearchOutcome {
+        // The search sends its reports and listens for commands through
+        // channels. Nobody else will use these, so the reports are
+        // collected afterward and no commands are ever sent.
+        let (report_tx, report_rx) = crossbeam_channel::unbounded::<SearchReport>();
+        let (_control_tx, control_rx) = crossbeam_channel::unbounded::<SearchControl>();
+
+        let mut board = board.clone();
+        let mut search_params = *params;
+        let mut search_info = SearchInfo::new();
+
+        if search_params.search_mode == SearchMode::Infinite {
+            search_params.search_mode = SearchMode::Depth;
+        }
+
+        let mut search_refs = SearchRefs {
+            board: &mut board,
+            mg,
+            tt,
+            tt_enabled: true,
+            search_params: &mut search_params,
+            search_info: &mut search_info,
+            control_rx: &control_rx,
+            report_tx: &report_tx,
+        };
+
+        let (best_move, _) = Search::iterative_deepening(&mut search_refs);
+
+        // Keep the summary of the deepest completed iteration.
+        let mut summary = None;
+        while let Ok(report) = report_rx.try_recv() {
+            if let SearchReport::SearchSummary(s) = report {
+                summary = Some(s);
+            }
+        }
+
+        SearchOutcome { best_move, summary }
+    }
+}
+
+impl Default for Search {
+    fn default() -> Self {
+        Self::new()
+    }
 }
@@ -22,15 +22,19 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 ======================================================================= */
 
 use super::{
-    defs::{SearchTerminate, CHECKMATE, CHECK_TERMINATION, DRAW, INF, SEND_STATS, STALEMATE},
+    defs::{
+        SearchTerminate, CHECKMATE, CHECKMATE_THRESHOLD, CHECK_TERMINATION, DRAW, INF, RFP_MARGIN,
+        RFP_MAX_DEPTH, SEND_STATS, STALEMATE,
+    },
+    picker::MovePicker,
     Search, SearchRefs,
 };
 use crate::{
     board::defs::Pieces,
-    defs::MAX_PLY,
-    engine::defs::{ErrFatal, HashFlag, SearchData},
+    defs::{ErrFatal, MAX_PLY},
     evaluation,
-    movegen::defs::{Move, MoveList, MoveType, ShortMove},
+    movegen::defs::{Move, ShortMove},
+    transposition::{HashFlag, SearchData},
 };
 
 impl Search {
@@ -43,6 +47,7 @@ impl Search {
     ) -> i16 {
         let quiet = refs.search_params.quiet; // If quiet, don't send intermediate stats.
         let is_root = refs.search_info.ply == 0; // At root if no moves were played.
+        let is_pv = (beta as i32 - alpha as i32) > 1; // Open window; i32 as -INF..INF overflows i16.
         let mut do_pvs = false; // Used for PVS (Principal Variation Search)
 
         // Check if termination condition is met.
@@ -83,9 +88,10 @@ impl Search {
         // Count this node, as it is not aborted or searched by QSearch.
         refs.search_info.nodes += 1;
 
-        // Variables to hold TT value and move if any.
+        // Variables to hold TT value, move and static evaluation if any.
         let mut tt_value: Option<i16> = None;
         let mut tt_move: ShortMove = ShortMove::new(0);
+        let mut tt_eval: Option<i16> = None;
 
         // Probe the TT for information.
         if refs.tt_enabled {
@@ -98,6 +104,7 @@ impl Search {
                 let tt_result = data.get(depth, refs.search_info.ply, alpha, beta);
                 tt_value = tt_result.0;
                 tt_move = tt_result.1;
+                tt_eval = data.eval();
             }
         }
 
@@ -108,16 +115,34 @@ impl Search {
             }
         }
 
+        // The static evaluation of the position, if the TT has it. It is
+        // only calculated when it is needed, and then stored in the TT, so
+        // it doesn't have to be calculated again when this position is
+        // reached in the next iteration or in quiescence search. When in
+        // check, there is no static evaluation: the side to move must get
+        // out of check first.
+        let mut static_eval = if is_check { None } else { tt_eval };
+
+        // Reverse futility pruning. Close to the leaves, if the static
+        // evaluation is so far above beta that losing a margin for each
+        // remaining ply still keeps it there, the opponent will not allow
+        // this position. Don't do this in PV-nodes, where the exact value
+        // is needed, or when beta is a checkmate score.
+        if !is_pv && !is_check && depth <= RFP_MAX_DEPTH && beta.abs() < CHECKMATE_THRESHOLD {
+            let eval =
+                *static_eval.get_or_insert_with(|| evaluation::evaluate_position(refs.board));
+
+            if eval - RFP_MARGIN * (depth as i16) >= beta {
+                return beta;
+            }
+        }
+
         /*=== Actual searching starts here ===*/
 
-        // Generate the moves in this position
+        // Set up the move picker. It generates and orders the moves in
+        // stages, so the best moves will be searched first.
         let mut legal_moves_found = 0;
-        let mut move_list = MoveList::new();
-        refs.mg
-            .generate_moves(refs.board, &mut move_list, MoveType::All);
-
-        // Do move scoring, so the best move will be searched first.
-        Search::score_moves(&mut move_list, tt_move, refs);
+        let mut move_picker = MovePicker::new(tt_move, refs);
 
         // After SEND_STATS nodes have been searched, check if the
         // MIN_TIME_STATS has been exceeded; if so, sne dthe current
@@ -135,14 +160,8 @@ impl Search {
         // Holds the best move in the move loop, for storing into the TT.
         let mut best_move: ShortMove = ShortMove::new(0);
 
-        // Iterate over the moves.
-        for i in 0..move_list.len() {
-            // This function finds the best move to test according to the
-            // move scoring, and puts it at the current index of the move
-            // list, so get_move() will get this next.
-            Search::pick_move(&mut move_list, i);
-
-            let current_move = move_list.get_move(i);
+        // Iterate over the moves, in the order the picker hands them out.
+        while let Some(current_move) = move_picker.next(refs) {
             let is_legal = refs.board.make(current_move, refs.mg);
 
             // If not legal, skip the move and the rest of the function.
@@ -150,6 +169,11 @@ impl Search {
                 continue;
             }
 
+            // The next node will probe the TT for the new position.
+            refs.search_info
+                .tt_prefetcher
+                .prefetch(refs.board.game_state.zobrist_key);
+
             // We found a legal move.
             legal_moves_found += 1;
             refs.search_info.ply += 1;
@@ -210,16 +234,19 @@ impl Search {
                         refs.search_info.ply,
                         HashFlag::Beta,
                         beta,
+                        static_eval,
+                        is_pv,
                         best_move,
                     ),
                 );
 
                 // If the move is not a capture but still causes a
-                // beta-cutoff, then store it as a killer move and update
-                // the history heuristics.
+                // beta-cutoff, then store it as a killer move and counter
+                // move, and update the history heuristics.
                 if current_move.captured() == Pieces::NONE {
                     Search::store_killer_move(current_move, refs);
-                    // Search::update_history_heuristic(current_move, depth, refs);
+                    Search::store_counter_move(current_move, refs);
+                    Search::update_history_heuristic(current_move, depth, refs);
                 }
 
                 return beta;
@@ -257,7 +284,15 @@ impl Search {
         // didn't improve alpha, or EXACT if we did raise alpha.
         refs.tt.lock().expect(ErrFatal::LOCK).insert(
             refs.board.game_state.zobrist_key,
-            SearchData::create(depth, refs.search_info.ply, hash_flag, alpha, best_move),
+            SearchData::create(
+                depth,
+                refs.search_info.ply,
+                hash_flag,
+                alpha,
+                static_eval,
+                is_pv,
+                best_move,
+            ),
         );
 
         // We have traversed the entire move list and found the best
@@ -1,11 +1,11 @@
 use crate::{
     board::Board,
-    defs::MAX_PLY,
-    engine::defs::{Information, SearchData, TT},
+    defs::{NrOf, Sides, MAX_PLY},
     movegen::{
         defs::{Move, ShortMove},
         MoveGenerator,
     },
+    transposition::{SearchData, TTPrefetcher, TT},
 };
 use crossbeam_channel::{Receiver, Sender};
 use std::{
@@ -26,15 +26,19 @@ pub const SEND_STATS: usize = 0x7FFFF; // 524.287 nodes
 pub const MIN_TIME_STATS: u128 = 2_000; // Minimum time for sending stats
 pub const MIN_TIME_CURR_MOVE: u128 = 1_000; // Minimum time for sending curr_move
 pub const MAX_KILLER_MOVES: usize = 2;
+pub const MAX_HISTORY_SCORE: u32 = 1 << 24;
+pub const RFP_MAX_DEPTH: i8 = 4; // Deepest node to try reverse futility pruning
+pub const RFP_MARGIN: i16 = 120; // Centipawns per ply of remaining depth
 
 pub type SearchResult = (Move, SearchTerminate);
 type KillerMoves = [[ShortMove; MAX_KILLER_MOVES]; MAX_PLY as usize];
-// type HistoryHeuristic = [[[u32; NrOf::SQUARES]; NrOf::PIECE_TYPES]; Sides::BOTH];
+type HistoryHeuristic = [[[u32; NrOf::SQUARES]; NrOf::PIECE_TYPES]; Sides::BOTH];
+type CounterMoves = [[[ShortMove; NrOf::SQUARES]; NrOf::PIECE_TYPES]; Sides::BOTH];
 
 #[derive(PartialEq)]
 // These commands can be used by the engine thread to control the search.
 pub enum SearchControl {
-    Start(SearchParams),
+    Start(usize, SearchParams), // Search id, reported back when finished.
     Stop,
     Quit,
     Nothing,
@@ -117,20 +121,29 @@ impl SearchParams {
     }
 }
 
+impl Default for SearchParams {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 // The search function will put all findings collected during the running
 // search into this struct.
 #[derive(PartialEq)]
 pub struct SearchInfo {
-    start_time: Option<Instant>,    // Time the search started
-    pub depth: i8,                  // Depth currently being searched
-    pub seldepth: i8,               // Maximum selective depth reached
-    pub nodes: usize,               // Nodes searched
-    pub ply: i8,                    // Number of plys from the root
-    pub killer_moves: KillerMoves,  // Killer moves (array; see "type" above)
-    pub last_stats_sent: u128,      // When last stats update was sent
-    pub last_curr_move_sent: u128,  // When last current move was sent
-    pub allocated_time: u128,       // Allotted msecs to spend on move
-    pub terminate: SearchTerminate, // Terminate flag
+    start_time: Option<Instant>,             // Time the search started
+    pub depth: i8,                           // Depth currently being searched
+    pub seldepth: i8,                        // Maximum selective depth reached
+    pub nodes: usize,                        // Nodes searched
+    pub ply: i8,                             // Number of plys from the root
+    pub killer_moves: KillerMoves,           // Killer moves (array; see "type" above)
+    pub history_heuristic: HistoryHeuristic, // Quiet move cutoffs [side][piece][to]
+    pub counter_moves: CounterMoves,         // Refutations [side][piece][to] of previous move
+    pub last_stats_sent: u128,               // When last stats update was sent
+    pub last_curr_move_sent: u128,           // When last current move was sent
+    pub allocated_time: u128,                // Allotted msecs to spend on move
+    pub terminate: SearchTerminate,          // Terminate flag
+    pub tt_prefetcher: TTPrefetcher,         // Prefetches TT buckets without locking
 }
 
 impl SearchInfo {
@@ -142,10 +155,13 @@ impl SearchInfo {
             nodes: 0,
             ply: 0,
             killer_moves: [[ShortMove::new(0); MAX_KILLER_MOVES]; MAX_PLY as usize],
+            history_heuristic: [[[0; NrOf::SQUARES]; NrOf::PIECE_TYPES]; Sides::BOTH],
+            counter_moves: [[[ShortMove::new(0); NrOf::SQUARES]; NrOf::PIECE_TYPES]; Sides::BOTH],
             last_stats_sent: 0,
             last_curr_move_sent: 0,
             allocated_time: 0,
             terminate: SearchTerminate::Nothing,
+            tt_prefetcher: TTPrefetcher::none(),
         }
     }
 
@@ -166,6 +182,12 @@ impl SearchInfo {
     }
 }
 
+impl Default for SearchInfo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 // After each completed depth, iterative deepening summarizes the running
 // search results within this struct before sending it to the engine
 // thread. The engine thread will send it to Comm, which will transform the
@@ -192,6 +214,28 @@ impl SearchSummary {
         }
         pv.trim().to_string()
     }
+
+    // If the score is a mate score, return the number of moves to mate.
+    // The number is negative if the engine is being mated itself.
+    pub fn moves_to_mate(&self) -> Option<i16> {
+        if (self.cp.abs() >= CHECKMATE_THRESHOLD) && (self.cp.abs() < CHECKMATE) {
+            // Number of plies to mate.
+            let ply = CHECKMATE - self.cp.abs();
+
+            // Check if the number of ply's is odd
+            let is_odd = ply % 2 == 1;
+
+            // Calculate number of moves to mate
+            let moves = if is_odd { (ply + 1) / 2 } else { ply / 2 };
+
+            // If the engine is being mated itself, flip the score.
+            let flip = if self.cp < 0 { -1 } else { 1 };
+
+            Some(moves * flip)
+        } else {
+            None
+        }
+    }
 }
 
 #[derive(PartialEq, Copy, Clone)]
@@ -248,13 +292,13 @@ pub struct SearchRefs<'a> {
     pub search_params: &'a mut SearchParams,
     pub search_info: &'a mut SearchInfo,
     pub control_rx: &'a Receiver<SearchControl>,
-    pub report_tx: &'a Sender<Information>,
+    pub report_tx: &'a Sender<SearchReport>,
 }
 
 // This struct holds all the reports a search can send to the engine.
 #[derive(PartialEq)]
 pub enum SearchReport {
-    Finished(Move),                       // Search done. Contains the best move.
+    Finished(usize, Move),                // Search done. Contains id and best move.
     SearchSummary(SearchSummary),         // Periodic intermediate results.
     SearchCurrentMove(SearchCurrentMove), // Move currently searched.
     SearchStats(SearchStats),             // General search statistics
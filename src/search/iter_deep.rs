@@ -23,7 +23,7 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use super::{
     defs::{SearchMode, SearchRefs, SearchResult, INF},
-    ErrFatal, Information, Search, SearchReport, SearchSummary,
+    ErrFatal, Search, SearchReport, SearchSummary,
 };
 use crate::{defs::MAX_PLY, movegen::defs::Move};
 
@@ -65,6 +65,15 @@ impl Search {
         let alpha: i16 = -INF;
         let beta: i16 = INF;
 
+        // Entries in the TT from earlier searches are now older. Take the
+        // prefetcher once, so the search doesn't lock the TT to prefetch.
+        let mut tt = refs.tt.lock().expect(ErrFatal::LOCK);
+        tt.new_search();
+        if refs.tt_enabled {
+            refs.search_info.tt_prefetcher = tt.prefetcher();
+        }
+        std::mem::drop(tt);
+
         // Start the search
         refs.search_info.timer_start();
         while (depth <= MAX_PLY) && (depth <= refs.search_params.depth) && !stop {
@@ -97,10 +106,9 @@ impl Search {
                     pv: root_pv.clone(),
                 };
 
-                // Create information for the engine
+                // Send the summary to the engine.
                 let report = SearchReport::SearchSummary(summary);
-                let information = Information::Search(report);
-                refs.report_tx.send(information).expect(ErrFatal::CHANNEL);
+                refs.report_tx.send(report).expect(ErrFatal::CHANNEL);
 
                 // Search one ply deepr.
                 depth += 1;
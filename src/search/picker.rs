@@ -0,0 +1,288 @@
+/* =======================================================================
+Rustic is a chess playing engine.
+Copyright (C) 2019-2024, Marcel Vanthoor
+https://rustic-chess.org/
+
+Rustic is written in the Rust programming language. It is an original
+work, not derived from any engine that came before it. However, it does
+use a lot of concepts which are well-known and are in use by most if not
+all classical alpha/beta-based chess engines.
+
+Rustic is free software: you can redistribute it and/or modify it under
+the terms of the GNU General Public License version 3 as published by
+the Free Software Foundation.
+
+Rustic is distributed in the hope that it will be useful, but WITHOUT
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+for more details.
+
+You should have received a copy of the GNU General Public License along
+with this program.  If not, see <http://www.gnu.org/licenses/>.
+======================================================================= */
+
+// picker.rs contains the staged move picker. Instead of generating all the
+// moves in a position and sorting them up front, the picker hands out moves
+// one by one, in stages. Moves are only generated when their stage is
+// reached, so if the first move (often the TT move) causes a beta cutoff,
+// no moves have to be generated at all.
+//
+// The stages are:
+//
+// 1. The TT move, if it is pseudo-legal in this position.
+// 2. Good captures, ordered by MVV-LVA.
+// 3. Killer moves for the current ply.
+// 4. The counter move for the opponent's previous move.
+// 5. Quiet moves, ordered by history heuristic.
+// 6. Bad captures: captures losing material according to SEE.
+//
+// In quiescence search, only the capture stages are used.
+
+use super::{
+    defs::{SearchRefs, MAX_KILLER_MOVES},
+    sorting::MVV_LVA,
+    Search,
+};
+use crate::movegen::defs::{Move, MoveList, MoveType, ShortMove};
+
+#[derive(PartialEq, Copy, Clone)]
+enum Stage {
+    TTMove,
+    GenerateCaptures,
+    GoodCaptures,
+    Killers,
+    CounterMove,
+    GenerateQuiets,
+    Quiets,
+    BadCaptures,
+    Done,
+}
+
+pub struct MovePicker {
+    stage: Stage,
+    captures_only: bool,
+    tt_move: Option<Move>,
+    killers: [ShortMove; MAX_KILLER_MOVES],
+    counter_move: ShortMove,
+    move_list: MoveList,
+    index: u8,     // Next move to pick in the current stage.
+    end: u8,       // End of the current stage in the move list.
+    bad_count: u8, // Bad captures are collected at the start of the list.
+    killer_index: usize,
+}
+
+impl MovePicker {
+    // Create a picker for the main search. The TT move is validated here, so
+    // it can be returned without generating any moves.
+    pub fn new(tt_move: ShortMove, refs: &SearchRefs) -> Self {
+        let m = Move::new(tt_move.get_move() as usize);
+        let tt_move = if m.get_move() != 0 && refs.mg.is_pseudo_legal(refs.board, m) {
+            Some(m)
+        } else {
+            None
+        };
+
+        let ply = refs.search_info.ply as usize;
+        let counter_move = match Search::previous_move(refs.board) {
+            Some(p) => refs.search_info.counter_moves[refs.board.opponent()][p.piece()][p.to()],
+            None => ShortMove::new(0),
+        };
+
+        Self {
+            stage: Stage::TTMove,
+            captures_only: false,
+            tt_move,
+            killers: refs.search_info.killer_moves[ply],
+            counter_move,
+            move_list: MoveList::new(),
+            index: 0,
+            end: 0,
+            bad_count: 0,
+            killer_index: 0,
+        }
+    }
+
+    // Create a picker for quiescence search, which only hands out captures.
+    pub fn new_qsearch() -> Self {
+        Self {
+            stage: Stage::GenerateCaptures,
+            captures_only: true,
+            tt_move: None,
+            killers: [ShortMove::new(0); MAX_KILLER_MOVES],
+            counter_move: ShortMove::new(0),
+            move_list: MoveList::new(),
+            index: 0,
+            end: 0,
+            bad_count: 0,
+            killer_index: 0,
+        }
+    }
+
+    // Returns the next move to search, or None if all moves have been
+    // handed out. The moves are pseudo-legal: the caller still has to
+    // check if they leave the king in check.
+    pub fn next(&mut self, refs: &SearchRefs) -> Option<Move> {
+        loop {
+            match self.stage {
+                Stage::TTMove => {
+                    self.stage = Stage::GenerateCaptures;
+                    if self.tt_move.is_some() {
+                        return self.tt_move;
+                    }
+                }
+
+                Stage::GenerateCaptures => {
+                    refs.mg
+                        .generate_moves(refs.board, &mut self.move_list, MoveType::Capture);
+                    for i in 0..self.move_list.len() {
+                        let m = self.move_list.get_mut_move(i);
+                        m.set_sort_score(MVV_LVA[m.captured()][m.piece()] as u32);
+                    }
+                    self.end = self.move_list.len();
+                    self.stage = Stage::GoodCaptures;
+                }
+
+                Stage::GoodCaptures => {
+                    if let Some(m) = self.pick() {
+                        if self.is_tt_move(m) {
+                            continue;
+                        }
+
+                        // Captures that lose material are moved to the front
+                        // of the list, after the previous bad captures. That
+                        // part of the list has already been handed out, so
+                        // nothing is overwritten.
+                        if !Search::is_good_capture(refs.board, refs.mg, m) {
+                            self.move_list
+                                .swap(self.bad_count as usize, self.index as usize - 1);
+                            self.bad_count += 1;
+                            continue;
+                        }
+
+                        return Some(m);
+                    }
+
+                    self.stage = if self.captures_only {
+                        Stage::BadCaptures
+                    } else {
+                        Stage::Killers
+                    };
+                    self.index = 0;
+                }
+
+                Stage::Killers => {
+                    while self.killer_index < MAX_KILLER_MOVES {
+                        let killer = self.killers[self.killer_index];
+                        self.killer_index += 1;
+                        if let Some(m) = self.validate(killer, refs) {
+                            return Some(m);
+                        }
+                    }
+                    self.stage = Stage::CounterMove;
+                }
+
+                Stage::CounterMove => {
+                    self.stage = Stage::GenerateQuiets;
+                    if !self.is_killer(self.counter_move) {
+                        if let Some(m) = self.validate(self.counter_move, refs) {
+                            return Some(m);
+                        }
+                    }
+                }
+
+                // Quiet moves are added to the list after the captures.
+                Stage::GenerateQuiets => {
+                    let us = refs.board.us();
+                    let start = self.move_list.len();
+                    refs.mg
+                        .generate_moves(refs.board, &mut self.move_list, MoveType::Quiet);
+                    for i in start..self.move_list.len() {
+                        let m = self.move_list.get_mut_move(i);
+                        let value = refs.search_info.history_heuristic[us][m.piece()][m.to()];
+                        m.set_sort_score(value);
+                    }
+                    self.index = start;
+                    self.end = self.move_list.len();
+                    self.stage = Stage::Quiets;
+                }
+
+                Stage::Quiets => {
+                    if let Some(m) = self.pick() {
+                        let short = m.to_short_move();
+                        if self.is_tt_move(m)
+                            || self.is_killer(short)
+                            || short.get_move() == self.counter_move.get_move()
+                        {
+                            continue;
+                        }
+                        return Some(m);
+                    }
+                    self.index = 0;
+                    self.stage = Stage::BadCaptures;
+                }
+
+                Stage::BadCaptures => {
+                    if self.index < self.bad_count {
+                        let m = self.move_list.get_move(self.index);
+                        self.index += 1;
+                        return Some(m);
+                    }
+                    self.stage = Stage::Done;
+                }
+
+                Stage::Done => return None,
+            }
+        }
+    }
+
+    // Put the move with the highest sort score between the current index
+    // and the end of the stage at the current index, and return it.
+    fn pick(&mut self) -> Option<Move> {
+        if self.index >= self.end {
+            return None;
+        }
+
+        for i in (self.index + 1)..self.end {
+            let best = self.move_list.get_move(self.index).get_sort_score();
+            if self.move_list.get_move(i).get_sort_score() > best {
+                self.move_list.swap(self.index as usize, i as usize);
+            }
+        }
+
+        let m = self.move_list.get_move(self.index);
+        self.index += 1;
+        Some(m)
+    }
+
+    // Killers and counter moves come from other positions. They are only
+    // returned if they are quiet, pseudo-legal moves in this position, and
+    // not equal to the TT move. A pseudo-legality check makes sure the
+    // to-square is still empty. En-passant is excluded, because it is
+    // handed out by the capture stage.
+    fn validate(&self, short: ShortMove, refs: &SearchRefs) -> Option<Move> {
+        let m = Move::new(short.get_move() as usize);
+        let is_valid = m.get_move() != 0
+            && !m.en_passant()
+            && !self.is_tt_move(m)
+            && refs.mg.is_pseudo_legal(refs.board, m);
+
+        if is_valid {
+            Some(m)
+        } else {
+            None
+        }
+    }
+
+    fn is_tt_move(&self, m: Move) -> bool {
+        match self.tt_move {
+            Some(tt) => tt.get_move() == m.get_move(),
+            None => false,
+        }
+    }
+
+    fn is_killer(&self, short: ShortMove) -> bool {
+        self.killers
+            .iter()
+            .any(|k| k.get_move() != 0 && k.get_move() == short.get_move())
+    }
+}
@@ -23,12 +23,13 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use super::{
     defs::{SearchTerminate, CHECK_TERMINATION, SEND_STATS},
+    picker::MovePicker,
     Search, SearchRefs,
 };
 use crate::{
-    defs::MAX_PLY,
+    defs::{ErrFatal, MAX_PLY},
     evaluation,
-    movegen::defs::{Move, MoveList, MoveType, ShortMove},
+    movegen::defs::Move,
 };
 
 impl Search {
@@ -57,8 +58,20 @@ impl Search {
         // Do a stand-pat here: Check how we're doing, even before we make
         // a move. If the evaluation score is larger than beta, then we're
         // already so bad we don't need to search any further. Just return
-        // the beta score.
-        let eval_score = evaluation::evaluate_position(refs.board);
+        // the beta score. If the position is in the TT, its static
+        // evaluation was already calculated, so it's taken from there. The
+        // PSQT evaluation is updated incrementally and costs less than a
+        // TT probe, so this is only done when using a network.
+        let tt_eval = if refs.tt_enabled && refs.board.nnue().is_some() {
+            refs.tt
+                .lock()
+                .expect(ErrFatal::LOCK)
+                .probe(refs.board.game_state.zobrist_key)
+                .and_then(|data| data.eval())
+        } else {
+            None
+        };
+        let eval_score = tt_eval.unwrap_or_else(|| evaluation::evaluate_position(refs.board));
         if eval_score >= beta {
             return beta;
         }
@@ -76,13 +89,9 @@ impl Search {
         // the recursion, or until there are no more captures available.
         // Then the function will return after looping the move list.
 
-        // Generate only capture moves.
-        let mut move_list = MoveList::new();
-        let mtc = MoveType::Capture;
-        refs.mg.generate_moves(refs.board, &mut move_list, mtc);
-
-        // Do move scoring, so the best move will be searched first.
-        Search::score_moves(&mut move_list, ShortMove::new(0), refs);
+        // Set up a move picker that only hands out captures. The ones
+        // that win material will be searched first.
+        let mut move_picker = MovePicker::new_qsearch();
 
         // Update search stats in the GUI. Check every SEND_STATS nodes if
         // the minium MIN_TIME_STATS has elapsed before sending.
@@ -91,11 +100,7 @@ impl Search {
         }
 
         // Iterate over the capture moves.
-        for i in 0..move_list.len() {
-            // Pick the next moves with the higest score.
-            Search::pick_move(&mut move_list, i);
-
-            let current_move = move_list.get_move(i);
+        while let Some(current_move) = move_picker.next(refs) {
             let is_legal = refs.board.make(current_move, refs.mg);
 
             // If not legal, skip the move and the rest of the function.
@@ -23,15 +23,15 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 // Move sorting routines.
 
-use super::{
-    defs::{SearchRefs, MAX_KILLER_MOVES},
-    Search,
+use super::Search;
+use crate::{
+    board::{
+        defs::{Pieces, BB_SQUARES},
+        Board,
+    },
+    defs::{Bitboard, NrOf, Piece, Side, Sides},
+    movegen::{defs::Move, MoveGenerator},
 };
-use crate::{board::defs::Pieces, defs::NrOf, movegen::defs::MoveList, movegen::defs::ShortMove};
-
-const MVV_LVA_OFFSET: u32 = u32::MAX - 256;
-const TTMOVE_SORT_VALUE: u32 = 60;
-const KILLER_VALUE: u32 = 10;
 
 // MVV_VLA[victim][attacker]
 pub const MVV_LVA: [[u16; NrOf::PIECE_TYPES + 1]; NrOf::PIECE_TYPES + 1] = [
@@ -44,52 +44,202 @@ pub const MVV_LVA: [[u16; NrOf::PIECE_TYPES + 1]; NrOf::PIECE_TYPES + 1] = [
     [0, 0, 0, 0, 0, 0, 0],       // victim None, attacker K, Q, R, B, N, P, None
 ];
 
+// Piece values used by the static exchange evaluation: K, Q, R, B, N, P, None.
+// The king is worth so much that it never wants to be part of a losing
+// exchange, but it can still be the last piece to capture.
+pub const SEE_VALUES: [i16; NrOf::PIECE_TYPES + 1] = [10_000, 900, 500, 300, 300, 100, 0];
+
+// Pieces in the order in which they join an exchange: least valuable first.
+const SEE_ORDER: [Piece; NrOf::PIECE_TYPES] = [
+    Pieces::PAWN,
+    Pieces::KNIGHT,
+    Pieces::BISHOP,
+    Pieces::ROOK,
+    Pieces::QUEEN,
+    Pieces::KING,
+];
+
 impl Search {
-    pub fn score_moves(ml: &mut MoveList, tt_move: ShortMove, refs: &SearchRefs) {
-        for i in 0..ml.len() {
-            let m = ml.get_mut_move(i);
-            let mut value: u32 = 0;
-
-            // Sort order priority is: TT Move first, then captures, then
-            // quiet moves that are in the list of killer moves.
-            if m.get_move() == tt_move.get_move() {
-                value = MVV_LVA_OFFSET + TTMOVE_SORT_VALUE;
-            } else if m.captured() != Pieces::NONE {
-                // Order captures higher than MVV_LVA_OFFSET
-                value = MVV_LVA_OFFSET + MVV_LVA[m.captured()][m.piece()] as u32;
-            } else {
-                let ply = refs.search_info.ply as usize;
-                let mut n = 0;
-                while n < MAX_KILLER_MOVES && value == 0 {
-                    let killer = refs.search_info.killer_moves[ply][n];
-                    if m.get_move() == killer.get_move() {
-                        // Order killers below MVV_LVA_OFFSET
-                        value = MVV_LVA_OFFSET - ((i as u32 + 1) * KILLER_VALUE);
-                    }
-                    n += 1;
-                }
+    // A capture is "good" if it doesn't lose material, according to the
+    // static exchange evaluation. If the victim is worth at least as much as
+    // the capturing piece, the capture can't lose material, and the
+    // (relatively expensive) exchange evaluation can be skipped.
+    pub fn is_good_capture(board: &Board, mg: &MoveGenerator, m: Move) -> bool {
+        SEE_VALUES[m.captured()] >= SEE_VALUES[m.piece()] || Search::see(board, mg, m) >= 0
+    }
+
+    // Static Exchange Evaluation: determine the material outcome of the
+    // sequence of captures on the to-square of the given move, assuming
+    // both sides always recapture with their least valuable piece, and each
+    // side can stop capturing if continuing would lose material.
+    pub fn see(board: &Board, mg: &MoveGenerator, m: Move) -> i16 {
+        let to = m.to();
+        let mut gain = [0i16; 32];
+        let mut d = 0;
+        let mut side = board.us();
+        let mut piece = m.piece(); // Piece standing on the to-square
+        let mut mover = m.piece(); // Piece type that moved there
+        let mut bb_from = BB_SQUARES[m.from()];
+        let mut bb_occupancy = board.occupancy();
+
+        // The first capture. An en-passant capture takes a pawn that is not
+        // on the to-square, so it's removed from the occupancy directly.
+        gain[0] = if m.en_passant() {
+            bb_occupancy &= !BB_SQUARES[to ^ 8];
+            SEE_VALUES[Pieces::PAWN]
+        } else {
+            SEE_VALUES[m.captured()]
+        };
+
+        // A promoting pawn stands on the to-square as the promoted piece.
+        if m.promoted() != Pieces::NONE {
+            gain[0] += SEE_VALUES[m.promoted()] - SEE_VALUES[Pieces::PAWN];
+            piece = m.promoted();
+        }
+
+        // Sliders of both sides, for finding pieces that are uncovered
+        // during the exchange.
+        let bb_queens = board.bb_pieces[Sides::WHITE][Pieces::QUEEN]
+            | board.bb_pieces[Sides::BLACK][Pieces::QUEEN];
+        let bb_diagonal = bb_queens
+            | board.bb_pieces[Sides::WHITE][Pieces::BISHOP]
+            | board.bb_pieces[Sides::BLACK][Pieces::BISHOP];
+        let bb_straight = bb_queens
+            | board.bb_pieces[Sides::WHITE][Pieces::ROOK]
+            | board.bb_pieces[Sides::BLACK][Pieces::ROOK];
+
+        let mut bb_attackers = mg.attackers_to(board, Sides::WHITE, to, bb_occupancy)
+            | mg.attackers_to(board, Sides::BLACK, to, bb_occupancy);
+
+        loop {
+            d += 1;
+            side ^= 1;
+
+            // Speculative gain if the piece on the to-square is captured.
+            gain[d] = SEE_VALUES[piece] - gain[d - 1];
+
+            // Neither side can improve by continuing the exchange.
+            if (-gain[d - 1]).max(gain[d]) < 0 || d == gain.len() - 1 {
+                break;
+            }
+
+            // Remove the piece that just captured. This can uncover a
+            // slider behind it, which then joins the exchange. A knight
+            // can never stand in front of a slider.
+            bb_occupancy &= !bb_from;
+            if matches!(
+                mover,
+                Pieces::PAWN | Pieces::BISHOP | Pieces::QUEEN | Pieces::KING
+            ) {
+                let bb_bishop = mg.get_slider_attacks(Pieces::BISHOP, to, bb_occupancy);
+                bb_attackers |= bb_bishop & bb_diagonal;
+            }
+            if matches!(mover, Pieces::ROOK | Pieces::QUEEN | Pieces::KING) {
+                let bb_rook = mg.get_slider_attacks(Pieces::ROOK, to, bb_occupancy);
+                bb_attackers |= bb_rook & bb_straight;
             }
+            bb_attackers &= bb_occupancy;
 
-            /*
-                // If still not sorted, try to sort by history heuristic.
-                if value == 0 {
-                    let piece = m.piece();
-                    let to = m.to();
-                    value = refs.search_info.history_heuristic[refs.board.us()][piece][to];
+            // Find the next piece to capture with.
+            match Search::least_valuable(board, side, bb_attackers) {
+                Some((p, bb)) => {
+                    piece = p;
+                    mover = p;
+                    bb_from = bb;
                 }
-            */
+                None => break,
+            }
+        }
 
-            m.set_sort_score(value);
+        // Walk back through the exchange. At each step, the side to move
+        // either continues the exchange, or stops if that is better.
+        while d > 1 {
+            d -= 1;
+            gain[d - 1] = -((-gain[d - 1]).max(gain[d]));
         }
+
+        gain[0]
     }
 
-    // This function puts the move with the highest sort score at the
-    // "start_index" position, where alpha-beta will pick the next move.
-    pub fn pick_move(ml: &mut MoveList, start_index: u8) {
-        for i in (start_index + 1)..ml.len() {
-            if ml.get_move(i).get_sort_score() > ml.get_move(start_index).get_sort_score() {
-                ml.swap(start_index as usize, i as usize);
+    // Find the least valuable piece of the given side within the attackers.
+    fn least_valuable(
+        board: &Board,
+        side: Side,
+        bb_attackers: Bitboard,
+    ) -> Option<(Piece, Bitboard)> {
+        for piece in SEE_ORDER {
+            let bb = bb_attackers & board.get_pieces(piece, side);
+            if bb > 0 {
+                return Some((piece, bb & bb.wrapping_neg()));
             }
         }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        misc::parse,
+        movegen::defs::{MoveList, MoveType},
+    };
+
+    // Static exchange evaluation of the move, given in coordinate notation.
+    fn see(fen: &str, coordinates: &str) -> i16 {
+        let board = Board::from_fen(fen).expect("valid FEN");
+        let mg = MoveGenerator::new();
+        let (from, to, promoted) = parse::algebraic_move_to_number(coordinates).expect("move");
+        let mut ml = MoveList::new();
+        mg.generate_legal_moves(&board, &mut ml, MoveType::All);
+        let m = (0..ml.len())
+            .map(|i| ml.get_move(i))
+            .find(|m| m.from() == from && m.to() == to && m.promoted() == promoted)
+            .expect("legal move");
+
+        Search::see(&board, &mg, m)
+    }
+
+    #[test]
+    fn exchanges_are_evaluated_exactly() {
+        let cases = [
+            // Undefended pawn.
+            ("4k3/8/8/3p4/8/8/8/3QK3 w - - 0 1", "d1d5", 100),
+            // Pawn defended by a pawn, taken by the queen.
+            ("4k3/8/2p5/3p4/8/8/8/3QK3 w - - 0 1", "d1d5", -800),
+            // Pawn defended by a rook, taken by a single rook.
+            ("3r2k1/8/8/3p4/8/8/3R4/6K1 w - - 0 1", "d2d5", -400),
+            // The same, with a second rook behind the first one.
+            ("3r2k1/8/8/3p4/8/8/3R4/3R2K1 w - - 0 1", "d2d5", 100),
+            // Long exchange with x-rays on both sides.
+            (
+                "1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1",
+                "d3e5",
+                -200,
+            ),
+            // Undefended en-passant capture.
+            ("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6", 100),
+            // Promotion on a square the king defends: the pawn is lost.
+            ("8/Pk6/8/8/8/8/8/4K3 w - - 0 1", "a7a8q", -100),
+        ];
+
+        for (fen, m, value) in cases {
+            assert_eq!(see(fen, m), value, "{fen} {m}");
+        }
+    }
+
+    #[test]
+    fn winning_captures_are_not_negative() {
+        let cases = [
+            // Knight taken by a pawn, and recaptured.
+            ("4k3/8/2p5/3n4/4P3/8/8/4K3 w - - 0 1", "e4d5"),
+            // Rook taken while promoting, and recaptured by the king.
+            ("1rk5/P7/8/8/8/8/8/4K3 w - - 0 1", "a7b8q"),
+        ];
+
+        for (fen, m) in cases {
+            assert!(see(fen, m) > 0, "{fen} {m}");
+        }
     }
 }
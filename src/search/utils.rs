@@ -24,14 +24,13 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 use super::{
     defs::{
         SearchControl, SearchCurrentMove, SearchMode, SearchRefs, SearchReport, SearchStats,
-        SearchTerminate, MAX_KILLER_MOVES, MIN_TIME_CURR_MOVE, MIN_TIME_STATS,
+        SearchTerminate, MAX_HISTORY_SCORE, MAX_KILLER_MOVES, MIN_TIME_CURR_MOVE, MIN_TIME_STATS,
     },
     Search,
 };
 use crate::{
     board::{defs::Pieces, Board},
-    defs::{Sides, MAX_MOVE_RULE},
-    engine::defs::{ErrFatal, Information},
+    defs::{ErrFatal, Sides, MAX_MOVE_RULE},
     movegen::defs::Move,
 };
 
@@ -57,9 +56,8 @@ impl Search {
             let nps = Search::nodes_per_second(refs.search_info.nodes, msecs);
             let stats = SearchStats::new(msecs, refs.search_info.nodes, nps, hash_full);
             let stats_report = SearchReport::SearchStats(stats);
-            let information = Information::Search(stats_report);
 
-            refs.report_tx.send(information).expect(ErrFatal::CHANNEL);
+            refs.report_tx.send(stats_report).expect(ErrFatal::CHANNEL);
             refs.search_info.last_stats_sent = elapsed;
         }
     }
@@ -72,9 +70,8 @@ impl Search {
         if elapsed >= lcm + MIN_TIME_CURR_MOVE {
             let scm = SearchCurrentMove::new(current_move, count);
             let scm_report = SearchReport::SearchCurrentMove(scm);
-            let information = Information::Search(scm_report);
 
-            refs.report_tx.send(information).expect(ErrFatal::CHANNEL);
+            refs.report_tx.send(scm_report).expect(ErrFatal::CHANNEL);
             refs.search_info.last_curr_move_sent = elapsed;
         }
     }
@@ -87,7 +84,7 @@ impl Search {
         match cmd {
             SearchControl::Stop => refs.search_info.terminate = SearchTerminate::Stop,
             SearchControl::Quit => refs.search_info.terminate = SearchTerminate::Quit,
-            SearchControl::Start(_) | SearchControl::Nothing => (),
+            SearchControl::Start(..) | SearchControl::Nothing => (),
         };
 
         // Terminate search if certain conditions are met.
@@ -214,4 +211,43 @@ impl Search {
             refs.search_info.killer_moves[ply][0] = current_move.to_short_move();
         }
     }
+
+    // Increase the history score of a quiet move that caused a beta
+    // cutoff. Moves causing cutoffs deep in the tree are more valuable, so
+    // the bonus grows with the depth. If an entry becomes too large, all the
+    // entries for this side are halved; this keeps them from overflowing,
+    // and lets the older results fade away.
+    pub fn update_history_heuristic(current_move: Move, depth: i8, refs: &mut SearchRefs) {
+        let us = refs.board.us();
+        let history = &mut refs.search_info.history_heuristic[us];
+        let entry = &mut history[current_move.piece()][current_move.to()];
+
+        *entry += (depth as u32) * (depth as u32);
+
+        if *entry > MAX_HISTORY_SCORE {
+            for value in history.iter_mut().flatten() {
+                *value /= 2;
+            }
+        }
+    }
+
+    // Store a quiet move that caused a beta cutoff as the counter move for
+    // the move the opponent played just before it.
+    pub fn store_counter_move(current_move: Move, refs: &mut SearchRefs) {
+        if let Some(previous) = Search::previous_move(refs.board) {
+            let opponent = refs.board.opponent();
+            refs.search_info.counter_moves[opponent][previous.piece()][previous.to()] =
+                current_move.to_short_move();
+        }
+    }
+
+    // Returns the move that led to the current position, if there is one.
+    pub fn previous_move(board: &Board) -> Option<Move> {
+        let len = board.history.len();
+        if len > 0 {
+            Some(board.history.get_ref(len - 1).next_move)
+        } else {
+            None
+        }
+    }
 }
@@ -0,0 +1,153 @@
+/* =======================================================================
+Rustic is a chess playing engine.
+Copyright (C) 2019-2024, Marcel Vanthoor
+https://rustic-chess.org/
+
+Rustic is written in the Rust programming language. It is an original
+work, not derived from any engine that came before it. However, it does
+use a lot of concepts which are well-known and are in use by most if not
+all classical alpha/beta-based chess engines.
+
+Rustic is free software: you can redistribute it and/or modify it under
+the terms of the GNU General Public License version 3 as published by
+the Free Software Foundation.
+
+Rustic is distributed in the hope that it will be useful, but WITHOUT
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+for more details.
+
+You should have received a copy of the GNU General Public License along
+with this program.  If not, see <http://www.gnu.org/licenses/>.
+======================================================================= */
+
+// bench.rs searches a fixed list of positions to a fixed depth. Each
+// search starts with an empty TT and fresh move ordering tables, so the
+// total number of nodes only changes if the search or evaluation does.
+// This makes the node count a signature: a change that is supposed to be
+// functionally neutral should not change it. The speed in nodes per
+// second can be used to compare the engine's performance across machines.
+
+use crate::{
+    board::Board,
+    engine::defs::{ErrFatal, SearchData, TT},
+    movegen::MoveGenerator,
+    search::{
+        defs::{SearchControl, SearchInfo, SearchMode, SearchParams, SearchRefs, SearchReport},
+        Search,
+    },
+};
+use std::{
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+pub const BENCH_DEPTH_DEFAULT: i8 = 7;
+pub const BENCH_HASH_DEFAULT: usize = 16;
+
+// A mix of opening, middle game and endgame positions, including some
+// with checks, promotions, mate and stalemate.
+#[rustfmt::skip]
+const BENCH_POSITIONS: [&str; 40] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 10",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 11",
+    "4rrk1/pp1n3p/3q2pQ/2p1pb2/2PP4/2P3N1/P2B2PP/4RRK1 b - - 7 19",
+    "rq3rk1/ppp2ppp/1bnpb3/3N2B1/3NP3/7P/PPPQ1PP1/2KR3R w - - 7 14",
+    "r1bq1r1k/1pp1n1pp/1p1p4/4p2Q/4Pp2/1BNP4/PPP2PPP/3R1RK1 w - - 2 14",
+    "r3r1k1/2p2ppp/p1p1bn2/8/1q2P3/2NPQN2/PPP3PP/R4RK1 b - - 2 15",
+    "r1bbk1nr/pp3p1p/2n5/1N4p1/2Np1B2/8/PPP2PPP/2KR1B1R w kq - 0 13",
+    "r1bq1rk1/ppp1nppp/4n3/3p3Q/3P4/1BP1B3/PP1N2PP/R4RK1 w - - 1 16",
+    "4r1k1/r1q2ppp/ppp2n2/4P3/5Rb1/1N1BQ3/PPP3PP/R5K1 w - - 1 17",
+    "2rqkb1r/ppp2p2/2npb1p1/1N1Nn2p/2P1PP2/8/PP2B1PP/R1BQK2R b KQ - 0 11",
+    "r1bq1r1k/b1p1npp1/p2p3p/1p6/3PP3/1B2NN2/PP3PPP/R2Q1RK1 w - - 1 16",
+    "3r1rk1/p5pp/bpp1pp2/8/q1PP1P2/b3P3/P2NQRPP/1R2B1K1 b - - 6 22",
+    "r1q2rk1/2p1bppp/2Pp4/p6b/Q1PNp3/4B3/PP1R1PPP/2K4R w - - 2 18",
+    "4k2r/1pb2ppp/1p2p3/1R1p4/3P4/2r1PN2/P4PPP/1R4K1 b - - 3 22",
+    "3q2k1/pb3p1p/4pbp1/2r5/PpN2N2/1P2P2P/5PP1/Q2R2K1 b - - 4 26",
+    "6k1/6p1/6Pp/ppp5/3pn2P/1P3K2/1PP2P2/3N4 b - - 0 1",
+    "3b4/5kp1/1p1p1p1p/pP1PpP1P/P1P1P3/3KN3/8/8 w - - 0 1",
+    "2K5/p7/7P/5pR1/8/5k2/r7/8 w - - 0 1",
+    "8/6pk/1p6/8/PP3p1p/5P2/4KP1q/3Q4 w - - 0 1",
+    "7k/3p2pp/4q3/8/4Q3/5Kp1/P6b/8 w - - 0 1",
+    "8/2p5/8/2kPKp1p/2p4P/2P5/3P4/8 w - - 0 1",
+    "8/1p3pp1/7p/5P1P/2k3P1/8/2K2P2/8 w - - 0 1",
+    "8/pp2r1k1/2p1p3/3pP2p/1P1P1P1P/P5KR/8/8 w - - 0 1",
+    "8/3p4/p1bk3p/Pp6/1Kp1PpPp/2P2P1P/2P5/5B2 b - - 0 1",
+    "5k2/7R/4P2p/5K2/p1r2P1p/8/8/8 b - - 0 1",
+    "6k1/6p1/P6p/r1N5/5p2/7P/1b3PP1/4R1K1 w - - 0 1",
+    "1r3k2/4q3/2Pp3b/3Bp3/2Q2p2/1p1P2P1/1P2KP2/3N4 w - - 0 1",
+    "6k1/4pp1p/3p2p1/P1pPb3/R7/1r2P1PP/3B1P2/6K1 w - - 0 1",
+    "8/3p3B/5p2/5P2/p7/PP5b/k7/6K1 w - - 0 1",
+    "8/8/8/8/5kp1/P7/8/1K1N4 w - - 0 1",
+    "8/8/8/5N2/8/p7/8/2NK3k w - - 0 1",
+    "8/3k4/8/8/8/4B3/4KB2/2B5 w - - 0 1",
+    "8/8/1P6/5pr1/8/4R3/7k/2K5 w - - 0 1",
+    "8/2p4P/8/kr6/6R1/8/8/1K6 w - - 0 1",
+    "8/8/3P3k/8/1p6/8/1P6/1K3n2 b - - 0 1",
+    "8/R7/2q5/8/6k1/8/1P5p/K6R w - - 0 124",
+    "6k1/3b3r/1p1p4/p1n2p2/1PPNpP1q/P3Q1p1/1R1RB1P1/5K2 b - - 0 1",
+    "8/8/8/8/8/6k1/6p1/6K1 w - - 0 1",
+    "7k/7P/6K1/8/3B4/8/8/8 b - - 0 1",
+];
+
+// Search all the bench positions to the given depth, using a TT of the
+// given size in MB. This doesn't use the engine's board or TT, so it can
+// be run at any time without disturbing the current game.
+pub fn run(depth: i8, hash: usize, mg: Arc<MoveGenerator>) {
+    let tt = Arc::new(Mutex::new(TT::<SearchData>::new(hash)));
+    let mut board = Board::new();
+    let mut total_nodes: usize = 0;
+    let mut total_time: u128 = 0;
+
+    // The search needs channels, even though nothing is sent to it. The
+    // reports it sends back are not used, but the receiver must exist.
+    let (_control_tx, control_rx) = crossbeam_channel::unbounded::<SearchControl>();
+    let (report_tx, report_rx) = crossbeam_channel::unbounded::<SearchReport>();
+
+    println!("Benchmarking search to depth {depth} with {hash} MB hash:");
+
+    for (i, fen) in BENCH_POSITIONS.iter().enumerate() {
+        board.fen_read(Some(fen)).expect(ErrFatal::NEW_GAME);
+        tt.lock().expect(ErrFatal::LOCK).clear();
+
+        let mut search_params = SearchParams::new();
+        search_params.depth = depth;
+        search_params.search_mode = SearchMode::Depth;
+        search_params.quiet = true;
+        let mut search_info = SearchInfo::new();
+
+        let mut refs = SearchRefs {
+            board: &mut board,
+            mg: &mg,
+            tt: &tt,
+            tt_enabled: hash > 0,
+            search_params: &mut search_params,
+            search_info: &mut search_info,
+            control_rx: &control_rx,
+            report_tx: &report_tx,
+        };
+
+        let now = Instant::now();
+        Search::iterative_deepening(&mut refs);
+        let elapsed = now.elapsed().as_millis();
+        let nodes = search_info.nodes;
+        report_rx.try_iter().for_each(drop);
+
+        total_nodes += nodes;
+        total_time += elapsed;
+        println!(
+            "Position {:>2}: {:>10} nodes {:>6} ms  {}",
+            i + 1,
+            nodes,
+            elapsed,
+            fen
+        );
+    }
+
+    let nps = Search::nodes_per_second(total_nodes, total_time);
+    println!();
+    println!("Total time (ms) : {total_time}");
+    println!("Nodes searched  : {total_nodes}");
+    println!("Nodes/second    : {nps}");
+}
@@ -0,0 +1,769 @@
+/* =======================================================================
+Rustic is a chess playing engine.
+Copyright (C) 2019-2024, Marcel Vanthoor
+https://rustic-chess.org/
+
+Rustic is written in the Rust programming language. It is an original
+work, not derived from any engine that came before it. However, it does
+use a lot of concepts which are well-known and are in use by most if not
+all classical alpha/beta-based chess engines.
+
+Rustic is free software: you can redistribute it and/or modify it under
+the terms of the GNU General Public License version 3 as published by
+the Free Software Foundation.
+
+Rustic is distributed in the hope that it will be useful, but WITHOUT
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+for more details.
+
+You should have received a copy of the GNU General Public License along
+with this program.  If not, see <http://www.gnu.org/licenses/>.
+======================================================================= */
+
+use crate::{
+    defs::{About, FEN_START_POSITION},
+    engine::defs::EngineOptionDefaults,
+    movegen::defs::MoveGenMode,
+    tools::bench::{BENCH_DEPTH_DEFAULT, BENCH_HASH_DEFAULT},
+    tools::datagen,
+    tools::tuner::{ITERATIONS_DEFAULT, LEARNING_RATE_DEFAULT},
+};
+use clap::{value_parser, Arg, ArgAction, ArgMatches};
+
+// Consts for command line options, flags and arguments
+
+struct CmdLineArgs {}
+impl CmdLineArgs {
+    // FEN
+    const FEN_LONG: &'static str = "fen";
+    const FEN_SHORT: char = 'f';
+    const FEN_HELP: &'static str = "Set up the given position";
+
+    // Perft
+    const PERFT_LONG: &'static str = "perft";
+    const PERFT_SHORT: char = 'p';
+    const PERFT_HELP: &'static str = "Run perft to the given depth";
+    const PERFT_DEFAULT: i8 = 0;
+
+    // Move generator
+    const MOVEGEN_LONG: &'static str = "movegen";
+    const MOVEGEN_SHORT: char = 'm';
+    const MOVEGEN_HELP: &'static str = "Move generator for perft (compare: cross-validate both)";
+    const MOVEGEN_VALUES: [&'static str; 3] = [
+        MoveGenMode::PSEUDO,
+        MoveGenMode::LEGAL,
+        MoveGenMode::COMPARE,
+    ];
+    const MOVEGEN_DEFAULT: &'static str = MoveGenMode::PSEUDO;
+
+    // Interface
+    const COMM_LONG: &'static str = "comm";
+    const COMM_SHORT: char = 'c';
+    const COMM_HELP: &'static str = "Select communication protocol to use";
+    const COMM_VALUES: [&'static str; 2] = ["uci", "xboard"];
+    const COMM_DEFAULT: &'static str = "uci";
+
+    // Threads
+    const THREADS_LONG: &'static str = "threads";
+    const THREADS_SHORT: char = 't';
+    const THREADS_HELP: &'static str = "Number of CPU-threads to use";
+    const THREADS_DEFAULT: usize = 1;
+
+    const HASH_LONG: &'static str = "hash";
+    const HASH_SHORT: char = 'h';
+    const HASH_HELP: &'static str = "Transposition Table size in MB";
+    const HASH_DEFAULT: usize = EngineOptionDefaults::HASH_DEFAULT;
+
+    // Help. Clap's own help flag would also claim -h, which is taken by
+    // the hash size, so help is only available as --help. It is global,
+    // so the subcommands have it as well.
+    const HELP_LONG: &'static str = "help";
+    const HELP_HELP: &'static str = "Print help";
+
+    // Quiet (no search stats updates except on depth change)
+    const QUIET_LONG: &'static str = "quiet";
+    const QUIET_SHORT: char = 'q';
+    const QUIET_HELP: &'static str = "No intermediate search stats updates";
+
+    // Kiwipete
+    const KIWI_LONG: &'static str = "kiwipete";
+    const KIWI_SHORT: char = 'k';
+    const KIWI_HELP: &'static str = "Set up KiwiPete position (ignore --fen)";
+
+    // EPD solver (long options only)
+    const EPD_SOLVE_LONG: &'static str = "epd-solve";
+    const EPD_SOLVE_HELP: &'static str = "Search each position in an EPD file and check bm/am";
+    const MOVETIME_LONG: &'static str = "movetime";
+    const MOVETIME_HELP: &'static str = "Time per position in ms for --epd-solve";
+    const MOVETIME_DEFAULT: u128 = 1000;
+
+    // Perft suite (long options only)
+    const PERFT_SUITE_LONG: &'static str = "perft-suite";
+    const PERFT_SUITE_HELP: &'static str = "Run the perft test suite in the given EPD file";
+    const SUITE_DEPTH_LONG: &'static str = "suite-depth";
+    const SUITE_DEPTH_HELP: &'static str = "Maximum depth to run in the perft suite";
+    const SUITE_DEPTH_DEFAULT: i8 = 0;
+    const SUITE_SELECT_LONG: &'static str = "suite-select";
+    const SUITE_SELECT_HELP: &'static str = "Tests to run in the perft suite, such as 1-10,15";
+    const KEEP_GOING_LONG: &'static str = "keep-going";
+    const KEEP_GOING_HELP: &'static str = "Continue the perft suite after a failed test";
+
+    // Bench (subcommand, with optional depth and hash size)
+    const BENCH: &'static str = "bench";
+    const BENCH_HELP: &'static str = "Search the bench positions and print nodes and speed";
+    const BENCH_DEPTH: &'static str = "depth";
+    const BENCH_DEPTH_HELP: &'static str = "Depth to search each position to";
+    const BENCH_HASH: &'static str = "hash";
+    const BENCH_HASH_HELP: &'static str = "Transposition Table size in MB";
+
+    // Self-play and gauntlet (subcommands, with long options only)
+    const SELFPLAY: &'static str = "selfplay";
+    const SELFPLAY_HELP: &'static str = "Play a match between two engine configurations";
+    const GAUNTLET: &'static str = "gauntlet";
+    const GAUNTLET_HELP: &'static str = "Play a match against each of the given UCI engines";
+    const OPENINGS_LONG: &'static str = "openings";
+    const OPENINGS_HELP: &'static str = "EPD or PGN file with the opening positions";
+    const GAMES_LONG: &'static str = "games";
+    const GAMES_HELP: &'static str = "Number of games per match (0: no limit)";
+    const GAMES_DEFAULT: usize = 100;
+    const TC_LONG: &'static str = "tc";
+    const TC_HELP: &'static str = "Time control in seconds, such as 10+0.1";
+    const TC_DEFAULT: &'static str = "10+0.1";
+    const ENGINE1_LONG: &'static str = "engine1";
+    const ENGINE1_HELP: &'static str =
+        "First configuration, such as name=Dev,hash=64,evalfile=dev.nnue";
+    const ENGINE2_LONG: &'static str = "engine2";
+    const ENGINE2_HELP: &'static str = "Second configuration, such as name=Base,hash=64";
+    const SPRT_LONG: &'static str = "sprt";
+    const SPRT_HELP: &'static str = "Stop at the SPRT result for Elo bounds elo0,elo1";
+    const ALPHA_LONG: &'static str = "alpha";
+    const ALPHA_HELP: &'static str = "SPRT chance of accepting H1 while H0 is true";
+    const BETA_LONG: &'static str = "beta";
+    const BETA_HELP: &'static str = "SPRT chance of accepting H0 while H1 is true";
+    const ALPHA_BETA_DEFAULT: f64 = 0.05;
+    const PGN_OUT_LONG: &'static str = "pgnout";
+    const PGN_OUT_HELP: &'static str = "Write the games to this PGN file";
+    const PGN_OUT_GAUNTLET_DEFAULT: &'static str = "gauntlet.pgn";
+    const CONFIG_LONG: &'static str = "config";
+    const CONFIG_HELP: &'static str = "Rustic's configuration, such as name=Dev,hash=64";
+    const ENGINE_LONG: &'static str = "engine";
+    const ENGINE_HELP: &'static str = "Path to a UCI engine to play against (repeatable)";
+
+    // Tuner (subcommand, with long options only)
+    const TUNE: &'static str = "tune";
+    const TUNE_HELP: &'static str = "Tune the evaluation on positions labelled with results";
+    const DATA_LONG: &'static str = "data";
+    const DATA_HELP: &'static str = "File with quiet positions and game results";
+    const ITERATIONS_LONG: &'static str = "iterations";
+    const ITERATIONS_HELP: &'static str = "Number of gradient descent iterations";
+    const RATE_LONG: &'static str = "rate";
+    const RATE_HELP: &'static str = "Learning rate, in centipawns per step";
+    const OUT_LONG: &'static str = "out";
+    const OUT_HELP: &'static str = "File to write the tuned tables to";
+    const OUT_DEFAULT: &'static str = "psqt_tuned.rs";
+
+    // Data generator (subcommand, with long options only)
+    const DATAGEN: &'static str = "datagen";
+    const DATAGEN_HELP: &'static str = "Generate training data from fast self-play games";
+    const DATAGEN_GAMES_HELP: &'static str = "Number of games to play, over all threads";
+    const NODES_LONG: &'static str = "nodes";
+    const NODES_HELP: &'static str = "Nodes to search per move";
+    const RANDOM_PLIES_LONG: &'static str = "random-plies";
+    const RANDOM_PLIES_HELP: &'static str = "Random moves at the start of each game";
+    const DATAGEN_THREADS_HELP: &'static str = "Number of threads, each writing its own file";
+    const DATAGEN_HASH_HELP: &'static str = "Transposition Table size in MB per thread";
+    const DATAGEN_HASH_DEFAULT: usize = 16;
+    const DATAGEN_OUT_HELP: &'static str = "Output file name; threads add _0, _1, ...";
+    const DATAGEN_OUT_DEFAULT: &'static str = "datagen.txt";
+
+    // Wizardry
+    const WIZARDRY_LONG: &'static str = "wizardry";
+    const WIZARDRY_SHORT: char = 'w';
+    const WIZARDRY_HELP: &'static str = "Generate magic numbers";
+
+    // Test
+    const EPD_TEST_LONG: &'static str = "epdtest";
+    const EPD_TEST_SHORT: char = 'e';
+    const EPD_TEST_HELP: &'static str = "Run EPD Test Suite";
+}
+
+// The self-play or gauntlet settings as given on the command line. They
+// are checked and converted when the match is set up. In a gauntlet, the
+// first configuration is Rustic's, and the engines are its opponents.
+pub struct MatchArgs {
+    pub openings: Option<String>,
+    pub games: usize,
+    pub tc: String,
+    pub configs: [String; 2],
+    pub engines: Vec<String>,
+    pub sprt: Option<String>,
+    pub alpha: f64,
+    pub beta: f64,
+    pub pgn_out: Option<String>,
+}
+
+// The tuner settings as given on the command line.
+pub struct TuneArgs {
+    pub data: String,
+    pub iterations: usize,
+    pub rate: f64,
+    pub out: String,
+}
+
+// The data generator settings as given on the command line.
+pub struct DatagenArgs {
+    pub games: usize,
+    pub nodes: usize,
+    pub random_plies: usize,
+    pub threads: usize,
+    pub hash: usize,
+    pub out: String,
+}
+
+pub struct CmdLine {
+    arguments: ArgMatches,
+}
+
+impl CmdLine {
+    pub fn new() -> Self {
+        Self {
+            arguments: Self::get(),
+        }
+    }
+
+    pub fn comm(&self) -> String {
+        self.arguments
+            .get_one::<String>(CmdLineArgs::COMM_LONG)
+            .unwrap_or(&CmdLineArgs::COMM_DEFAULT.to_string())
+            .clone()
+    }
+
+    pub fn fen(&self) -> String {
+        self.arguments
+            .get_one::<String>(CmdLineArgs::FEN_LONG)
+            .unwrap_or(&FEN_START_POSITION.to_string())
+            .clone()
+    }
+
+    pub fn perft(&self) -> i8 {
+        *self
+            .arguments
+            .get_one::<i8>(CmdLineArgs::PERFT_LONG)
+            .unwrap_or(&CmdLineArgs::PERFT_DEFAULT)
+    }
+
+    pub fn movegen(&self) -> String {
+        self.arguments
+            .get_one::<String>(CmdLineArgs::MOVEGEN_LONG)
+            .unwrap_or(&CmdLineArgs::MOVEGEN_DEFAULT.to_string())
+            .clone()
+    }
+
+    pub fn threads(&self) -> usize {
+        *self
+            .arguments
+            .get_one::<usize>(CmdLineArgs::THREADS_LONG)
+            .unwrap_or(&CmdLineArgs::THREADS_DEFAULT)
+    }
+
+    pub fn hash(&self) -> usize {
+        *self
+            .arguments
+            .get_one::<usize>(CmdLineArgs::HASH_LONG)
+            .unwrap_or(&CmdLineArgs::HASH_DEFAULT)
+    }
+
+    pub fn epd_solve(&self) -> Option<String> {
+        self.arguments
+            .get_one::<String>(CmdLineArgs::EPD_SOLVE_LONG)
+            .cloned()
+    }
+
+    pub fn movetime(&self) -> u128 {
+        *self
+            .arguments
+            .get_one::<u128>(CmdLineArgs::MOVETIME_LONG)
+            .unwrap_or(&CmdLineArgs::MOVETIME_DEFAULT)
+    }
+
+    pub fn perft_suite(&self) -> Option<String> {
+        self.arguments
+            .get_one::<String>(CmdLineArgs::PERFT_SUITE_LONG)
+            .cloned()
+    }
+
+    pub fn suite_depth(&self) -> i8 {
+        *self
+            .arguments
+            .get_one::<i8>(CmdLineArgs::SUITE_DEPTH_LONG)
+            .unwrap_or(&CmdLineArgs::SUITE_DEPTH_DEFAULT)
+    }
+
+    pub fn suite_select(&self) -> Option<String> {
+        self.arguments
+            .get_one::<String>(CmdLineArgs::SUITE_SELECT_LONG)
+            .cloned()
+    }
+
+    pub fn has_keep_going(&self) -> bool {
+        self.arguments.get_flag(CmdLineArgs::KEEP_GOING_LONG)
+    }
+
+    pub fn bench(&self) -> Option<(i8, usize)> {
+        self.arguments
+            .subcommand_matches(CmdLineArgs::BENCH)
+            .map(|bench| {
+                let depth = bench
+                    .get_one::<i8>(CmdLineArgs::BENCH_DEPTH)
+                    .unwrap_or(&BENCH_DEPTH_DEFAULT);
+                let hash = bench
+                    .get_one::<usize>(CmdLineArgs::BENCH_HASH)
+                    .unwrap_or(&BENCH_HASH_DEFAULT);
+                (*depth, *hash)
+            })
+    }
+
+    pub fn selfplay(&self) -> Option<MatchArgs> {
+        self.arguments
+            .subcommand_matches(CmdLineArgs::SELFPLAY)
+            .map(|sp| {
+                let mut args = Self::match_args(sp);
+                args.configs = [
+                    Self::string(sp, CmdLineArgs::ENGINE1_LONG).unwrap_or_default(),
+                    Self::string(sp, CmdLineArgs::ENGINE2_LONG).unwrap_or_default(),
+                ];
+                args.sprt = Self::string(sp, CmdLineArgs::SPRT_LONG);
+                args.alpha = *sp
+                    .get_one::<f64>(CmdLineArgs::ALPHA_LONG)
+                    .unwrap_or(&CmdLineArgs::ALPHA_BETA_DEFAULT);
+                args.beta = *sp
+                    .get_one::<f64>(CmdLineArgs::BETA_LONG)
+                    .unwrap_or(&CmdLineArgs::ALPHA_BETA_DEFAULT);
+                args
+            })
+    }
+
+    pub fn gauntlet(&self) -> Option<MatchArgs> {
+        self.arguments
+            .subcommand_matches(CmdLineArgs::GAUNTLET)
+            .map(|g| {
+                let mut args = Self::match_args(g);
+                args.configs[0] = Self::string(g, CmdLineArgs::CONFIG_LONG).unwrap_or_default();
+                args.engines = g
+                    .get_many::<String>(CmdLineArgs::ENGINE_LONG)
+                    .map(|e| e.cloned().collect())
+                    .unwrap_or_default();
+                args.pgn_out = args
+                    .pgn_out
+                    .or(Some(String::from(CmdLineArgs::PGN_OUT_GAUNTLET_DEFAULT)));
+                args
+            })
+    }
+
+    pub fn tune(&self) -> Option<TuneArgs> {
+        self.arguments
+            .subcommand_matches(CmdLineArgs::TUNE)
+            .map(|t| TuneArgs {
+                data: Self::string(t, CmdLineArgs::DATA_LONG).unwrap_or_default(),
+                iterations: *t
+                    .get_one::<usize>(CmdLineArgs::ITERATIONS_LONG)
+                    .unwrap_or(&ITERATIONS_DEFAULT),
+                rate: *t
+                    .get_one::<f64>(CmdLineArgs::RATE_LONG)
+                    .unwrap_or(&LEARNING_RATE_DEFAULT),
+                out: Self::string(t, CmdLineArgs::OUT_LONG)
+                    .unwrap_or(String::from(CmdLineArgs::OUT_DEFAULT)),
+            })
+    }
+
+    pub fn datagen(&self) -> Option<DatagenArgs> {
+        self.arguments
+            .subcommand_matches(CmdLineArgs::DATAGEN)
+            .map(|d| DatagenArgs {
+                games: *d
+                    .get_one::<usize>(CmdLineArgs::GAMES_LONG)
+                    .unwrap_or(&datagen::GAMES_DEFAULT),
+                nodes: *d
+                    .get_one::<usize>(CmdLineArgs::NODES_LONG)
+                    .unwrap_or(&datagen::NODES_DEFAULT),
+                random_plies: *d
+                    .get_one::<usize>(CmdLineArgs::RANDOM_PLIES_LONG)
+                    .unwrap_or(&datagen::RANDOM_PLIES_DEFAULT),
+                threads: *d
+                    .get_one::<usize>(CmdLineArgs::THREADS_LONG)
+                    .unwrap_or(&CmdLineArgs::THREADS_DEFAULT),
+                hash: *d
+                    .get_one::<usize>(CmdLineArgs::HASH_LONG)
+                    .unwrap_or(&CmdLineArgs::DATAGEN_HASH_DEFAULT),
+                out: Self::string(d, CmdLineArgs::OUT_LONG)
+                    .unwrap_or(String::from(CmdLineArgs::DATAGEN_OUT_DEFAULT)),
+            })
+    }
+
+    // The settings that self-play and gauntlet have in common.
+    fn match_args(matches: &ArgMatches) -> MatchArgs {
+        MatchArgs {
+            openings: Self::string(matches, CmdLineArgs::OPENINGS_LONG),
+            games: *matches
+                .get_one::<usize>(CmdLineArgs::GAMES_LONG)
+                .unwrap_or(&CmdLineArgs::GAMES_DEFAULT),
+            tc: Self::string(matches, CmdLineArgs::TC_LONG)
+                .unwrap_or(String::from(CmdLineArgs::TC_DEFAULT)),
+            configs: [String::new(), String::new()],
+            engines: Vec::new(),
+            sprt: None,
+            alpha: CmdLineArgs::ALPHA_BETA_DEFAULT,
+            beta: CmdLineArgs::ALPHA_BETA_DEFAULT,
+            pgn_out: Self::string(matches, CmdLineArgs::PGN_OUT_LONG),
+        }
+    }
+
+    fn string(matches: &ArgMatches, name: &str) -> Option<String> {
+        matches.get_one::<String>(name).cloned()
+    }
+
+    pub fn has_kiwipete(&self) -> bool {
+        self.arguments.get_flag(CmdLineArgs::KIWI_LONG)
+    }
+
+    pub fn has_quiet(&self) -> bool {
+        self.arguments.get_flag(CmdLineArgs::QUIET_LONG)
+    }
+
+    #[cfg(feature = "extra")]
+    pub fn has_wizardry(&self) -> bool {
+        self.arguments.get_flag(CmdLineArgs::WIZARDRY_LONG)
+    }
+
+    #[cfg(feature = "extra")]
+    pub fn has_test(&self) -> bool {
+        self.arguments.get_flag(CmdLineArgs::EPD_TEST_LONG)
+    }
+
+    fn get() -> ArgMatches {
+        let mut cmd_line = clap::Command::new(About::ENGINE)
+            .version(About::VERSION)
+            .author(About::AUTHOR)
+            .about(About::WEBSITE)
+            .disable_help_flag(true)
+            .arg(
+                Arg::new(CmdLineArgs::HELP_LONG)
+                    .long(CmdLineArgs::HELP_LONG)
+                    .help(CmdLineArgs::HELP_HELP)
+                    .action(ArgAction::Help)
+                    .global(true),
+            )
+            .arg(
+                Arg::new(CmdLineArgs::COMM_LONG)
+                    .short(CmdLineArgs::COMM_SHORT)
+                    .long(CmdLineArgs::COMM_LONG)
+                    .help(CmdLineArgs::COMM_HELP)
+                    .num_args(1)
+                    .default_value(CmdLineArgs::COMM_DEFAULT)
+                    .value_parser(CmdLineArgs::COMM_VALUES),
+            )
+            .arg(
+                Arg::new(CmdLineArgs::FEN_LONG)
+                    .short(CmdLineArgs::FEN_SHORT)
+                    .long(CmdLineArgs::FEN_LONG)
+                    .help(CmdLineArgs::FEN_HELP)
+                    .num_args(1)
+                    .default_value(FEN_START_POSITION)
+                    .value_parser(value_parser!(String)),
+            )
+            .arg(
+                Arg::new(CmdLineArgs::PERFT_LONG)
+                    .short(CmdLineArgs::PERFT_SHORT)
+                    .long(CmdLineArgs::PERFT_LONG)
+                    .help(CmdLineArgs::PERFT_HELP)
+                    .value_parser(value_parser!(i8))
+                    .num_args(1),
+            )
+            .arg(
+                Arg::new(CmdLineArgs::MOVEGEN_LONG)
+                    .short(CmdLineArgs::MOVEGEN_SHORT)
+                    .long(CmdLineArgs::MOVEGEN_LONG)
+                    .help(CmdLineArgs::MOVEGEN_HELP)
+                    .num_args(1)
+                    .default_value(CmdLineArgs::MOVEGEN_DEFAULT)
+                    .value_parser(CmdLineArgs::MOVEGEN_VALUES),
+            )
+            .arg(
+                Arg::new(CmdLineArgs::THREADS_LONG)
+                    .short(CmdLineArgs::THREADS_SHORT)
+                    .long(CmdLineArgs::THREADS_LONG)
+                    .help(CmdLineArgs::THREADS_HELP)
+                    .value_parser(value_parser!(usize))
+                    .num_args(1),
+            )
+            .arg(
+                Arg::new(CmdLineArgs::HASH_LONG)
+                    .short(CmdLineArgs::HASH_SHORT)
+                    .long(CmdLineArgs::HASH_LONG)
+                    .help(CmdLineArgs::HASH_HELP)
+                    .value_parser(value_parser!(usize))
+                    .num_args(1),
+            )
+            .arg(
+                Arg::new(CmdLineArgs::EPD_SOLVE_LONG)
+                    .long(CmdLineArgs::EPD_SOLVE_LONG)
+                    .help(CmdLineArgs::EPD_SOLVE_HELP)
+                    .value_parser(value_parser!(String))
+                    .num_args(1),
+            )
+            .arg(
+                Arg::new(CmdLineArgs::MOVETIME_LONG)
+                    .long(CmdLineArgs::MOVETIME_LONG)
+                    .help(CmdLineArgs::MOVETIME_HELP)
+                    .value_parser(value_parser!(u128))
+                    .num_args(1),
+            )
+            .arg(
+                Arg::new(CmdLineArgs::PERFT_SUITE_LONG)
+                    .long(CmdLineArgs::PERFT_SUITE_LONG)
+                    .help(CmdLineArgs::PERFT_SUITE_HELP)
+                    .value_parser(value_parser!(String))
+                    .num_args(1),
+            )
+            .arg(
+                Arg::new(CmdLineArgs::SUITE_DEPTH_LONG)
+                    .long(CmdLineArgs::SUITE_DEPTH_LONG)
+                    .help(CmdLineArgs::SUITE_DEPTH_HELP)
+                    .value_parser(value_parser!(i8))
+                    .num_args(1),
+            )
+            .arg(
+                Arg::new(CmdLineArgs::SUITE_SELECT_LONG)
+                    .long(CmdLineArgs::SUITE_SELECT_LONG)
+                    .help(CmdLineArgs::SUITE_SELECT_HELP)
+                    .value_parser(value_parser!(String))
+                    .num_args(1),
+            )
+            .arg(
+                Arg::new(CmdLineArgs::KEEP_GOING_LONG)
+                    .long(CmdLineArgs::KEEP_GOING_LONG)
+                    .help(CmdLineArgs::KEEP_GOING_HELP)
+                    .action(ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new(CmdLineArgs::KIWI_LONG)
+                    .long(CmdLineArgs::KIWI_LONG)
+                    .short(CmdLineArgs::KIWI_SHORT)
+                    .help(CmdLineArgs::KIWI_HELP)
+                    .action(ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new(CmdLineArgs::QUIET_LONG)
+                    .long(CmdLineArgs::QUIET_LONG)
+                    .short(CmdLineArgs::QUIET_SHORT)
+                    .help(CmdLineArgs::QUIET_HELP)
+                    .action(ArgAction::SetTrue),
+            )
+            .subcommand(
+                clap::Command::new(CmdLineArgs::BENCH)
+                    .about(CmdLineArgs::BENCH_HELP)
+                    .arg(
+                        Arg::new(CmdLineArgs::BENCH_DEPTH)
+                            .help(CmdLineArgs::BENCH_DEPTH_HELP)
+                            .value_parser(value_parser!(i8)),
+                    )
+                    .arg(
+                        Arg::new(CmdLineArgs::BENCH_HASH)
+                            .help(CmdLineArgs::BENCH_HASH_HELP)
+                            .value_parser(value_parser!(usize)),
+                    ),
+            )
+            .subcommand(
+                Self::match_command(CmdLineArgs::SELFPLAY, CmdLineArgs::SELFPLAY_HELP)
+                    .arg(
+                        Arg::new(CmdLineArgs::ENGINE1_LONG)
+                            .long(CmdLineArgs::ENGINE1_LONG)
+                            .help(CmdLineArgs::ENGINE1_HELP)
+                            .value_parser(value_parser!(String))
+                            .num_args(1),
+                    )
+                    .arg(
+                        Arg::new(CmdLineArgs::ENGINE2_LONG)
+                            .long(CmdLineArgs::ENGINE2_LONG)
+                            .help(CmdLineArgs::ENGINE2_HELP)
+                            .value_parser(value_parser!(String))
+                            .num_args(1),
+                    )
+                    .arg(
+                        Arg::new(CmdLineArgs::SPRT_LONG)
+                            .long(CmdLineArgs::SPRT_LONG)
+                            .help(CmdLineArgs::SPRT_HELP)
+                            .value_parser(value_parser!(String))
+                            .allow_hyphen_values(true)
+                            .num_args(1),
+                    )
+                    .arg(
+                        Arg::new(CmdLineArgs::ALPHA_LONG)
+                            .long(CmdLineArgs::ALPHA_LONG)
+                            .help(CmdLineArgs::ALPHA_HELP)
+                            .value_parser(value_parser!(f64))
+                            .num_args(1),
+                    )
+                    .arg(
+                        Arg::new(CmdLineArgs::BETA_LONG)
+                            .long(CmdLineArgs::BETA_LONG)
+                            .help(CmdLineArgs::BETA_HELP)
+                            .value_parser(value_parser!(f64))
+                            .num_args(1),
+                    ),
+            )
+            .subcommand(
+                Self::match_command(CmdLineArgs::GAUNTLET, CmdLineArgs::GAUNTLET_HELP)
+                    .arg(
+                        Arg::new(CmdLineArgs::CONFIG_LONG)
+                            .long(CmdLineArgs::CONFIG_LONG)
+                            .help(CmdLineArgs::CONFIG_HELP)
+                            .value_parser(value_parser!(String))
+                            .num_args(1),
+                    )
+                    .arg(
+                        Arg::new(CmdLineArgs::ENGINE_LONG)
+                            .long(CmdLineArgs::ENGINE_LONG)
+                            .help(CmdLineArgs::ENGINE_HELP)
+                            .value_parser(value_parser!(String))
+                            .action(ArgAction::Append)
+                            .required(true),
+                    ),
+            )
+            .subcommand(
+                clap::Command::new(CmdLineArgs::TUNE)
+                    .about(CmdLineArgs::TUNE_HELP)
+                    .arg(
+                        Arg::new(CmdLineArgs::DATA_LONG)
+                            .long(CmdLineArgs::DATA_LONG)
+                            .help(CmdLineArgs::DATA_HELP)
+                            .value_parser(value_parser!(String))
+                            .required(true)
+                            .num_args(1),
+                    )
+                    .arg(
+                        Arg::new(CmdLineArgs::ITERATIONS_LONG)
+                            .long(CmdLineArgs::ITERATIONS_LONG)
+                            .help(CmdLineArgs::ITERATIONS_HELP)
+                            .value_parser(value_parser!(usize))
+                            .num_args(1),
+                    )
+                    .arg(
+                        Arg::new(CmdLineArgs::RATE_LONG)
+                            .long(CmdLineArgs::RATE_LONG)
+                            .help(CmdLineArgs::RATE_HELP)
+                            .value_parser(value_parser!(f64))
+                            .num_args(1),
+                    )
+                    .arg(
+                        Arg::new(CmdLineArgs::OUT_LONG)
+                            .long(CmdLineArgs::OUT_LONG)
+                            .help(CmdLineArgs::OUT_HELP)
+                            .value_parser(value_parser!(String))
+                            .num_args(1),
+                    ),
+            )
+            .subcommand(
+                clap::Command::new(CmdLineArgs::DATAGEN)
+                    .about(CmdLineArgs::DATAGEN_HELP)
+                    .arg(
+                        Arg::new(CmdLineArgs::GAMES_LONG)
+                            .long(CmdLineArgs::GAMES_LONG)
+                            .help(CmdLineArgs::DATAGEN_GAMES_HELP)
+                            .value_parser(value_parser!(usize))
+                            .num_args(1),
+                    )
+                    .arg(
+                        Arg::new(CmdLineArgs::NODES_LONG)
+                            .long(CmdLineArgs::NODES_LONG)
+                            .help(CmdLineArgs::NODES_HELP)
+                            .value_parser(value_parser!(usize))
+                            .num_args(1),
+                    )
+                    .arg(
+                        Arg::new(CmdLineArgs::RANDOM_PLIES_LONG)
+                            .long(CmdLineArgs::RANDOM_PLIES_LONG)
+                            .help(CmdLineArgs::RANDOM_PLIES_HELP)
+                            .value_parser(value_parser!(usize))
+                            .num_args(1),
+                    )
+                    .arg(
+                        Arg::new(CmdLineArgs::THREADS_LONG)
+                            .long(CmdLineArgs::THREADS_LONG)
+                            .help(CmdLineArgs::DATAGEN_THREADS_HELP)
+                            .value_parser(value_parser!(usize))
+                            .num_args(1),
+                    )
+                    .arg(
+                        Arg::new(CmdLineArgs::HASH_LONG)
+                            .long(CmdLineArgs::HASH_LONG)
+                            .help(CmdLineArgs::DATAGEN_HASH_HELP)
+                            .value_parser(value_parser!(usize))
+                            .num_args(1),
+                    )
+                    .arg(
+                        Arg::new(CmdLineArgs::OUT_LONG)
+                            .long(CmdLineArgs::OUT_LONG)
+                            .help(CmdLineArgs::DATAGEN_OUT_HELP)
+                            .value_parser(value_parser!(String))
+                            .num_args(1),
+                    ),
+            );
+
+        if cfg!(feature = "extra") {
+            cmd_line = cmd_line
+                .arg(
+                    Arg::new(CmdLineArgs::WIZARDRY_LONG)
+                        .short(CmdLineArgs::WIZARDRY_SHORT)
+                        .long(CmdLineArgs::WIZARDRY_LONG)
+                        .help(CmdLineArgs::WIZARDRY_HELP)
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new(CmdLineArgs::EPD_TEST_LONG)
+                        .short(CmdLineArgs::EPD_TEST_SHORT)
+                        .long(CmdLineArgs::EPD_TEST_LONG)
+                        .help(CmdLineArgs::EPD_TEST_HELP)
+                        .action(ArgAction::SetTrue),
+                );
+        }
+
+        cmd_line.get_matches()
+    }
+
+    // A subcommand that plays a match, with the settings that self-play
+    // and gauntlet have in common.
+    fn match_command(name: &'static str, about: &'static str) -> clap::Command {
+        clap::Command::new(name)
+            .about(about)
+            .arg(
+                Arg::new(CmdLineArgs::OPENINGS_LONG)
+                    .long(CmdLineArgs::OPENINGS_LONG)
+                    .help(CmdLineArgs::OPENINGS_HELP)
+                    .value_parser(value_parser!(String))
+                    .num_args(1),
+            )
+            .arg(
+                Arg::new(CmdLineArgs::GAMES_LONG)
+                    .long(CmdLineArgs::GAMES_LONG)
+                    .help(CmdLineArgs::GAMES_HELP)
+                    .value_parser(value_parser!(usize))
+                    .num_args(1),
+            )
+            .arg(
+                Arg::new(CmdLineArgs::TC_LONG)
+                    .long(CmdLineArgs::TC_LONG)
+                    .help(CmdLineArgs::TC_HELP)
+                    .value_parser(value_parser!(String))
+                    .num_args(1),
+            )
+            .arg(
+                Arg::new(CmdLineArgs::PGN_OUT_LONG)
+                    .long(CmdLineArgs::PGN_OUT_LONG)
+                    .help(CmdLineArgs::PGN_OUT_HELP)
+                    .value_parser(value_parser!(String))
+                    .num_args(1),
+            )
+    }
+}
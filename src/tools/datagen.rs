@@ -0,0 +1,239 @@
+/* =======================================================================
+Rustic is a chess playing engine.
+Copyright (C) 2019-2024, Marcel Vanthoor
+https://rustic-chess.org/
+
+Rustic is written in the Rust programming language. It is an original
+work, not derived from any engine that came before it. However, it does
+use a lot of concepts which are well-known and are in use by most if not
+all classical alpha/beta-based chess engines.
+
+Rustic is free software: you can redistribute it and/or modify it under
+the terms of the GNU General Public License version 3 as published by
+the Free Software Foundation.
+
+Rustic is distributed in the hope that it will be useful, but WITHOUT
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+for more details.
+
+You should have received a copy of the GNU General Public License along
+with this program.  If not, see <http://www.gnu.org/licenses/>.
+======================================================================= */
+
+// datagen.rs generates training data for tuning the evaluation, by playing
+// fast self-play games with a fixed number of nodes per move. Each game
+// starts with a few random moves, so the games don't repeat. Positions
+// are kept if they are quiet: the side to move is not in check, and the
+// best move is not a capture. Each kept position is written as a line
+// with the FEN, the search score and the game result, both from white's
+// point of view:
+//
+// rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1 | 35 | 0.5
+//
+// Each thread plays its own games and writes to its own file, so the
+// threads don't have to wait for each other.
+
+use crate::{
+    board::{defs::Pieces, Board},
+    defs::Sides,
+    engine::defs::{ErrFatal, SearchData, TT},
+    movegen::{
+        defs::{Move, MoveList, MoveType},
+        MoveGenerator,
+    },
+    search::{
+        defs::{
+            SearchControl, SearchInfo, SearchMode, SearchParams, SearchRefs, SearchReport,
+            SearchSummary,
+        },
+        Search,
+    },
+    tools::selfplay,
+};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaChaRng;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+pub const GAMES_DEFAULT: usize = 100;
+pub const NODES_DEFAULT: usize = 5_000;
+pub const RANDOM_PLIES_DEFAULT: usize = 8;
+
+// How often each thread reports its progress, in games.
+const REPORT_INTERVAL: usize = 10;
+
+pub struct DatagenOptions {
+    pub games: usize,        // Total number of games, over all threads.
+    pub nodes: usize,        // Nodes to search per move.
+    pub random_plies: usize, // Random moves at the start of each game.
+    pub hash: usize,         // TT size per thread, in MB.
+}
+
+// A position that will be written once the game result is known.
+struct Sample {
+    fen: String,
+    score: i16,
+}
+
+// Play the games, divided over one thread per output file.
+pub fn run(options: &DatagenOptions, files: Vec<File>, mg: Arc<MoveGenerator>) {
+    let threads = files.len();
+    let mut handles = Vec::new();
+
+    println!(
+        "Generating data: {} games, {} nodes per move, {} random plies, {} threads",
+        options.games, options.nodes, options.random_plies, threads
+    );
+
+    for (i, file) in files.into_iter().enumerate() {
+        // Divide the games as evenly as possible.
+        let games = options.games / threads + usize::from(i < options.games % threads);
+        let nodes = options.nodes;
+        let random_plies = options.random_plies;
+        let hash = options.hash;
+        let mg = Arc::clone(&mg);
+
+        handles.push(thread::spawn(move || {
+            let mut writer = BufWriter::new(file);
+            let mut positions = 0;
+            let tt = Arc::new(Mutex::new(TT::<SearchData>::new(hash)));
+            let mut random = ChaChaRng::from_entropy();
+
+            for game in 1..=games {
+                positions += play_game(&mut writer, &tt, &mg, &mut random, nodes, random_plies);
+                if game % REPORT_INTERVAL == 0 || game == games {
+                    println!("Thread {}: {} games, {} positions", i, game, positions);
+                }
+            }
+            writer.flush().expect(ErrFatal::WRITE_IO);
+
+            positions
+        }));
+    }
+
+    let total: usize = handles
+        .into_iter()
+        .map(|h| h.join().expect(ErrFatal::THREAD))
+        .sum();
+    println!("Done: {} positions", total);
+}
+
+// Play a single game, write its quiet positions, and return how many
+// there were.
+fn play_game(
+    writer: &mut impl Write,
+    tt: &Arc<Mutex<TT<SearchData>>>,
+    mg: &Arc<MoveGenerator>,
+    random: &mut ChaChaRng,
+    nodes: usize,
+    random_plies: usize,
+) -> usize {
+    let mut board = random_opening(mg, random, random_plies);
+    let mut samples: Vec<Sample> = Vec::new();
+    tt.lock().expect(ErrFatal::LOCK).clear();
+
+    // The search needs channels; the reports are used to get the score.
+    let (_control_tx, control_rx) = crossbeam_channel::unbounded::<SearchControl>();
+    let (report_tx, report_rx) = crossbeam_channel::unbounded::<SearchReport>();
+
+    let result = loop {
+        if let Some((result, _)) = selfplay::game_over(&board, mg) {
+            break result;
+        }
+
+        let mut search_params = SearchParams::new();
+        search_params.nodes = nodes;
+        search_params.search_mode = SearchMode::Nodes;
+        search_params.quiet = true;
+        let mut search_info = SearchInfo::new();
+        let mut refs = SearchRefs {
+            board: &mut board,
+            mg,
+            tt,
+            tt_enabled: true,
+            search_params: &mut search_params,
+            search_info: &mut search_info,
+            control_rx: &control_rx,
+            report_tx: &report_tx,
+        };
+        let (best_move, _) = Search::iterative_deepening(&mut refs);
+
+        // Take the score of the last completed depth.
+        let summary: Option<SearchSummary> = report_rx
+            .try_iter()
+            .filter_map(|report| match report {
+                SearchReport::SearchSummary(s) => Some(s),
+                _ => None,
+            })
+            .last();
+
+        if let Some(s) = summary {
+            if is_quiet(&board, mg, best_move) && s.moves_to_mate().is_none() {
+                let score = if board.us() == Sides::WHITE {
+                    s.cp
+                } else {
+                    -s.cp
+                };
+                samples.push(Sample {
+                    fen: board.fen_write(),
+                    score,
+                });
+            }
+        }
+
+        if best_move.get_move() == 0 || !board.make(best_move, mg) {
+            break "1/2-1/2";
+        }
+    };
+
+    let result = match result {
+        "1-0" => "1.0",
+        "0-1" => "0.0",
+        _ => "0.5",
+    };
+    for sample in samples.iter() {
+        writeln!(writer, "{} | {} | {}", sample.fen, sample.score, result)
+            .expect(ErrFatal::WRITE_IO);
+    }
+
+    samples.len()
+}
+
+// Set up the starting position and play random legal moves. If the game
+// ends during the random moves, start over.
+fn random_opening(mg: &MoveGenerator, random: &mut ChaChaRng, plies: usize) -> Board {
+    let mut board = Board::new();
+
+    'opening: loop {
+        board.fen_read(None).expect(ErrFatal::NEW_GAME);
+        for _ in 0..plies {
+            let mut ml = MoveList::new();
+            mg.generate_legal_moves(&board, &mut ml, MoveType::All);
+            if ml.is_empty() {
+                continue 'opening;
+            }
+            let m = ml.get_move(random.gen_range(0..ml.len()));
+            board.make_legal(m);
+        }
+        if selfplay::game_over(&board, mg).is_none() {
+            break;
+        }
+    }
+
+    board
+}
+
+// A position is quiet if the side to move is not in check and the best
+// move doesn't capture anything.
+fn is_quiet(board: &Board, mg: &MoveGenerator, best_move: Move) -> bool {
+    let king = board.king_square(board.us());
+    let in_check = mg.square_attacked(board, board.opponent(), king);
+    let is_capture = best_move.captured() != Pieces::NONE || best_move.en_passant();
+
+    !in_check && !is_capture
+}
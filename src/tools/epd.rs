@@ -0,0 +1,228 @@
+/* =======================================================================
+Rustic is a chess playing engine.
+Copyright (C) 2019-2024, Marcel Vanthoor
+https://rustic-chess.org/
+
+Rustic is written in the Rust programming language. It is an original
+work, not derived from any engine that came before it. However, it does
+use a lot of concepts which are well-known and are in use by most if not
+all classical alpha/beta-based chess engines.
+
+Rustic is free software: you can redistribute it and/or modify it under
+the terms of the GNU General Public License version 3 as published by
+the Free Software Foundation.
+
+Rustic is distributed in the hope that it will be useful, but WITHOUT
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+for more details.
+
+You should have received a copy of the GNU General Public License along
+with this program.  If not, see <http://www.gnu.org/licenses/>.
+======================================================================= */
+
+// epd.rs parses lines in Extended Position Description format. An EPD
+// line starts with the first four fields of a FEN-string, followed by a
+// list of operations. Each operation is an opcode with zero or more
+// operands, terminated by a semicolon, such as:
+//
+// r1b1k2r/ppppnppp/2n2q2/2b5/3NP3/2P1B3/PP3PPP/RN1QKB1R w KQkq - bm Nxc6; id "WAC.005";
+//
+// Some EPD files include the half-move clock and full-move number as in
+// a FEN-string. These are accepted as well, as is the ";D1 20 ;D2 400"
+// format used by perft test suites, where each depth is an opcode.
+//
+// When writing EPD, the move counters are written as the "hmvc" and
+// "fmvn" operations, so no information about the position is lost.
+
+use crate::board::Board;
+
+pub const EPD_BEST_MOVE: &str = "bm";
+pub const EPD_AVOID_MOVE: &str = "am";
+pub const EPD_ID: &str = "id";
+pub const EPD_HALF_MOVE_CLOCK: &str = "hmvc";
+pub const EPD_FULL_MOVE_NUMBER: &str = "fmvn";
+
+const EPD_POSITION_FIELDS: usize = 4;
+const SEMI_COLON: char = ';';
+const QUOTE: char = '"';
+
+pub struct EpdOperation {
+    pub opcode: String,
+    pub operands: Vec<String>,
+}
+
+pub struct Epd {
+    pub fen: String,
+    pub operations: Vec<EpdOperation>,
+}
+
+impl Epd {
+    // Parse a single EPD line. Parsing fails if there are not enough
+    // fields for the position, or if a quoted string is not terminated.
+    // The position itself is not checked: that happens when the FEN is
+    // read into a board.
+    pub fn parse(line: &str) -> Result<Self, ()> {
+        let mut rest = line.trim();
+        let mut fields: Vec<&str> = Vec::new();
+
+        // Take the four position fields.
+        while fields.len() < EPD_POSITION_FIELDS {
+            let (field, remaining) = split_word(rest);
+            if field.is_empty() {
+                return Err(());
+            }
+            fields.push(field);
+            rest = remaining;
+        }
+
+        // Take the move counters, if this is actually a complete FEN.
+        let (hmc, after_hmc) = split_word(rest);
+        let (fmn, after_fmn) = split_word(after_hmc);
+        let has_counters = is_number(hmc) && is_number(fmn);
+        if has_counters {
+            rest = after_fmn;
+        }
+
+        let operations = operations(rest)?;
+        let mut epd = Self {
+            fen: fields.join(" "),
+            operations,
+        };
+
+        // Complete the FEN with the move counters, from either the
+        // position itself or the hmvc and fmvn operations.
+        let counters = if has_counters {
+            Some((String::from(hmc), String::from(fmn)))
+        } else {
+            match (
+                epd.operand(EPD_HALF_MOVE_CLOCK),
+                epd.operand(EPD_FULL_MOVE_NUMBER),
+            ) {
+                (Some(h), Some(f)) => Some((String::from(h), String::from(f))),
+                _ => None,
+            }
+        };
+        if let Some((h, f)) = counters {
+            epd.fen = format!("{} {} {}", epd.fen, h, f);
+        }
+
+        Ok(epd)
+    }
+
+    // Create an EPD for the position on the board. The move counters
+    // become operations; more operations can be added afterward.
+    pub fn from_board(board: &Board) -> Self {
+        let counter = |opcode: &str, value: String| EpdOperation {
+            opcode: String::from(opcode),
+            operands: vec![value],
+        };
+
+        Self {
+            fen: board.fen_write(),
+            operations: vec![
+                counter(EPD_HALF_MOVE_CLOCK, board.halfmove_clock().to_string()),
+                counter(EPD_FULL_MOVE_NUMBER, board.fullmove_number().to_string()),
+            ],
+        }
+    }
+
+    // Write the EPD line: the four position fields of the FEN, followed
+    // by the operations. Operands that would not survive parsing on their
+    // own, such as a name with spaces, are quoted.
+    pub fn as_string(&self) -> String {
+        let mut epd: Vec<String> = self
+            .fen
+            .split_whitespace()
+            .take(EPD_POSITION_FIELDS)
+            .map(String::from)
+            .collect();
+
+        for o in self.operations.iter() {
+            let mut operation = o.opcode.clone();
+            for operand in o.operands.iter() {
+                let needs_quotes = operand.is_empty()
+                    || operand.contains(|c: char| c.is_whitespace() || c == SEMI_COLON);
+                if needs_quotes {
+                    operation = format!("{operation} {QUOTE}{operand}{QUOTE}");
+                } else {
+                    operation = format!("{operation} {operand}");
+                }
+            }
+            epd.push(format!("{operation}{SEMI_COLON}"));
+        }
+
+        epd.join(" ")
+    }
+
+    // Returns the operands of the given opcode, if it is present.
+    pub fn operands(&self, opcode: &str) -> Option<&[String]> {
+        self.operations
+            .iter()
+            .find(|o| o.opcode == opcode)
+            .map(|o| &o.operands[..])
+    }
+
+    // Returns the first operand of the given opcode. This is convenient
+    // for opcodes that only have one, such as "id".
+    pub fn operand(&self, opcode: &str) -> Option<&str> {
+        self.operands(opcode)
+            .and_then(|o| o.first())
+            .map(|o| o.as_str())
+    }
+}
+
+// Split off the first word of the string, and return it together with
+// the remainder.
+fn split_word(s: &str) -> (&str, &str) {
+    let s = s.trim_start();
+    match s.find(char::is_whitespace) {
+        Some(i) => (&s[..i], &s[i..]),
+        None => (s, ""),
+    }
+}
+
+fn is_number(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_digit())
+}
+
+// Parse the operations. Operands are separated by whitespace, unless they
+// are within quotes; a semicolon within quotes doesn't end the operation.
+fn operations(s: &str) -> Result<Vec<EpdOperation>, ()> {
+    let mut operations: Vec<EpdOperation> = Vec::new();
+    let mut words: Vec<String> = Vec::new();
+    let mut word = String::new();
+    let mut in_quotes = false;
+    let mut is_quoted = false;
+
+    for c in s.chars().chain(std::iter::once(SEMI_COLON)) {
+        match c {
+            QUOTE => {
+                in_quotes = !in_quotes;
+                is_quoted = true;
+            }
+            _ if in_quotes => word.push(c),
+            _ if c.is_whitespace() || c == SEMI_COLON => {
+                if !word.is_empty() || is_quoted {
+                    words.push(word.clone());
+                    word.clear();
+                    is_quoted = false;
+                }
+                if c == SEMI_COLON && !words.is_empty() {
+                    operations.push(EpdOperation {
+                        opcode: words.remove(0),
+                        operands: words.clone(),
+                    });
+                    words.clear();
+                }
+            }
+            _ => word.push(c),
+        }
+    }
+
+    if in_quotes {
+        return Err(());
+    }
+
+    Ok(operations)
+}
@@ -0,0 +1,197 @@
+/* =======================================================================
+Rustic is a chess playing engine.
+Copyright (C) 2019-2024, Marcel Vanthoor
+https://rustic-chess.org/
+
+Rustic is written in the Rust programming language. It is an original
+work, not derived from any engine that came before it. However, it does
+use a lot of concepts which are well-known and are in use by most if not
+all classical alpha/beta-based chess engines.
+
+Rustic is free software: you can redistribute it and/or modify it under
+the terms of the GNU General Public License version 3 as published by
+the Free Software Foundation.
+
+Rustic is distributed in the hope that it will be useful, but WITHOUT
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+for more details.
+
+You should have received a copy of the GNU General Public License along
+with this program.  If not, see <http://www.gnu.org/licenses/>.
+======================================================================= */
+
+// gauntlet.rs plays Rustic against one or more external UCI engines, one
+// match per opponent, using the match code in selfplay.rs. The external
+// engines are run through the UCI client. When all matches are done, the
+// results are summarized in a table.
+
+use crate::{
+    board::Board,
+    defs::Sides,
+    misc::parse,
+    movegen::{
+        defs::{Move, MoveList, MoveType},
+        MoveGenerator,
+    },
+    search::defs::{GameTime, SearchSummary, CHECKMATE},
+    tools::{
+        pgn::PgnGame,
+        selfplay::{self, EnginePlayer, MatchOptions, Player, PlayerConfig, Record},
+        uci_client::UciClient,
+    },
+};
+use std::{sync::Arc, time::Duration};
+
+// Time an external engine may spend on a move after its clock has run
+// out, before it is told to stop. The game is lost on time anyway, but
+// this gives the engine a chance to send its move, so it stays in sync.
+const TIME_GRACE: u64 = 500;
+
+const UCI_CHESS960: &str = "UCI_Chess960";
+
+// An external engine, playing through the UCI client.
+pub struct ExternalPlayer {
+    client: UciClient,
+    mg: Arc<MoveGenerator>,
+}
+
+impl ExternalPlayer {
+    pub fn new(client: UciClient, mg: &Arc<MoveGenerator>) -> Self {
+        Self {
+            client,
+            mg: Arc::clone(mg),
+        }
+    }
+
+    // Find the engine's move, given in coordinate notation, among the
+    // legal moves in the position.
+    fn find_move(&self, board: &Board, best_move: &str) -> Option<Move> {
+        let (from, to, promoted) = parse::algebraic_move_to_number(best_move).ok()?;
+        let mut ml = MoveList::new();
+        self.mg.generate_legal_moves(board, &mut ml, MoveType::All);
+
+        (0..ml.len())
+            .map(|i| ml.get_move(i))
+            .find(|m| m.from() == from && m.to() == to && m.promoted() == promoted)
+    }
+}
+
+impl Player for ExternalPlayer {
+    fn name(&self) -> &str {
+        self.client.name()
+    }
+
+    fn new_game(&mut self, chess960: bool) {
+        let mut result = Ok(());
+        if self.client.has_option(UCI_CHESS960) {
+            result = self.client.set_option(UCI_CHESS960, &chess960.to_string());
+        }
+        if let Err(e) = result.and_then(|_| self.client.new_game()) {
+            println!("{}: {}", self.client.name(), e);
+        }
+    }
+
+    fn think(
+        &mut self,
+        game: &PgnGame,
+        board: &Board,
+        game_time: GameTime,
+    ) -> Option<(Move, Option<SearchSummary>)> {
+        let moves: Vec<String> = game.moves.iter().map(|m| m.m.as_string()).collect();
+        let clock = if board.us() == Sides::WHITE {
+            game_time.wtime
+        } else {
+            game_time.btime
+        };
+        let timeout = Duration::from_millis(clock as u64 + TIME_GRACE);
+
+        let answer = self
+            .client
+            .position(game.tag("FEN"), &moves)
+            .and_then(|_| self.client.go(&game_time, timeout));
+        let answer = match answer {
+            Ok(a) => a,
+            Err(e) => {
+                println!("{}: {}", self.client.name(), e);
+                return None;
+            }
+        };
+
+        // Convert the engine's score into a search summary, so it can be
+        // written into the PGN as a comment like Rustic's own.
+        let cp = match (answer.cp, answer.mate) {
+            (_, Some(mate)) if mate > 0 => Some(CHECKMATE - (2 * mate - 1)),
+            (_, Some(mate)) if mate < 0 => Some(-(CHECKMATE + 2 * mate)),
+            (cp, _) => cp,
+        };
+        let summary = match (answer.depth, cp) {
+            (Some(depth), Some(cp)) => Some(SearchSummary {
+                depth,
+                seldepth: 0,
+                time: 0,
+                cp,
+                mate: 0,
+                nodes: 0,
+                nps: 0,
+                hash_full: 0,
+                pv: Vec::new(),
+            }),
+            _ => None,
+        };
+
+        self.find_move(board, &answer.best_move)
+            .map(|m| (m, summary))
+    }
+
+    fn quit(&mut self) {
+        self.client.quit();
+    }
+}
+
+// Play a match between Rustic with the given configuration and each of
+// the external engines in turn.
+pub fn run(
+    options: &mut MatchOptions,
+    config: &PlayerConfig,
+    opponents: Vec<UciClient>,
+    mg: Arc<MoveGenerator>,
+) {
+    let mut results: Vec<(String, Record)> = Vec::new();
+
+    for opponent in opponents {
+        let mut players: [Box<dyn Player>; 2] = [
+            Box::new(EnginePlayer::new(config, &mg)),
+            Box::new(ExternalPlayer::new(opponent, &mg)),
+        ];
+
+        let record = selfplay::play_match(&mut players, options, &mg);
+        results.push((String::from(players[1].name()), record));
+
+        for player in players.iter_mut() {
+            player.quit();
+        }
+    }
+
+    println!("Gauntlet results for {}:", config.name);
+    println!(
+        "{:<30}  {:>5}  {:>5}  {:>5}  {:>6}  {:>6}  Elo",
+        "Opponent", "Games", "Wins", "Draws", "Losses", "Score"
+    );
+    for (name, record) in results.iter() {
+        let elo = match record.elo() {
+            Some((elo, margin)) => format!("{:.1} +/- {:.1}", elo, margin),
+            None => String::from("-"),
+        };
+        println!(
+            "{:<30}  {:>5}  {:>5}  {:>5}  {:>6}  {:>6.3}  {}",
+            name,
+            record.games(),
+            record.wins,
+            record.draws,
+            record.losses,
+            record.score(),
+            elo
+        );
+    }
+}
@@ -0,0 +1,475 @@
+/* =======================================================================
+Rustic is a chess playing engine.
+Copyright (C) 2019-2024, Marcel Vanthoor
+https://rustic-chess.org/
+
+Rustic is written in the Rust programming language. It is an original
+work, not derived from any engine that came before it. However, it does
+use a lot of concepts which are well-known and are in use by most if not
+all classical alpha/beta-based chess engines.
+
+Rustic is free software: you can redistribute it and/or modify it under
+the terms of the GNU General Public License version 3 as published by
+the Free Software Foundation.
+
+Rustic is distributed in the hope that it will be useful, but WITHOUT
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+for more details.
+
+You should have received a copy of the GNU General Public License along
+with this program.  If not, see <http://www.gnu.org/licenses/>.
+======================================================================= */
+
+use crate::{
+    board::Board,
+    engine::defs::{ErrFatal, PerftData, TT},
+    misc::print,
+    movegen::{
+        defs::{Move, MoveGenMode, MoveList, MoveType},
+        MoveGenerator,
+    },
+};
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Instant,
+};
+
+// This function runs perft(), while collecting speed information.
+// It uses iterative deepening, so when running perft(7), it will output
+// the results of perft(1) up to and including perft(7).
+pub fn run(
+    board: Arc<Mutex<Board>>,
+    depth: i8,
+    mg: Arc<MoveGenerator>,
+    tt: Arc<Mutex<TT<PerftData>>>,
+    tt_enabled: bool,
+    mode: MoveGenMode,
+    threads: usize,
+) {
+    let mut total_time: u128 = 0;
+    let mut total_nodes: u64 = 0;
+    let mut hash_full = String::from("");
+
+    // Create a mutex guard for the board, so it can be safely cloned.
+    // Panic if the guard can't be created, because something is wrong with
+    // the main engine thread.
+    let mtx_board = board.lock().expect(ErrFatal::LOCK);
+
+    // Clone the locked board for local use.
+    let mut local_board = mtx_board.clone();
+
+    // The function now has its own local board. Drop the guard. It is not
+    // necessary to keep the lock until perft runs out.
+    std::mem::drop(mtx_board);
+
+    let generator = match mode {
+        MoveGenMode::Pseudo => "pseudo-legal",
+        MoveGenMode::Legal => "legal",
+    };
+    let plural = if threads == 1 { "" } else { "s" };
+    println!(
+        "Benchmarking perft 1-{depth} ({generator} move generator, {threads} thread{plural}):"
+    );
+
+    print::position(&local_board, None);
+
+    // Perform all perfts for depths 1 up to and including "depth"
+    for d in 1..=depth {
+        // Current time
+        let now = Instant::now();
+        let mut leaf_nodes = 0;
+
+        leaf_nodes += perft_parallel(&mut local_board, d, &mg, &tt, tt_enabled, mode, threads);
+
+        // Measure time and speed
+        let elapsed = now.elapsed().as_millis();
+        let leaves_per_second = ((leaf_nodes * 1000) as f64 / elapsed as f64).floor();
+
+        // Add tot totals for final calculation at the very end.
+        total_time += elapsed;
+        total_nodes += leaf_nodes;
+
+        // Request TT usage. (This is provided permille as per UCI
+        // spec, so divide by 10 to get the usage in percents.)
+        if tt_enabled {
+            hash_full = format!(
+                ", hash full: {}%",
+                tt.lock().expect(ErrFatal::LOCK).hash_full() as f64 / 10f64
+            );
+        }
+
+        // Print the results.
+        println!(
+            "Perft {d}: {leaf_nodes} ({elapsed} ms, {leaves_per_second} leaves/sec{hash_full})"
+        );
+    }
+
+    // Final calculation of the entire time taken, and average speed of leaves/second.
+    let final_lnps = ((total_nodes * 1000) as f64 / total_time as f64).floor();
+    println!("Total time spent: {total_time} ms");
+    println!("Execution speed: {final_lnps} leaves/second");
+}
+
+// This is the actual Perft function. It is public, because it is used by
+// the "testsuite" module.
+pub fn perft(
+    board: &mut Board,
+    depth: i8,
+    mg: &MoveGenerator,
+    tt: &Mutex<TT<PerftData>>,
+    tt_enabled: bool,
+    mode: MoveGenMode,
+) -> u64 {
+    let mut leaf_nodes: u64 = 0;
+    let mut move_list: MoveList = MoveList::new();
+
+    // Count each visited leaf node.
+    if depth == 0 {
+        return 1;
+    }
+
+    // See if the current position is in the TT, and if so, get the
+    // number of leaf nodes that were previously calculated for it.
+    let mut leaf_nodes_tt: Option<u64> = None;
+    if tt_enabled {
+        if let Some(data) = tt.lock().expect(ErrFatal::LOCK).probe(board.zobrist_key()) {
+            leaf_nodes_tt = data.get(depth);
+        };
+    }
+
+    // If we found a leaf node count, return it immediately.
+    if let Some(leaf_nodes) = leaf_nodes_tt {
+        return leaf_nodes;
+    }
+
+    match mode {
+        MoveGenMode::Pseudo => {
+            mg.generate_moves(board, &mut move_list, MoveType::All);
+
+            // Run perft for each of the moves.
+            for i in 0..move_list.len() {
+                // Get the move to be executed and counted.
+                let m = move_list.get_move(i);
+
+                // If the move is legal...
+                if board.make(m, mg) {
+                    // Then count the number of leaf nodes it generates...
+                    leaf_nodes += perft(board, depth - 1, mg, tt, tt_enabled, mode);
+
+                    // Then unmake the move so the next one can be counted.
+                    board.unmake();
+                }
+            }
+        }
+        MoveGenMode::Legal => {
+            mg.generate_legal_moves(board, &mut move_list, MoveType::All);
+
+            // All the moves are legal, so one ply before the leaves, the
+            // number of moves is the number of leaf nodes (bulk counting).
+            if depth == 1 {
+                return move_list.len() as u64;
+            }
+
+            for i in 0..move_list.len() {
+                board.make_legal(move_list.get_move(i));
+                leaf_nodes += perft(board, depth - 1, mg, tt, tt_enabled, mode);
+                board.unmake();
+            }
+        }
+    }
+
+    // We have calculated the number of leaf nodes for this position.
+    // Store this in the TT for later use.
+    if tt_enabled {
+        tt.lock()
+            .expect(ErrFatal::LOCK)
+            .insert(board.zobrist_key(), PerftData::create(depth, leaf_nodes))
+    }
+
+    // Return the number of leaf nodes for the given position and depth.
+    leaf_nodes
+}
+
+// This function runs perft using multiple threads. The root moves are
+// divided among the threads, which run perft on their own copy of the
+// board. The threads share the perft TT, so positions calculated by one
+// thread can be used by the others. With only one thread, or if there is
+// nothing to divide, it runs the normal perft function.
+pub fn perft_parallel(
+    board: &mut Board,
+    depth: i8,
+    mg: &MoveGenerator,
+    tt: &Mutex<TT<PerftData>>,
+    tt_enabled: bool,
+    mode: MoveGenMode,
+    threads: usize,
+) -> u64 {
+    if threads <= 1 || depth <= 1 {
+        return perft(board, depth, mg, tt, tt_enabled, mode);
+    }
+
+    divide(board, depth, mg, tt, tt_enabled, mode, threads)
+        .iter()
+        .map(|(_, leaf_nodes)| leaf_nodes)
+        .sum()
+}
+
+// Perft divide: run perft for each of the root moves separately, and
+// return the number of leaf nodes per move. This makes it possible to find
+// the exact move where this engine disagrees with another one, by going
+// down the tree one move at a time. The root moves are handed out to the
+// given number of threads one by one, so a thread that finishes a small
+// subtree early will pick up the next move.
+pub fn divide(
+    board: &mut Board,
+    depth: i8,
+    mg: &MoveGenerator,
+    tt: &Mutex<TT<PerftData>>,
+    tt_enabled: bool,
+    mode: MoveGenMode,
+    threads: usize,
+) -> Vec<(Move, u64)> {
+    let mut move_list = MoveList::new();
+    let mut root_moves: Vec<Move> = Vec::new();
+
+    match mode {
+        MoveGenMode::Pseudo => mg.generate_moves(board, &mut move_list, MoveType::All),
+        MoveGenMode::Legal => mg.generate_legal_moves(board, &mut move_list, MoveType::All),
+    }
+
+    // Pseudo-legal moves must still be checked for legality. After that,
+    // all the root moves can be executed with make_legal().
+    for i in 0..move_list.len() {
+        let m = move_list.get_move(i);
+        match mode {
+            MoveGenMode::Pseudo => {
+                if board.make(m, mg) {
+                    board.unmake();
+                    root_moves.push(m);
+                }
+            }
+            MoveGenMode::Legal => root_moves.push(m),
+        }
+    }
+
+    // Start the threads. Each of them takes the next root move that has not
+    // been counted yet, until there are no more root moves left.
+    let mut leaf_nodes: Vec<u64> = vec![0; root_moves.len()];
+    let next_move = AtomicUsize::new(0);
+    let nr_of_threads = threads.clamp(1, root_moves.len().max(1));
+
+    thread::scope(|scope| {
+        let mut handles = Vec::with_capacity(nr_of_threads);
+
+        for _ in 0..nr_of_threads {
+            let mut local_board = board.clone();
+            let root_moves = &root_moves;
+            let next_move = &next_move;
+
+            handles.push(scope.spawn(move || {
+                let mut counted: Vec<(usize, u64)> = Vec::new();
+
+                loop {
+                    let index = next_move.fetch_add(1, Ordering::Relaxed);
+                    if index >= root_moves.len() {
+                        break;
+                    }
+
+                    local_board.make_legal(root_moves[index]);
+                    let nodes = perft(&mut local_board, depth - 1, mg, tt, tt_enabled, mode);
+                    local_board.unmake();
+                    counted.push((index, nodes));
+                }
+
+                counted
+            }));
+        }
+
+        // Collect the results, in the order of the root moves.
+        for handle in handles {
+            for (index, nodes) in handle.join().expect(ErrFatal::THREAD) {
+                leaf_nodes[index] = nodes;
+            }
+        }
+    });
+
+    root_moves.into_iter().zip(leaf_nodes).collect()
+}
+
+// This function runs both move generators side by side, for depths 1 up to
+// and including "depth", to cross-validate them against each other.
+pub fn run_compare(board: Arc<Mutex<Board>>, depth: i8, mg: Arc<MoveGenerator>) {
+    let mtx_board = board.lock().expect(ErrFatal::LOCK);
+    let mut local_board = mtx_board.clone();
+    std::mem::drop(mtx_board);
+
+    println!("Comparing move generators, perft 1-{depth}:");
+
+    print::position(&local_board, None);
+
+    for d in 1..=depth {
+        let now = Instant::now();
+        let result = compare(&mut local_board, d, &mg);
+        let elapsed = now.elapsed().as_millis();
+
+        match result {
+            Some(leaf_nodes) => println!("Perft {d}: {leaf_nodes} ({elapsed} ms, identical)"),
+            None => {
+                println!("Perft {d}: move generators disagree.");
+                break;
+            }
+        }
+    }
+}
+
+// This function walks the game tree using both move generators at the
+// same time. In every position, the pseudo-legal moves that pass make()
+// must be exactly the same as the moves from the legal move generator. If
+// they are not, the position and the offending moves are printed, and the
+// function returns None. Otherwise, the number of leaf nodes is returned.
+pub fn compare(board: &mut Board, depth: i8, mg: &MoveGenerator) -> Option<u64> {
+    let mut pseudo_legal = MoveList::new();
+    let mut legal = MoveList::new();
+    let mut leaf_nodes: u64 = 0;
+
+    if depth == 0 {
+        return Some(1);
+    }
+
+    mg.generate_moves(board, &mut pseudo_legal, MoveType::All);
+    mg.generate_legal_moves(board, &mut legal, MoveType::All);
+
+    // Filter the pseudo-legal moves down to the legal ones.
+    let mut filtered = MoveList::new();
+    for i in 0..pseudo_legal.len() {
+        let m = pseudo_legal.get_move(i);
+        if board.make(m, mg) {
+            board.unmake();
+            filtered.push(m);
+        }
+    }
+
+    // Find the moves missing from either list.
+    let contains =
+        |list: &MoveList, m: u32| (0..list.len()).any(|i| list.get_move(i).get_move() == m);
+    let missing_legal: Vec<String> = (0..filtered.len())
+        .map(|i| filtered.get_move(i))
+        .filter(|m| !contains(&legal, m.get_move()))
+        .map(|m| m.as_string())
+        .collect();
+    let missing_pseudo: Vec<String> = (0..legal.len())
+        .map(|i| legal.get_move(i))
+        .filter(|m| !contains(&filtered, m.get_move()))
+        .map(|m| m.as_string())
+        .collect();
+
+    if !missing_legal.is_empty() || !missing_pseudo.is_empty() {
+        print::position(board, None);
+        println!("Not generated by legal: {}", missing_legal.join(" "));
+        println!(
+            "Not generated by pseudo-legal: {}",
+            missing_pseudo.join(" ")
+        );
+        return None;
+    }
+
+    for i in 0..legal.len() {
+        board.make_legal(legal.get_move(i));
+        let result = compare(board, depth - 1, mg);
+        board.unmake();
+        leaf_nodes += result?;
+    }
+
+    Some(leaf_nodes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::defs::{FEN_KIWIPETE_POSITION, FEN_START_POSITION};
+
+    // Positions and leaf node counts from the Chess Programming Wiki.
+    const POSITIONS: [(&str, bool, i8, u64); 6] = [
+        (FEN_START_POSITION, false, 3, 8_902),
+        (FEN_KIWIPETE_POSITION, false, 2, 2_039),
+        ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", false, 3, 2_812),
+        (
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            false,
+            2,
+            264,
+        ),
+        (
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            false,
+            2,
+            1_486,
+        ),
+        (
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+            true,
+            2,
+            528,
+        ),
+    ];
+
+    const MODES: [(MoveGenMode, &str); 2] = [
+        (MoveGenMode::Pseudo, MoveGenMode::PSEUDO),
+        (MoveGenMode::Legal, MoveGenMode::LEGAL),
+    ];
+
+    fn board(fen: &str, chess960: bool) -> Board {
+        let mut board = Board::new();
+        board.set_chess960(chess960);
+        assert_eq!(board.fen_read(Some(fen)), Ok(()), "{fen}");
+        board
+    }
+
+    #[test]
+    fn both_move_generators_count_the_same_leaves() {
+        let mg = MoveGenerator::new();
+        let tt = Mutex::new(TT::<PerftData>::new(1));
+
+        for (fen, chess960, depth, leaf_nodes) in POSITIONS {
+            for (mode, name) in MODES {
+                for tt_enabled in [false, true] {
+                    tt.lock().expect(ErrFatal::LOCK).clear();
+                    let mut board = board(fen, chess960);
+                    let before = board.fen_write();
+                    let result = perft(&mut board, depth, &mg, &tt, tt_enabled, mode);
+                    assert_eq!(result, leaf_nodes, "{fen} {name} tt: {tt_enabled}");
+                    assert_eq!(board.fen_write(), before);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn divided_perft_adds_up() {
+        let mg = MoveGenerator::new();
+        let tt = Mutex::new(TT::<PerftData>::new(1));
+        let (fen, chess960, depth, leaf_nodes) = POSITIONS[1];
+
+        for (mode, _) in MODES {
+            let mut board = board(fen, chess960);
+            let moves = divide(&mut board, depth, &mg, &tt, false, mode, 2);
+            assert_eq!(moves.len(), 48);
+            assert_eq!(moves.iter().map(|(_, n)| n).sum::<u64>(), leaf_nodes);
+        }
+    }
+
+    #[test]
+    fn compare_agrees_with_perft() {
+        let mg = MoveGenerator::new();
+
+        for (fen, chess960, depth, leaf_nodes) in POSITIONS {
+            let mut board = board(fen, chess960);
+            assert_eq!(compare(&mut board, depth, &mg), Some(leaf_nodes), "{fen}");
+        }
+    }
+}
@@ -0,0 +1,297 @@
+/* =======================================================================
+Rustic is a chess playing engine.
+Copyright (C) 2019-2024, Marcel Vanthoor
+https://rustic-chess.org/
+
+Rustic is written in the Rust programming language. It is an original
+work, not derived from any engine that came before it. However, it does
+use a lot of concepts which are well-known and are in use by most if not
+all classical alpha/beta-based chess engines.
+
+Rustic is free software: you can redistribute it and/or modify it under
+the terms of the GNU General Public License version 3 as published by
+the Free Software Foundation.
+
+Rustic is distributed in the hope that it will be useful, but WITHOUT
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+for more details.
+
+You should have received a copy of the GNU General Public License along
+with this program.  If not, see <http://www.gnu.org/licenses/>.
+======================================================================= */
+
+// perft_suite.rs runs a perft test suite. Each test is an EPD line with
+// the expected leaf node counts per depth as operations:
+//
+// rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 ;D1 20 ;D2 400
+//
+// The tests can come from a file, or from the built-in suite that is
+// compiled in with the "extra" feature.
+
+use crate::{
+    board::Board,
+    engine::defs::{PerftData, TT},
+    misc::print,
+    movegen::{defs::MoveGenMode, MoveGenerator},
+    tools::{epd::Epd, perft},
+};
+use std::{
+    ops::RangeInclusive,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+const ERR_NONE: usize = 0;
+const ERR_FEN: usize = 1;
+const ERR_DEPTH: usize = 2;
+const ERR_EXPECT: usize = 3;
+const ERR_FAIL: usize = 4;
+const ERR_WRITE: usize = 5;
+
+const TEST_RESULTS: [&str; 6] = [
+    "No errors. Test completed successfully.",
+    "Errors in parsing the FEN-string.",
+    "Errors parsing depth from test data.",
+    "Errors parsing expected leaf nodes from test data.",
+    "Failure: Found leaf nodes not equal to expected value.",
+    "Failure: Position written as FEN or EPD does not read back the same.",
+];
+
+// Short versions of the results above, for the summary table.
+const TABLE_RESULTS: [&str; 6] = ["OK", "FEN", "Depth", "Expect", "Fail", "Write"];
+
+pub struct SuiteOptions {
+    pub max_depth: i8,                      // Skip depths above this; 0 runs all.
+    pub select: Vec<RangeInclusive<usize>>, // Test numbers to run; empty runs all.
+    pub keep_going: bool,                   // Continue with the next test on failure.
+    pub compare: bool,                      // Cross-validate both move generators.
+}
+
+impl SuiteOptions {
+    pub fn new() -> Self {
+        Self {
+            max_depth: 0,
+            select: Vec::new(),
+            keep_going: false,
+            compare: false,
+        }
+    }
+
+    // Parse a selection of test numbers, such as "1-10,15,20-25".
+    pub fn parse_select(select: &str) -> Result<Vec<RangeInclusive<usize>>, ()> {
+        let mut ranges = Vec::new();
+        for part in select.split(',').map(|p| p.trim()) {
+            let (first, last) = part.split_once('-').unwrap_or((part, part));
+            let first = first.trim().parse::<usize>().map_err(|_| ())?;
+            let last = last.trim().parse::<usize>().map_err(|_| ())?;
+            ranges.push(first..=last);
+        }
+        Ok(ranges)
+    }
+
+    fn is_selected(&self, test_nr: usize) -> bool {
+        self.select.is_empty() || self.select.iter().any(|r| r.contains(&test_nr))
+    }
+}
+
+// The outcome of a single test, for the summary table.
+struct TestResult {
+    nr: usize,
+    result: usize,
+    depth: i8,
+    elapsed: u128,
+    fen: String,
+}
+
+// Read a perft suite from a file. Lines that are empty or start with '#'
+// are skipped.
+pub fn read_file(path: &str) -> std::io::Result<Vec<String>> {
+    let text = std::fs::read_to_string(path)?;
+    let tests = text
+        .lines()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(String::from)
+        .collect();
+
+    Ok(tests)
+}
+
+// Run the selected tests of the suite. Returns true if all of them passed.
+pub fn run(
+    tests: &[&str],
+    options: &SuiteOptions,
+    tt: Arc<Mutex<TT<PerftData>>>,
+    tt_enabled: bool,
+    mode: MoveGenMode,
+    threads: usize,
+) -> bool {
+    let number_of_tests = tests.len();
+    let move_generator = MoveGenerator::new();
+    let mut board: Board = Board::new();
+    let mut results: Vec<TestResult> = Vec::new();
+    let mut result: usize = ERR_NONE;
+
+    // Run all the tests.
+    let mut test_nr = 0;
+    while (test_nr < number_of_tests) && (result == ERR_NONE || options.keep_going) {
+        if !options.is_selected(test_nr + 1) {
+            test_nr += 1;
+            continue;
+        }
+        result = ERR_NONE;
+
+        // Parse the test's data. The perft results per depth are stored
+        // as operations, such as "D1 20".
+        let epd = Epd::parse(tests[test_nr]);
+        let fen = match &epd {
+            Ok(e) => &e.fen[..],
+            Err(_) => tests[test_nr],
+        };
+
+        // Set up the position according to the provided FEN-string.
+        let setup_result = board.fen_read(Some(fen));
+        println!("Test {} from {}", test_nr + 1, number_of_tests);
+        println!("FEN: {fen}");
+
+        // If setup ok, then print position. Else, print error and continue to the next test.
+        let operations = match (setup_result, &epd) {
+            (Ok(()), Ok(e)) => {
+                print::position(&board, None);
+                &e.operations[..]
+            }
+            _ => {
+                result = ERR_FEN;
+                &[]
+            }
+        };
+
+        // Writing the position and reading it back must give the same
+        // position. If not, there's no use in running perft on it.
+        if result == ERR_NONE && !round_trip(&board) {
+            result = ERR_WRITE;
+        }
+
+        // Run all the parts of a test.
+        let now = Instant::now();
+        let mut last_depth = 0;
+        let mut index: usize = 0;
+        while index < operations.len() && (result == 0) {
+            // Each operation such as "D1 20" contains the depth in the
+            // opcode, and the expected leaf nodes in the operand.
+            let operation = &operations[index];
+            let depth = operation
+                .opcode
+                .get(1..)
+                .unwrap_or("")
+                .parse::<u8>()
+                .unwrap_or(0) as i8;
+            let expected_ln = operation
+                .operands
+                .first()
+                .and_then(|o| o.parse::<u64>().ok())
+                .unwrap_or(0);
+            index += 1;
+
+            // Abort if depth or expected leaf node parsing fails.
+            result = if depth == 0 { ERR_DEPTH } else { result };
+            result = if expected_ln == 0 { ERR_EXPECT } else { result };
+
+            // Skip depths above the maximum.
+            if options.max_depth > 0 && depth > options.max_depth {
+                continue;
+            }
+
+            if result == 0 {
+                print!("Expect for depth {depth}: {expected_ln}");
+
+                // This is the actual perft run for this test and depth.
+                // When comparing, both move generators walk the tree at
+                // the same time. If they disagree, the difference is
+                // printed, and no leaf nodes are counted.
+                let now = Instant::now();
+                let found_ln = if options.compare {
+                    perft::compare(&mut board, depth, &move_generator).unwrap_or(0)
+                } else {
+                    perft::perft_parallel(
+                        &mut board,
+                        depth,
+                        &move_generator,
+                        &tt,
+                        tt_enabled,
+                        mode,
+                        threads,
+                    )
+                };
+                let elapsed = now.elapsed().as_millis();
+                let moves_per_second = ((found_ln * 1000) as f64 / elapsed as f64).floor();
+                let is_ok = expected_ln == found_ln;
+
+                // Print the results
+                print!(" - Found: {found_ln}");
+                print!(" - Result: {}", if is_ok { "OK" } else { "Fail" });
+                println!(" ({elapsed} ms, {moves_per_second} leaves/sec)");
+
+                result = if !is_ok { ERR_FAIL } else { result };
+                last_depth = depth;
+            }
+        }
+
+        println!("Test {}: {}\n", test_nr + 1, TEST_RESULTS[result]);
+        results.push(TestResult {
+            nr: test_nr + 1,
+            result,
+            depth: last_depth,
+            elapsed: now.elapsed().as_millis(),
+            fen: String::from(fen),
+        });
+        test_nr += 1;
+    }
+
+    summary(&results)
+}
+
+// Write the position as FEN and as EPD, and read both back in. Each must
+// result in exactly the same position, including the castling rooks (for
+// Chess960) and the move counters.
+fn round_trip(board: &Board) -> bool {
+    let fen = board.fen_write();
+    let epd = Epd::from_board(board).as_string();
+
+    let from_fen = Board::from_fen(&fen).ok();
+    let from_epd = Epd::parse(&epd)
+        .ok()
+        .and_then(|e| Board::from_fen(&e.fen).ok());
+
+    [from_fen, from_epd].iter().all(|b| match b {
+        Some(b) => {
+            b.fen_write() == fen
+                && b.zobrist_key() == board.zobrist_key()
+                && b.castling_rooks() == board.castling_rooks()
+                && b.halfmove_clock() == board.halfmove_clock()
+                && b.fullmove_number() == board.fullmove_number()
+        }
+        None => false,
+    })
+}
+
+// Print a table with the result of each test that was run, and return
+// true if all of them passed.
+fn summary(results: &[TestResult]) -> bool {
+    let passed = results.iter().filter(|r| r.result == ERR_NONE).count();
+
+    println!(
+        "{:>5}  {:<6}  {:>5}  {:>10}  FEN",
+        "Test", "Result", "Depth", "Time (ms)"
+    );
+    for r in results.iter() {
+        println!(
+            "{:>5}  {:<6}  {:>5}  {:>10}  {}",
+            r.nr, TABLE_RESULTS[r.result], r.depth, r.elapsed, r.fen
+        );
+    }
+    println!("\nPassed: {}, failed: {}", passed, results.len() - passed);
+
+    passed == results.len()
+}
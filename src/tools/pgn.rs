@@ -0,0 +1,571 @@
+/* =======================================================================
+Rustic is a chess playing engine.
+Copyright (C) 2019-2024, Marcel Vanthoor
+https://rustic-chess.org/
+
+Rustic is written in the Rust programming language. It is an original
+work, not derived from any engine that came before it. However, it does
+use a lot of concepts which are well-known and are in use by most if not
+all classical alpha/beta-based chess engines.
+
+Rustic is free software: you can redistribute it and/or modify it under
+the terms of the GNU General Public License version 3 as published by
+the Free Software Foundation.
+
+Rustic is distributed in the hope that it will be useful, but WITHOUT
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+for more details.
+
+You should have received a copy of the GNU General Public License along
+with this program.  If not, see <http://www.gnu.org/licenses/>.
+======================================================================= */
+
+// pgn.rs reads and writes games in Portable Game Notation. While reading,
+// each game is replayed on a board, so every SAN move in the movetext is
+// converted into the engine's own move format and checked for legality.
+// Comments and NAGs are kept with the move they follow. A variation is an
+// alternative for the move it follows: it is replayed from the position
+// before that move, and stored with it. Variations can be nested.
+
+use crate::{
+    board::Board,
+    defs::{Sides, FEN_START_POSITION},
+    movegen::{defs::Move, MoveGenerator},
+    search::defs::SearchSummary,
+    tools::san,
+};
+use std::fmt::{self, Display};
+
+// The tags every PGN game should have, in the order they must be written.
+// The value is the one used when the game doesn't have the tag.
+const SEVEN_TAG_ROSTER: [(&str, &str); 7] = [
+    ("Event", "?"),
+    ("Site", "?"),
+    ("Date", "????.??.??"),
+    ("Round", "?"),
+    ("White", "?"),
+    ("Black", "?"),
+    ("Result", "*"),
+];
+
+// Move suffix annotations, in the order of their NAG numbers $1 to $6.
+const SUFFIX_NAGS: [&str; 6] = ["!", "?", "!!", "??", "!?", "?!"];
+
+const RESULTS: [&str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+const MAX_LINE_LENGTH: usize = 80;
+
+pub enum PgnError {
+    Io(String),
+    Fen(String),
+    IllegalMove(usize, String),
+}
+
+impl Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PgnError::Io(e) => write!(f, "Reading PGN failed: {}", e),
+            PgnError::Fen(fen) => write!(f, "Invalid FEN in PGN: {}", fen),
+            PgnError::IllegalMove(game, m) => write!(f, "Game {}: illegal move: {}", game, m),
+        }
+    }
+}
+
+pub type PgnResult<T> = Result<T, PgnError>;
+
+#[derive(Clone)]
+pub struct PgnMove {
+    pub m: Move,
+    pub nags: Vec<u8>,
+    pub comment: Option<String>,
+    pub variations: Vec<Vec<PgnMove>>, // Alternatives for this move.
+}
+
+impl PgnMove {
+    pub fn new(m: Move) -> Self {
+        Self {
+            m,
+            nags: Vec::new(),
+            comment: None,
+            variations: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub moves: Vec<PgnMove>,
+    pub result: String,
+}
+
+impl PgnGame {
+    pub fn new() -> Self {
+        Self {
+            tags: Vec::new(),
+            moves: Vec::new(),
+            result: String::from("*"),
+        }
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    // Set a tag, replacing its value if the game already has it.
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(n, _)| n == name) {
+            Some(tag) => tag.1 = String::from(value),
+            None => self.tags.push((String::from(name), String::from(value))),
+        }
+    }
+
+    // A Chess960 game is marked by its Variant tag.
+    pub fn is_chess960(&self) -> bool {
+        let variant = self.tag("Variant").unwrap_or("").to_lowercase();
+        variant.contains("960") || variant.contains("fischer")
+    }
+
+    // Set up the board with the position the game starts from. This is
+    // the position in the FEN tag if there is one, or the starting
+    // position otherwise. Chess960 is switched on or off first, according
+    // to the Variant tag, so the castling rights in the FEN and the
+    // castling moves are read the right way. The positions during the game
+    // can be visited by playing the moves one by one using make_legal().
+    pub fn setup(&self, board: &mut Board) -> PgnResult<()> {
+        board.set_chess960(self.is_chess960());
+
+        let fen = self.tag("FEN").unwrap_or(FEN_START_POSITION);
+        board
+            .fen_read(Some(fen))
+            .map_err(|_| PgnError::Fen(String::from(fen)))
+    }
+}
+
+// Read a PGN file from disk. See read() below.
+pub fn read_file(path: &str, board: &mut Board, mg: &MoveGenerator) -> PgnResult<Vec<PgnGame>> {
+    let text = std::fs::read_to_string(path).map_err(|e| PgnError::Io(e.to_string()))?;
+    read(&text, board, mg)
+}
+
+// Read all the games in the given text. The board is used to replay each
+// game; when this function returns, it holds the final position of the
+// last game that was read.
+pub fn read(text: &str, board: &mut Board, mg: &MoveGenerator) -> PgnResult<Vec<PgnGame>> {
+    let mut games: Vec<PgnGame> = Vec::new();
+    let mut game = PgnGame::new();
+    let mut variations: Vec<Vec<PgnMove>> = Vec::new();
+    let mut in_movetext = false;
+    let mut chars = text.chars().peekable();
+    let mut line_start = true;
+
+    while let Some(c) = chars.next() {
+        let at_line_start = line_start;
+        line_start = c == '\n';
+
+        match c {
+            // A percent sign at the start of a line escapes the entire line.
+            '%' if at_line_start => {
+                skip_until(&mut chars, '\n');
+                line_start = true;
+            }
+
+            // A tag pair. If it comes after movetext without a result, the
+            // previous game wasn't terminated properly; finish it anyway.
+            '[' => {
+                if in_movetext {
+                    end_variations(&mut game, &mut variations, board, 0);
+                    games.push(game);
+                    game = PgnGame::new();
+                    in_movetext = false;
+                }
+                let (name, value) = tag_pair(&mut chars);
+                game.tags.push((name, value));
+            }
+
+            // Comments belong to the move they follow. A comment before the
+            // first move is dropped.
+            '{' => {
+                let comment: String = take_until(&mut chars, '}');
+                if let Some(last) = current_line(&mut game, &mut variations).last_mut() {
+                    last.comment = Some(String::from(comment.trim()));
+                }
+            }
+            ';' => {
+                skip_until(&mut chars, '\n');
+                line_start = true;
+            }
+
+            // A variation replaces the last move, so that move is taken
+            // back before the variation is replayed. A variation before the
+            // first move has nothing to replace, so it is skipped.
+            '(' => {
+                if current_line(&mut game, &mut variations).is_empty() {
+                    skip_variation(&mut chars);
+                } else {
+                    board.unmake();
+                    variations.push(Vec::new());
+                }
+            }
+            ')' => {
+                let depth = variations.len().saturating_sub(1);
+                end_variations(&mut game, &mut variations, board, depth);
+            }
+
+            // Numeric Annotation Glyph.
+            '$' => {
+                let nag = take_while(&mut chars, |c| c.is_ascii_digit());
+                let line = current_line(&mut game, &mut variations);
+                if let (Some(last), Ok(n)) = (line.last_mut(), nag.parse::<u8>()) {
+                    last.nags.push(n);
+                }
+            }
+
+            _ if c.is_whitespace() => (),
+
+            // Everything else is a symbol: a move number, a result, or a
+            // move in SAN.
+            _ => {
+                let rest = take_while(&mut chars, is_symbol_char);
+                let symbol = format!("{}{}", c, rest);
+
+                // The first move of a game sets up the starting position.
+                if !in_movetext {
+                    game.setup(board)?;
+                    in_movetext = true;
+                }
+
+                // A result ends the game. A variation that wasn't closed
+                // ends with it.
+                if RESULTS.contains(&symbol.as_str()) {
+                    end_variations(&mut game, &mut variations, board, 0);
+                    game.result = symbol;
+                    games.push(game);
+                    game = PgnGame::new();
+                    in_movetext = false;
+                    continue;
+                }
+
+                // A move number can be glued to the move, as in "1.e4".
+                let san_move = strip_move_number(&symbol);
+                if !san_move.is_empty() {
+                    let nr = games.len() + 1;
+                    let m = san::san_to_move(board, mg, san_move)
+                        .map_err(|_| PgnError::IllegalMove(nr, String::from(san_move)))?;
+                    board.make_legal(m);
+
+                    // Annotations such as "!?" are stored as their NAG.
+                    let mut pgn_move = PgnMove::new(m);
+                    let suffix = san_move.trim_start_matches(|c| c != '!' && c != '?');
+                    if let Some(nag) = SUFFIX_NAGS.iter().position(|&s| s == suffix) {
+                        pgn_move.nags.push(nag as u8 + 1);
+                    }
+                    current_line(&mut game, &mut variations).push(pgn_move);
+                }
+            }
+        }
+    }
+
+    // Keep the last game, even if it doesn't end with a result.
+    end_variations(&mut game, &mut variations, board, 0);
+    if in_movetext || !game.tags.is_empty() {
+        games.push(game);
+    }
+
+    Ok(games)
+}
+
+// Write a game as PGN. The seven tag roster comes first, followed by any
+// other tags the game has, and then the movetext. The board is used to
+// replay the game, so the moves can be written in SAN.
+pub fn write(game: &PgnGame, board: &mut Board, mg: &MoveGenerator) -> PgnResult<String> {
+    let mut pgn = String::new();
+
+    for (name, default) in SEVEN_TAG_ROSTER {
+        let value = match name {
+            "Result" => game.result.as_str(),
+            _ => game.tag(name).unwrap_or(default),
+        };
+        pgn.push_str(&format!("[{} \"{}\"]\n", name, escape(value)));
+    }
+    for (name, value) in game.tags.iter() {
+        if !SEVEN_TAG_ROSTER.iter().any(|(n, _)| n == name) {
+            pgn.push_str(&format!("[{} \"{}\"]\n", name, escape(value)));
+        }
+    }
+    pgn.push('\n');
+
+    // Collect the movetext as separate tokens, so the lines can be
+    // wrapped between them. A move number stays with its move.
+    game.setup(board)?;
+    let mut tokens: Vec<String> = Vec::new();
+    write_line(&game.moves, board, mg, &mut tokens);
+    tokens.push(game.result.clone());
+
+    let mut line = String::new();
+    for token in tokens {
+        if !line.is_empty() && line.len() + 1 + token.len() > MAX_LINE_LENGTH {
+            pgn.push_str(&line);
+            pgn.push('\n');
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&token);
+    }
+    pgn.push_str(&line);
+    pgn.push_str("\n\n");
+
+    Ok(pgn)
+}
+
+// Write the moves of a line as tokens, starting from the position on the
+// board. Each move's variations are written right after it, and replayed
+// from the position before the move. The board ends up at the position
+// after the last move of the line.
+fn write_line(line: &[PgnMove], board: &mut Board, mg: &MoveGenerator, tokens: &mut Vec<String>) {
+    let mut needs_number = true;
+    for pgn_move in line.iter() {
+        let nr = board.fullmove_number();
+        let san_move = san::move_to_san(board, mg, pgn_move.m);
+        if board.us() == Sides::WHITE {
+            tokens.push(format!("{}. {}", nr, san_move));
+        } else if needs_number {
+            tokens.push(format!("{}... {}", nr, san_move));
+        } else {
+            tokens.push(san_move);
+        }
+
+        for nag in pgn_move.nags.iter() {
+            tokens.push(format!("${}", nag));
+        }
+
+        // After a comment or a variation, black's move needs its own move
+        // number.
+        needs_number = false;
+        if let Some(comment) = &pgn_move.comment {
+            tokens.push(format!("{{{}}}", comment.replace('}', ")")));
+            needs_number = true;
+        }
+
+        // The parentheses are attached to the first and last token of the
+        // variation. The variation's moves are taken back afterwards.
+        for variation in pgn_move.variations.iter().filter(|v| !v.is_empty()) {
+            let first = tokens.len();
+            write_line(variation, board, mg, tokens);
+            tokens[first].insert(0, '(');
+            if let Some(last) = tokens.last_mut() {
+                last.push(')');
+            }
+            for _ in variation.iter() {
+                board.unmake();
+            }
+            needs_number = true;
+        }
+
+        board.make_legal(pgn_move.m);
+    }
+}
+
+// Create an "{eval/depth}" comment from a search summary. The evaluation
+// is in pawns from the point of view of the side that made the move, or
+// the number of moves to mate.
+pub fn eval_comment(summary: &SearchSummary) -> String {
+    let eval = match summary.moves_to_mate() {
+        Some(moves) if moves < 0 => format!("-M{}", -moves),
+        Some(moves) => format!("+M{}", moves),
+        None => format!("{:+.2}", summary.cp as f32 / 100.0),
+    };
+
+    format!("{}/{}", eval, summary.depth)
+}
+
+fn is_symbol_char(c: char) -> bool {
+    !c.is_whitespace() && !"{}()[];$".contains(c)
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn take_while<I, F>(chars: &mut std::iter::Peekable<I>, f: F) -> String
+where
+    I: Iterator<Item = char>,
+    F: Fn(char) -> bool,
+{
+    let mut s = String::new();
+    while let Some(&c) = chars.peek() {
+        if !f(c) {
+            break;
+        }
+        s.push(c);
+        chars.next();
+    }
+    s
+}
+
+// Take characters up to the end character. The end character itself is
+// consumed, but not returned.
+fn take_until<I: Iterator<Item = char>>(chars: &mut I, end: char) -> String {
+    chars.take_while(|&c| c != end).collect()
+}
+
+fn skip_until<I: Iterator<Item = char>>(chars: &mut I, end: char) {
+    for c in chars.by_ref() {
+        if c == end {
+            break;
+        }
+    }
+}
+
+// Remove a move number such as "12." or "12..." from the start of a
+// symbol, leaving the move that may be glued to it. Only digits followed
+// by dots are a move number; castling written with zeros ("0-0") must be
+// left alone. A symbol that is only a move number becomes empty.
+fn strip_move_number(symbol: &str) -> &str {
+    let rest = symbol.trim_start_matches(|c: char| c.is_ascii_digit());
+    if rest.is_empty() || rest.starts_with('.') {
+        rest.trim_start_matches('.')
+    } else {
+        symbol
+    }
+}
+
+// The line that moves are added to while reading: the innermost open
+// variation, or the main line of the game if there is none.
+fn current_line<'a>(
+    game: &'a mut PgnGame,
+    variations: &'a mut [Vec<PgnMove>],
+) -> &'a mut Vec<PgnMove> {
+    match variations.last_mut() {
+        Some(variation) => variation,
+        None => &mut game.moves,
+    }
+}
+
+// Close open variations until "depth" of them are left. The moves of each
+// variation are taken back, and the move it replaces is played again. The
+// variation is then stored with that move.
+fn end_variations(
+    game: &mut PgnGame,
+    variations: &mut Vec<Vec<PgnMove>>,
+    board: &mut Board,
+    depth: usize,
+) {
+    while variations.len() > depth {
+        let variation = variations.pop().unwrap_or_default();
+        for _ in variation.iter() {
+            board.unmake();
+        }
+
+        if let Some(parent) = current_line(game, variations).last_mut() {
+            board.make_legal(parent.m);
+            if !variation.is_empty() {
+                parent.variations.push(variation);
+            }
+        }
+    }
+}
+
+// Skip a variation, including any variations nested inside of it. Braces
+// are skipped separately, because a comment may contain parentheses.
+fn skip_variation<I: Iterator<Item = char>>(chars: &mut I) {
+    let mut depth = 1;
+    while depth > 0 {
+        match chars.next() {
+            Some('(') => depth += 1,
+            Some(')') => depth -= 1,
+            Some('{') => skip_until(chars, '}'),
+            Some(_) => (),
+            None => break,
+        }
+    }
+}
+
+// Parse a tag pair such as [Event "Casual game"]. The opening bracket has
+// already been consumed.
+fn tag_pair<I: Iterator<Item = char>>(chars: &mut I) -> (String, String) {
+    let name = take_until(chars, '"');
+    let mut value = String::new();
+    let mut escaped = false;
+
+    for c in chars.by_ref() {
+        match c {
+            '\\' if !escaped => escaped = true,
+            '"' if !escaped => break,
+            _ => {
+                value.push(c);
+                escaped = false;
+            }
+        }
+    }
+    skip_until(chars, ']');
+
+    (String::from(name.trim()), value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GAME: &str = "[Event \"Test\"]\n\n\
+        1. e4 e5 (1... c5 2. Nf3 (2. c3) d6) 2. Nf3 {main} Nc6 *\n";
+    const MOVETEXT: &str = "1. e4 e5 (1... c5 2. Nf3 (2. c3) 2... d6) 2. Nf3 {main} 2... Nc6 *";
+    const FINAL_FEN: &str = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+
+    fn read_game(text: &str, board: &mut Board, mg: &MoveGenerator) -> PgnGame {
+        match read(text, board, mg) {
+            Ok(mut games) if games.len() == 1 => games.remove(0),
+            Ok(games) => panic!("expected one game, read {}", games.len()),
+            Err(e) => panic!("{e}"),
+        }
+    }
+
+    #[test]
+    fn variations_are_stored_with_the_move_they_replace() {
+        let mg = MoveGenerator::new();
+        let mut board = Board::new();
+        let game = read_game(GAME, &mut board, &mg);
+
+        assert_eq!(game.moves.len(), 4);
+        assert_eq!(game.moves[1].variations.len(), 1);
+        let variation = &game.moves[1].variations[0];
+        assert_eq!(variation.len(), 3);
+        assert_eq!(variation[1].variations.len(), 1);
+        assert_eq!(variation[1].variations[0].len(), 1);
+        assert_eq!(game.moves[2].comment.as_deref(), Some("main"));
+
+        // The variations are taken back: the board is at the end of the
+        // main line.
+        assert_eq!(board.fen_write(), FINAL_FEN);
+    }
+
+    #[test]
+    fn variations_are_written_back() {
+        let mg = MoveGenerator::new();
+        let mut board = Board::new();
+        let game = read_game(GAME, &mut board, &mg);
+
+        let text = write(&game, &mut board, &mg).unwrap_or_else(|e| panic!("{e}"));
+        assert!(text.contains("[Event \"Test\"]"));
+        assert!(text.contains(MOVETEXT), "{text}");
+        assert_eq!(board.fen_write(), FINAL_FEN);
+
+        // Reading the written game gives the same game again.
+        let again = read_game(&text, &mut board, &mg);
+        let text_again = write(&again, &mut board, &mg).unwrap_or_else(|e| panic!("{e}"));
+        assert_eq!(text, text_again);
+    }
+
+    #[test]
+    fn castling_with_zeros_is_not_a_move_number() {
+        assert_eq!(strip_move_number("12.Nf3"), "Nf3");
+        assert_eq!(strip_move_number("12..."), "");
+        assert_eq!(strip_move_number("0-0"), "0-0");
+        assert_eq!(strip_move_number("O-O-O"), "O-O-O");
+    }
+}
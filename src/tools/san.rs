@@ -0,0 +1,327 @@
+/* =======================================================================
+Rustic is a chess playing engine.
+Copyright (C) 2019-2024, Marcel Vanthoor
+https://rustic-chess.org/
+
+Rustic is written in the Rust programming language. It is an original
+work, not derived from any engine that came before it. However, it does
+use a lot of concepts which are well-known and are in use by most if not
+all classical alpha/beta-based chess engines.
+
+Rustic is free software: you can redistribute it and/or modify it under
+the terms of the GNU General Public License version 3 as published by
+the Free Software Foundation.
+
+Rustic is distributed in the hope that it will be useful, but WITHOUT
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+for more details.
+
+You should have received a copy of the GNU General Public License along
+with this program.  If not, see <http://www.gnu.org/licenses/>.
+======================================================================= */
+
+// san.rs converts moves from and to Standard Algebraic Notation, such as
+// "Nbd7", "exd5", "e8=Q+" or "O-O". Unlike coordinate notation, SAN can't
+// be converted without knowing the position, because it only contains
+// the information needed to tell the legal moves apart.
+
+use crate::{
+    board::{
+        defs::{Pieces, Wing, PIECE_CHAR_CAPS, SQUARE_NAME},
+        Board,
+    },
+    defs::{Piece, Square},
+    misc::parse::{self, MoveParseError},
+    movegen::{
+        defs::{Move, MoveList, MoveType},
+        MoveGenerator,
+    },
+};
+
+const CASTLE_KINGSIDE: &str = "O-O";
+const CASTLE_QUEENSIDE: &str = "O-O-O";
+
+// Convert a legal move into SAN. The move is played on the board to find
+// out if it gives check or mate, and then taken back again, so the board
+// is unchanged when this function returns.
+pub fn move_to_san(board: &mut Board, mg: &MoveGenerator, m: Move) -> String {
+    let mut san = String::new();
+    let piece = m.piece();
+    let is_capture = m.captured() != Pieces::NONE || m.en_passant();
+
+    if m.castling() {
+        san.push_str(match Board::castling_wing(m) {
+            Wing::KINGSIDE => CASTLE_KINGSIDE,
+            _ => CASTLE_QUEENSIDE,
+        });
+    } else if piece == Pieces::PAWN {
+        // A pawn capture is written with the file the pawn came from.
+        if is_capture {
+            san.push_str(&SQUARE_NAME[m.from()][0..1]);
+            san.push('x');
+        }
+        san.push_str(SQUARE_NAME[m.to()]);
+        if m.promoted() != Pieces::NONE {
+            san.push('=');
+            san.push_str(PIECE_CHAR_CAPS[m.promoted()]);
+        }
+    } else {
+        san.push_str(PIECE_CHAR_CAPS[piece]);
+        san.push_str(&disambiguation(board, mg, m));
+        if is_capture {
+            san.push('x');
+        }
+        san.push_str(SQUARE_NAME[m.to()]);
+    }
+
+    // Play the move to see if the opponent is in check. If so, and the
+    // opponent has no legal moves left, it's mate.
+    board.make_legal(m);
+    let us = board.us();
+    if mg.square_attacked(board, us ^ 1, board.king_square(us)) {
+        let mut ml = MoveList::new();
+        mg.generate_legal_moves(board, &mut ml, MoveType::All);
+        san.push(if ml.is_empty() { '#' } else { '+' });
+    }
+    board.unmake();
+
+    san
+}
+
+// Find the legal move in this position that matches the given SAN string.
+// The parser is tolerant: the capture sign and the check and mate suffixes
+// are optional, the promotion may be written with or without '=', and
+// castling may be written with either the letter O or the digit zero. If
+// the string doesn't match exactly one legal move, the error tells if it
+// matched none or more than one.
+pub fn san_to_move(board: &Board, mg: &MoveGenerator, san: &str) -> Result<Move, MoveParseError> {
+    let mut ml = MoveList::new();
+    mg.generate_legal_moves(board, &mut ml, MoveType::All);
+    let moves = (0..ml.len()).map(|i| ml.get_move(i));
+
+    // Throw away everything that doesn't help to identify the move.
+    let cleaned: String = san
+        .trim()
+        .trim_end_matches(['+', '#', '!', '?'])
+        .chars()
+        .filter(|&c| c != 'x' && c != ':')
+        .collect();
+
+    // Castling is recognized on its own, because it has no destination
+    // square in SAN.
+    let castling = cleaned.replace('0', "O").to_ascii_uppercase();
+    if castling == CASTLE_KINGSIDE || castling == CASTLE_QUEENSIDE {
+        let wing = if castling == CASTLE_KINGSIDE {
+            Wing::KINGSIDE
+        } else {
+            Wing::QUEENSIDE
+        };
+        let found: Vec<Move> = moves
+            .filter(|m| m.castling() && Board::castling_wing(*m) == wing)
+            .collect();
+        return unique(&found);
+    }
+
+    let mut chars: Vec<char> = cleaned.chars().collect();
+
+    // A leading capital letter is the piece. Without it, it's a pawn move.
+    let piece = match chars.first() {
+        Some('K') => Pieces::KING,
+        Some('Q') => Pieces::QUEEN,
+        Some('R') => Pieces::ROOK,
+        Some('B') => Pieces::BISHOP,
+        Some('N') => Pieces::KNIGHT,
+        _ => Pieces::PAWN,
+    };
+    if piece != Pieces::PAWN {
+        chars.remove(0);
+    }
+
+    // A promotion piece is at the end, possibly preceded by '='. A square
+    // always ends with a digit, so a letter at the end can't be part of it.
+    let mut promoted = Pieces::NONE;
+    if let Some(&last) = chars.last() {
+        if let Some(p) = parse::promotion_piece_letter_to_number(last) {
+            promoted = p;
+            chars.pop();
+            if chars.last() == Some(&'=') {
+                chars.pop();
+            }
+        }
+    }
+
+    // The last two characters are the destination square. Anything that
+    // remains in front of them helps to tell apart pieces of the same
+    // type that can reach that square.
+    if chars.len() < 2 {
+        return Err(MoveParseError::Notation);
+    }
+    let split = chars.len() - 2;
+    let square: String = chars[split..].iter().collect();
+    let to = parse::algebraic_square_to_number(&square.to_ascii_lowercase())
+        .ok_or(MoveParseError::Notation)?;
+    let mut from_file: Option<usize> = None;
+    let mut from_rank: Option<usize> = None;
+    for c in chars[..split].iter() {
+        match c {
+            'a'..='h' => from_file = Some(*c as usize - 'a' as usize),
+            '1'..='8' => from_rank = Some(*c as usize - '1' as usize),
+            '=' | '-' => (),
+            _ => return Err(MoveParseError::Notation),
+        }
+    }
+
+    let found: Vec<Move> = moves
+        .filter(|m| {
+            !m.castling()
+                && m.piece() == piece
+                && m.to() == to
+                && m.promoted() == promoted
+                && from_file.is_none_or(|f| m.from() % 8 == f)
+                && from_rank.is_none_or(|r| m.from() / 8 == r)
+        })
+        .collect();
+
+    unique(&found)
+}
+
+// Determine what needs to be added after the piece letter to tell this
+// move apart from the same piece type moving to the same square. The file
+// is preferred, then the rank, and if neither is enough, both are used.
+fn disambiguation(board: &Board, mg: &MoveGenerator, m: Move) -> String {
+    let mut ml = MoveList::new();
+    mg.generate_legal_moves(board, &mut ml, MoveType::All);
+
+    let others: Vec<Square> = (0..ml.len())
+        .map(|i| ml.get_move(i))
+        .filter(|x| same_target(*x, m.piece(), m.to()) && x.from() != m.from())
+        .map(|x| x.from())
+        .collect();
+
+    let from = SQUARE_NAME[m.from()];
+    if others.is_empty() {
+        String::from("")
+    } else if others.iter().all(|sq| sq % 8 != m.from() % 8) {
+        String::from(&from[0..1])
+    } else if others.iter().all(|sq| sq / 8 != m.from() / 8) {
+        String::from(&from[1..2])
+    } else {
+        String::from(from)
+    }
+}
+
+fn same_target(m: Move, piece: Piece, to: Square) -> bool {
+    !m.castling() && m.piece() == piece && m.to() == to
+}
+
+fn unique(found: &[Move]) -> Result<Move, MoveParseError> {
+    match found {
+        [m] => Ok(*m),
+        [] => Err(MoveParseError::Illegal),
+        _ => Err(MoveParseError::Ambiguous),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::defs::{FEN_KIWIPETE_POSITION, FEN_START_POSITION};
+
+    // Parse the SAN string in the given position, and write the move that
+    // was found back into SAN.
+    fn parse_and_write(fen: &str, san: &str) -> Result<String, MoveParseError> {
+        let mut board = Board::from_fen(fen).expect("valid FEN");
+        let mg = MoveGenerator::new();
+        let m = san_to_move(&board, &mg, san)?;
+        let written = move_to_san(&mut board, &mg, m);
+        assert_eq!(board.fen_write(), fen, "board changed by move_to_san");
+        Ok(written)
+    }
+
+    fn assert_san(fen: &str, san: &str, expected: &str) {
+        assert_eq!(parse_and_write(fen, san).as_deref(), Ok(expected), "{san}");
+    }
+
+    #[test]
+    fn simple_moves_round_trip() {
+        for san in ["e4", "d3", "Nf3", "Nc3"] {
+            assert_san(FEN_START_POSITION, san, san);
+        }
+        for san in ["O-O", "O-O-O", "Bxa6", "dxe6", "Qxf6", "Nxf7", "gxh3"] {
+            assert_san(FEN_KIWIPETE_POSITION, san, san);
+        }
+    }
+
+    #[test]
+    fn tolerant_input_is_written_in_standard_form() {
+        assert_san(FEN_START_POSITION, "Nf3!?", "Nf3");
+        assert_san(FEN_KIWIPETE_POSITION, "0-0", "O-O");
+        assert_san(FEN_KIWIPETE_POSITION, "0-0-0", "O-O-O");
+        assert_san(FEN_KIWIPETE_POSITION, "Ba6", "Bxa6");
+        assert_san(FEN_KIWIPETE_POSITION, "Bexa6", "Bxa6");
+    }
+
+    #[test]
+    fn pieces_are_disambiguated() {
+        let by_file = "4k3/8/8/8/8/8/8/R4RK1 w - - 0 1";
+        assert_san(by_file, "Rad1", "Rad1");
+        assert_san(by_file, "Rfd1", "Rfd1");
+
+        let by_rank = "8/7k/8/R7/8/8/8/R5K1 w - - 0 1";
+        assert_san(by_rank, "R1a3", "R1a3");
+        assert_san(by_rank, "R5a3", "R5a3");
+
+        let by_both = "8/7k/8/8/8/Q7/8/Q1Q3K1 w - - 0 1";
+        assert_san(by_both, "Qa1b2", "Qa1b2");
+        assert_san(by_both, "Qcb2", "Qcb2");
+        assert_san(by_both, "Q3b2", "Q3b2");
+    }
+
+    #[test]
+    fn promotions_and_en_passant() {
+        let promotion = "8/P6k/8/8/8/8/8/K7 w - - 0 1";
+        assert_san(promotion, "a8=Q", "a8=Q");
+        assert_san(promotion, "a8N", "a8=N");
+        assert_san(promotion, "a8=r", "a8=R");
+
+        let en_passant = "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1";
+        assert_san(en_passant, "exd6", "exd6");
+    }
+
+    #[test]
+    fn check_and_mate_are_added() {
+        let check = "rnbqkbnr/ppppp1pp/8/5p2/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2";
+        assert_san(check, "Qh5", "Qh5+");
+
+        let mate = "rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq - 0 2";
+        assert_san(mate, "Qh4", "Qh4#");
+        assert_san(mate, "Qh4+", "Qh4#");
+    }
+
+    #[test]
+    fn bad_san_is_rejected() {
+        assert_eq!(
+            parse_and_write(FEN_START_POSITION, "e5"),
+            Err(MoveParseError::Illegal)
+        );
+        assert_eq!(
+            parse_and_write(FEN_START_POSITION, "O-O"),
+            Err(MoveParseError::Illegal)
+        );
+        assert_eq!(
+            parse_and_write(FEN_START_POSITION, "Nz3"),
+            Err(MoveParseError::Notation)
+        );
+        assert_eq!(
+            parse_and_write(FEN_START_POSITION, "N"),
+            Err(MoveParseError::Notation)
+        );
+
+        let by_file = "4k3/8/8/8/8/8/8/R4RK1 w - - 0 1";
+        assert_eq!(
+            parse_and_write(by_file, "Rd1"),
+            Err(MoveParseError::Ambiguous)
+        );
+    }
+}
@@ -0,0 +1,701 @@
+/* =======================================================================
+Rustic is a chess playing engine.
+Copyright (C) 2019-2024, Marcel Vanthoor
+https://rustic-chess.org/
+
+Rustic is written in the Rust programming language. It is an original
+work, not derived from any engine that came before it. However, it does
+use a lot of concepts which are well-known and are in use by most if not
+all classical alpha/beta-based chess engines.
+
+Rustic is free software: you can redistribute it and/or modify it under
+the terms of the GNU General Public License version 3 as published by
+the Free Software Foundation.
+
+Rustic is distributed in the hope that it will be useful, but WITHOUT
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+for more details.
+
+You should have received a copy of the GNU General Public License along
+with this program.  If not, see <http://www.gnu.org/licenses/>.
+======================================================================= */
+
+// selfplay.rs plays a match between two configurations of the engine,
+// inside this process. Each player has its own search thread and TT, and
+// both play on a simulated clock with increment. The openings come from an
+// EPD or PGN file, and each one is played twice, with the players
+// switching colors. The outcome is reported as a win/draw/loss record with
+// an Elo estimate. If SPRT bounds are given, the match stops as soon as the
+// sequential probability ratio test accepts one of its hypotheses. The
+// players implement the Player trait, so the gauntlet can use the same
+// match code to play against external engines.
+
+use crate::{
+    board::{defs::Pieces, Board},
+    defs::{Sides, FEN_START_POSITION, MAX_MOVE_RULE},
+    engine::defs::{EngineOptionDefaults, EngineOptionName, ErrFatal, ErrNormal, SearchData, TT},
+    evaluation::nnue::Network,
+    movegen::{
+        defs::{Move, MoveList, MoveType},
+        MoveGenerator,
+    },
+    search::{
+        defs::{GameTime, SearchControl, SearchMode, SearchParams, SearchReport, SearchSummary},
+        Search,
+    },
+    tools::{
+        epd::Epd,
+        pgn::{self, PgnGame, PgnMove},
+    },
+};
+use crossbeam_channel::Receiver;
+use std::{
+    fs::File,
+    io::Write,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+// A game that runs this long is adjudicated as a draw, so a match can't
+// get stuck in a game that never ends.
+const MAX_GAME_PLIES: usize = 1000;
+
+const WHITE_WINS: &str = "1-0";
+const BLACK_WINS: &str = "0-1";
+const DRAW: &str = "1/2-1/2";
+
+// One of the two engine configurations in the match. The network is
+// loaded once, and then shared by every game the player plays.
+pub struct PlayerConfig {
+    pub name: String,
+    pub hash: usize,
+    pub network: Option<Arc<Network>>,
+}
+
+impl PlayerConfig {
+    // Parse a configuration such as "name=Dev,hash=64,evalfile=dev.nnue".
+    // Except for the name, the settings are the engine's UCI options, with
+    // the same names; options that have no meaning for a player in a
+    // match, such as the buttons, are refused. Settings that are not given
+    // keep their default value.
+    pub fn parse(config: &str, default_name: &str) -> Result<Self, String> {
+        let mut player = Self {
+            name: String::from(default_name),
+            hash: EngineOptionDefaults::HASH_DEFAULT,
+            network: None,
+        };
+
+        for part in config
+            .split(',')
+            .map(|p| p.trim())
+            .filter(|p| !p.is_empty())
+        {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or(format!("{part}: expected option=value"))?;
+            let key = key.trim();
+
+            if key.eq_ignore_ascii_case("name") {
+                player.name = String::from(value.trim());
+                continue;
+            }
+
+            match EngineOptionName::new(key, value) {
+                EngineOptionName::Hash(v) => {
+                    player.hash = v
+                        .parse::<usize>()
+                        .map_err(|_| format!("{key}: {}", ErrNormal::NOT_INT))?;
+                }
+                EngineOptionName::EvalFile(v) => {
+                    player.network = if v.is_empty() || v == EngineOptionDefaults::EVAL_FILE_NONE {
+                        None
+                    } else {
+                        let network = Network::load(&v).map_err(|e| format!("{v}: {e}"))?;
+                        Some(Arc::new(network))
+                    };
+                }
+                EngineOptionName::Nothing => return Err(format!("{key}: unknown option")),
+                _ => return Err(format!("{key}: can't be set for a player in a match")),
+            }
+        }
+
+        Ok(player)
+    }
+}
+
+// Base time and increment in milliseconds.
+pub struct TimeControl {
+    pub base: u128,
+    pub increment: u128,
+}
+
+impl TimeControl {
+    // Parse a time control in seconds, such as "10+0.1" or "60".
+    pub fn parse(tc: &str) -> Result<Self, ()> {
+        let (base, increment) = tc.split_once('+').unwrap_or((tc, "0"));
+        let base = base.trim().parse::<f64>().map_err(|_| ())?;
+        let increment = increment.trim().parse::<f64>().map_err(|_| ())?;
+
+        if base <= 0.0 || increment < 0.0 {
+            return Err(());
+        }
+
+        Ok(Self {
+            base: (base * 1000.0).round() as u128,
+            increment: (increment * 1000.0).round() as u128,
+        })
+    }
+
+    // The time control as written in a PGN TimeControl tag.
+    fn as_string(&self) -> String {
+        let base = self.base as f64 / 1000.0;
+        let increment = self.increment as f64 / 1000.0;
+        format!("{}+{}", base, increment)
+    }
+}
+
+// The sequential probability ratio test checks if the Elo difference is
+// more likely to be elo0 (H0) or elo1 (H1). Alpha is the chance of
+// accepting H1 while H0 is true, beta the chance of the opposite.
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+impl Sprt {
+    // Create the test from Elo bounds such as "0,5".
+    pub fn parse(bounds: &str, alpha: f64, beta: f64) -> Result<Self, ()> {
+        let (elo0, elo1) = bounds.split_once(',').ok_or(())?;
+        let elo0 = elo0.trim().parse::<f64>().map_err(|_| ())?;
+        let elo1 = elo1.trim().parse::<f64>().map_err(|_| ())?;
+        let is_probability = |p: f64| p > 0.0 && p < 1.0;
+
+        if elo1 <= elo0 || !is_probability(alpha) || !is_probability(beta) {
+            return Err(());
+        }
+
+        Ok(Self {
+            elo0,
+            elo1,
+            alpha,
+            beta,
+        })
+    }
+
+    // The lower and upper bound for the log-likelihood ratio. H0 is
+    // accepted below the first, H1 above the second.
+    fn bounds(&self) -> (f64, f64) {
+        let lower = (self.beta / (1.0 - self.alpha)).ln();
+        let upper = ((1.0 - self.beta) / self.alpha).ln();
+        (lower, upper)
+    }
+
+    // The log-likelihood ratio of the match so far. This is the normal
+    // approximation of the generalized SPRT, which is also used by the
+    // well-known tournament managers.
+    fn llr(&self, record: &Record) -> f64 {
+        let variance = record.variance();
+        if variance <= 0.0 {
+            return 0.0;
+        }
+
+        let s0 = expected_score(self.elo0);
+        let s1 = expected_score(self.elo1);
+        let n = record.games() as f64;
+
+        (s1 - s0) * (2.0 * record.score() - s0 - s1) / (2.0 * variance / n)
+    }
+}
+
+// A position to start games from: either a FEN-string, or the starting
+// position, followed by the opening moves.
+pub struct Opening {
+    fen: Option<String>,
+    chess960: bool,
+    moves: Vec<Move>,
+}
+
+impl Opening {
+    fn setup(&self, board: &mut Board) {
+        board.set_chess960(self.chess960);
+        let fen = self.fen.as_deref().unwrap_or(FEN_START_POSITION);
+        board.fen_read(Some(fen)).expect(ErrFatal::NEW_GAME);
+        for m in self.moves.iter() {
+            board.make_legal(*m);
+        }
+    }
+}
+
+pub struct MatchOptions {
+    pub event: String,
+    pub openings: Vec<Opening>,
+    pub games: usize, // 0 plays until the SPRT stops the match.
+    pub time_control: TimeControl,
+    pub sprt: Option<Sprt>,
+    pub pgn_out: Option<File>,
+}
+
+// Read the openings from a PGN file (by extension) or an EPD file. If
+// there is no file, all games start from the starting position. Each PGN
+// game is replayed in Chess960 mode if its Variant tag says so.
+pub fn read_openings(path: Option<&str>, mg: &MoveGenerator) -> Result<Vec<Opening>, String> {
+    let mut board = Board::new();
+    let path = match path {
+        Some(p) => p,
+        None => {
+            return Ok(vec![Opening {
+                fen: None,
+                chess960: false,
+                moves: Vec::new(),
+            }])
+        }
+    };
+
+    let openings: Vec<Opening> = if path.to_lowercase().ends_with(".pgn") {
+        pgn::read_file(path, &mut board, mg)
+            .map_err(|e| e.to_string())?
+            .iter()
+            .map(|game| Opening {
+                fen: game.tag("FEN").map(String::from),
+                chess960: game.is_chess960(),
+                moves: game.moves.iter().map(|m| m.m).collect(),
+            })
+            .collect()
+    } else {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let mut openings = Vec::new();
+        for line in text.lines().map(|l| l.trim()) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match Epd::parse(line) {
+                Ok(epd) if board.fen_read(Some(&epd.fen)).is_ok() => openings.push(Opening {
+                    fen: Some(epd.fen),
+                    chess960: false,
+                    moves: Vec::new(),
+                }),
+                _ => println!("Skipping invalid opening: {}", line),
+            }
+        }
+        openings
+    };
+
+    if openings.is_empty() {
+        return Err(format!("No openings found in {}", path));
+    }
+
+    Ok(openings)
+}
+
+// The match result, from the point of view of the first player.
+#[derive(Default)]
+pub struct Record {
+    pub wins: usize,
+    pub draws: usize,
+    pub losses: usize,
+}
+
+impl Record {
+    pub fn games(&self) -> usize {
+        self.wins + self.draws + self.losses
+    }
+
+    pub fn score(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games() as f64
+    }
+
+    // The variance of the score of a single game.
+    fn variance(&self) -> f64 {
+        let n = self.games() as f64;
+        let score = self.score();
+        let squares = (self.wins as f64 + self.draws as f64 / 4.0) / n;
+        squares - score * score
+    }
+
+    // The Elo difference and its 95% error margin. There is no estimate
+    // as long as one of the players has scored everything.
+    pub fn elo(&self) -> Option<(f64, f64)> {
+        let score = self.score();
+        if score <= 0.0 || score >= 1.0 {
+            return None;
+        }
+
+        let deviation = (self.variance() / self.games() as f64).sqrt();
+        let low = (score - 1.96 * deviation).max(f64::EPSILON);
+        let high = (score + 1.96 * deviation).min(1.0 - f64::EPSILON);
+        let margin = (elo_difference(high) - elo_difference(low)) / 2.0;
+
+        Some((elo_difference(score), margin))
+    }
+}
+
+fn expected_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+fn elo_difference(score: f64) -> f64 {
+    400.0 * (score / (1.0 - score)).log10()
+}
+
+// A player in a match. Next to the board with the current position, the
+// player also gets the game so far, so it can pass the starting position
+// and the moves on to an external engine.
+pub trait Player {
+    fn name(&self) -> &str;
+
+    fn new_game(&mut self, chess960: bool);
+
+    // Return the best move in the current position, and the summary of
+    // the last completed search depth if there is one. None means that
+    // the player failed to come up with a move.
+    fn think(
+        &mut self,
+        game: &PgnGame,
+        board: &Board,
+        game_time: GameTime,
+    ) -> Option<(Move, Option<SearchSummary>)>;
+
+    fn quit(&mut self);
+}
+
+// Rustic itself, with the given configuration. Each engine player has its
+// own search thread, TT and board, and evaluates with its own network.
+pub struct EnginePlayer {
+    name: String,
+    network: Option<Arc<Network>>,
+    board: Arc<Mutex<Board>>,
+    tt: Arc<Mutex<TT<SearchData>>>,
+    search: Search,
+    report_rx: Receiver<SearchReport>,
+}
+
+impl EnginePlayer {
+    pub fn new(config: &PlayerConfig, mg: &Arc<MoveGenerator>) -> Self {
+        let board = Arc::new(Mutex::new(Board::new()));
+        let tt = Arc::new(Mutex::new(TT::<SearchData>::new(config.hash)));
+        let (report_tx, report_rx) = crossbeam_channel::unbounded::<SearchReport>();
+        let mut search = Search::new();
+        search.init(
+            report_tx,
+            Arc::clone(&board),
+            Arc::clone(mg),
+            Arc::clone(&tt),
+            config.hash > 0,
+        );
+
+        Self {
+            name: config.name.clone(),
+            network: config.network.clone(),
+            board,
+            tt,
+            search,
+            report_rx,
+        }
+    }
+}
+
+impl Player for EnginePlayer {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn new_game(&mut self, _chess960: bool) {
+        self.tt.lock().expect(ErrFatal::LOCK).clear();
+    }
+
+    fn think(
+        &mut self,
+        _game: &PgnGame,
+        board: &Board,
+        game_time: GameTime,
+    ) -> Option<(Move, Option<SearchSummary>)> {
+        let mut board = board.clone();
+        board.share_history();
+        board.set_network(self.network.clone());
+        *self.board.lock().expect(ErrFatal::LOCK) = board;
+
+        let mut sp = SearchParams::new();
+        sp.game_time = game_time;
+        sp.search_mode = SearchMode::GameTime;
+        sp.quiet = true;
+        self.search.send(SearchControl::Start(0, sp));
+
+        let mut summary: Option<SearchSummary> = None;
+        let best_move = loop {
+            match self.report_rx.recv().expect(ErrFatal::CHANNEL) {
+                SearchReport::Finished(_, m) => break m,
+                SearchReport::SearchSummary(s) => summary = Some(s),
+                _ => (),
+            }
+        };
+
+        Some((best_move, summary))
+    }
+
+    fn quit(&mut self) {
+        self.search.send(SearchControl::Quit);
+        self.search.wait_for_shutdown();
+    }
+}
+
+// Play a self-play match between two configurations of the engine.
+pub fn run(options: &mut MatchOptions, configs: &[PlayerConfig; 2], mg: Arc<MoveGenerator>) {
+    let mut players: [Box<dyn Player>; 2] = [
+        Box::new(EnginePlayer::new(&configs[0], &mg)),
+        Box::new(EnginePlayer::new(&configs[1], &mg)),
+    ];
+
+    play_match(&mut players, options, &mg);
+
+    for player in players.iter_mut() {
+        player.quit();
+    }
+}
+
+// Play a match and return its result. Games are played in pairs: both
+// players get to play each opening with white.
+pub fn play_match(
+    players: &mut [Box<dyn Player>; 2],
+    options: &mut MatchOptions,
+    mg: &MoveGenerator,
+) -> Record {
+    let names = [
+        String::from(players[0].name()),
+        String::from(players[1].name()),
+    ];
+    let mut record = Record::default();
+    let mut game_nr = 0;
+    let mut finished = false;
+
+    println!(
+        "Match: {} vs {}, {} openings, {} ms + {} ms",
+        names[0],
+        names[1],
+        options.openings.len(),
+        options.time_control.base,
+        options.time_control.increment
+    );
+
+    while !finished && (options.games == 0 || game_nr < options.games) {
+        let opening = &options.openings[(game_nr / 2) % options.openings.len()];
+        let white = game_nr % 2;
+        game_nr += 1;
+
+        let mut game = play_game(players, white, opening, &options.time_control, mg);
+        game.set_tag("Event", &options.event);
+        game.set_tag("Round", &game_nr.to_string());
+
+        // Score the game for the first player.
+        let first_wins = if white == 0 { WHITE_WINS } else { BLACK_WINS };
+        match &game.result[..] {
+            DRAW => record.draws += 1,
+            r if r == first_wins => record.wins += 1,
+            _ => record.losses += 1,
+        }
+
+        println!(
+            "Game {} ({} vs {}): {} {{{}}}",
+            game_nr,
+            names[white],
+            names[1 - white],
+            game.result,
+            game.tag("Termination").unwrap_or("")
+        );
+        println!(
+            "Score of {} vs {}: {} - {} - {}  [{:.3}] {}",
+            names[0],
+            names[1],
+            record.wins,
+            record.losses,
+            record.draws,
+            record.score(),
+            record.games()
+        );
+        if let Some((elo, margin)) = record.elo() {
+            println!("Elo difference: {:.1} +/- {:.1}", elo, margin);
+        }
+
+        if let Some(sprt) = &options.sprt {
+            let llr = sprt.llr(&record);
+            let (lower, upper) = sprt.bounds();
+            println!(
+                "SPRT: llr {:.2}, lbound {:.2}, ubound {:.2}",
+                llr, lower, upper
+            );
+            if llr <= lower {
+                println!("H0 was accepted");
+                finished = true;
+            } else if llr >= upper {
+                println!("H1 was accepted");
+                finished = true;
+            }
+        }
+
+        if let Some(file) = &mut options.pgn_out {
+            let mut replay = Board::new();
+            let text = pgn::write(&game, &mut replay, mg);
+            if let Err(e) = text.map(|t| file.write_all(t.as_bytes())) {
+                println!("Writing PGN failed: {}", e);
+            }
+        }
+        println!();
+    }
+
+    record
+}
+
+// Play a single game from the given opening. "white" is the index of the
+// player that has the white pieces.
+fn play_game(
+    players: &mut [Box<dyn Player>; 2],
+    white: usize,
+    opening: &Opening,
+    tc: &TimeControl,
+    mg: &MoveGenerator,
+) -> PgnGame {
+    let mut clock = [tc.base; Sides::BOTH];
+    let mut board = Board::new();
+    let mut game = PgnGame::new();
+
+    game.set_tag("White", players[white].name());
+    game.set_tag("Black", players[1 - white].name());
+    game.set_tag("TimeControl", &tc.as_string());
+    if let Some(fen) = &opening.fen {
+        game.set_tag("SetUp", "1");
+        game.set_tag("FEN", fen);
+    }
+    if opening.chess960 {
+        game.set_tag("Variant", "Chess960");
+    }
+
+    opening.setup(&mut board);
+    game.moves = opening.moves.iter().map(|m| PgnMove::new(*m)).collect();
+    for player in players.iter_mut() {
+        player.new_game(opening.chess960);
+    }
+
+    let (result, termination) = loop {
+        if let Some(end) = game_over(&board, mg) {
+            break end;
+        }
+
+        // The player to move searches the position on its own clock.
+        let us = board.us();
+        let player = if us == Sides::WHITE { white } else { 1 - white };
+        let game_time = GameTime::new(
+            clock[Sides::WHITE],
+            clock[Sides::BLACK],
+            tc.increment,
+            tc.increment,
+            None,
+        );
+        let now = Instant::now();
+        let answer = players[player].think(&game, &board, game_time);
+        let elapsed = now.elapsed().as_millis();
+        let loses = if us == Sides::WHITE {
+            BLACK_WINS
+        } else {
+            WHITE_WINS
+        };
+
+        if elapsed > clock[us] {
+            break (loses, "time forfeit");
+        }
+        clock[us] = clock[us] - elapsed + tc.increment;
+
+        let (best_move, summary) = match answer {
+            Some((m, s)) if m.get_move() != 0 && board.make(m, mg) => (m, s),
+            _ => break (loses, "illegal move"),
+        };
+
+        let mut pgn_move = PgnMove::new(best_move);
+        pgn_move.comment = summary.as_ref().map(pgn::eval_comment);
+        game.moves.push(pgn_move);
+    };
+
+    game.result = String::from(result);
+    game.set_tag("Termination", termination);
+    game
+}
+
+// Returns the result and the reason if the game on the board is over.
+pub fn game_over(board: &Board, mg: &MoveGenerator) -> Option<(&'static str, &'static str)> {
+    let mut ml = MoveList::new();
+    mg.generate_legal_moves(board, &mut ml, MoveType::All);
+
+    if ml.is_empty() {
+        let king = board.king_square(board.us());
+        let in_check = mg.square_attacked(board, board.opponent(), king);
+        let loses = if board.us() == Sides::WHITE {
+            BLACK_WINS
+        } else {
+            WHITE_WINS
+        };
+        return Some(if in_check {
+            (loses, "checkmate")
+        } else {
+            (DRAW, "stalemate")
+        });
+    }
+
+    if board.halfmove_clock() >= MAX_MOVE_RULE {
+        Some((DRAW, "50-move rule"))
+    } else if board.history_len() > 0 && Search::is_repetition(board) >= 2 {
+        Some((DRAW, "3-fold repetition"))
+    } else if is_insufficient_material(board) {
+        Some((DRAW, "insufficient material"))
+    } else if board.history_len() >= MAX_GAME_PLIES {
+        Some((DRAW, "adjudication"))
+    } else {
+        None
+    }
+}
+
+// Unlike the search's version of this function, this one only declares a
+// draw if no mate is possible at all: a king and at most one minor piece
+// against a bare king.
+fn is_insufficient_material(board: &Board) -> bool {
+    let count = |piece| {
+        (board.get_pieces(piece, Sides::WHITE) | board.get_pieces(piece, Sides::BLACK)).count_ones()
+    };
+    let no_mating_pieces = [Pieces::PAWN, Pieces::ROOK, Pieces::QUEEN]
+        .iter()
+        .all(|p| count(*p) == 0);
+
+    no_mating_pieces && count(Pieces::KNIGHT) + count(Pieces::BISHOP) <= 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::defs::SQUARE_NAME;
+
+    // A Chess960 position with X-FEN castling rights: KQkq are the
+    // outermost rooks, on the e- and g-files.
+    const OPENING_960: &str = "[Variant \"Chess960\"]\n\
+        [FEN \"bqnbrkrn/pppppppp/8/8/8/8/PPPPPPPP/BQNBRKRN w KQkq - 0 1\"]\n\n\
+        1. O-O O-O *\n";
+
+    #[test]
+    fn chess960_pgn_openings_are_replayed_as_chess960() {
+        let path = std::env::temp_dir().join(format!("rustic-960-{}.pgn", std::process::id()));
+        std::fs::write(&path, OPENING_960).expect("writing the test file failed");
+        let mg = MoveGenerator::new();
+        let openings = read_openings(path.to_str(), &mg);
+        let _ = std::fs::remove_file(&path);
+
+        let openings = openings.unwrap_or_else(|e| panic!("{e}"));
+        assert_eq!(openings.len(), 1);
+        assert!(openings[0].chess960);
+        assert!(openings[0].moves.iter().all(|m| m.castling()));
+
+        let mut board = Board::new();
+        openings[0].setup(&mut board);
+        assert!(board.chess960());
+        assert_eq!(SQUARE_NAME[board.king_square(Sides::WHITE)], "g1");
+        assert_eq!(SQUARE_NAME[board.king_square(Sides::BLACK)], "g8");
+    }
+}
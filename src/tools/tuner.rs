@@ -0,0 +1,341 @@
+/* =======================================================================
+Rustic is a chess playing engine.
+Copyright (C) 2019-2024, Marcel Vanthoor
+https://rustic-chess.org/
+
+Rustic is written in the Rust programming language. It is an original
+work, not derived from any engine that came before it. However, it does
+use a lot of concepts which are well-known and are in use by most if not
+all classical alpha/beta-based chess engines.
+
+Rustic is free software: you can redistribute it and/or modify it under
+the terms of the GNU General Public License version 3 as published by
+the Free Software Foundation.
+
+Rustic is distributed in the hope that it will be useful, but WITHOUT
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+for more details.
+
+You should have received a copy of the GNU General Public License along
+with this program.  If not, see <http://www.gnu.org/licenses/>.
+======================================================================= */
+
+// tuner.rs tunes the evaluation with the Texel method. It needs a file of
+// quiet positions, each labelled with the result of the game it was taken
+// from. The evaluation parameters are tuned so the evaluation predicts
+// these results as well as possible: the error is the mean squared
+// difference between each result and the evaluation mapped through a
+// sigmoid to an expected score.
+//
+// Because the search doesn't stop at captures, positions are first
+// resolved by the quiescence search: the tuner evaluates the position at
+// the end of its quiescence PV. The evaluation is linear in its
+// parameters, so each of those leaf positions is stored as a list of
+// features (parameter, count) and the tuning itself is plain gradient
+// descent, without running the search again.
+//
+// The result is written as Rust source, in the same layout as the tables
+// in evaluation/psqt.rs, so the tuned values can be pasted in.
+
+use crate::{
+    board::Board,
+    defs::{NrOf, Sides},
+    engine::defs::{SearchData, TT},
+    evaluation::{self, psqt},
+    misc::bits,
+    movegen::MoveGenerator,
+    search::{
+        defs::{SearchControl, SearchInfo, SearchParams, SearchRefs, SearchReport, INF},
+        Search,
+    },
+    tools::epd::Epd,
+};
+use std::sync::{Arc, Mutex};
+
+// All tunable evaluation terms get a range of parameters. At this point,
+// these are only the PSQT's, one value per piece type and square. A new
+// term needs its parameters added here, its features in features(), and
+// its output in to_source().
+const PSQT_PARAMS: usize = NrOf::PIECE_TYPES * NrOf::SQUARES;
+const NR_OF_PARAMS: usize = PSQT_PARAMS;
+
+// Names of the PSQT's in psqt.rs, in piece order.
+const PSQT_NAMES: [&str; NrOf::PIECE_TYPES] = [
+    "KING_MG",
+    "QUEEN_MG",
+    "ROOK_MG",
+    "BISHOP_MG",
+    "KNIGHT_MG",
+    "PAWN_MG",
+];
+
+// Adam optimizer settings. The learning rate is given by the user.
+const BETA1: f64 = 0.9;
+const BETA2: f64 = 0.999;
+const EPSILON: f64 = 1e-8;
+
+pub const ITERATIONS_DEFAULT: usize = 1000;
+pub const LEARNING_RATE_DEFAULT: f64 = 1.0;
+
+// A position from the data file, resolved to its quiescence leaf.
+pub struct TuneEntry {
+    result: f64,                 // Game result from white's view.
+    offset: f64,                 // Evaluation terms that aren't tuned.
+    features: Vec<(usize, f64)>, // Parameter index and its count.
+}
+
+// Load the positions from the data file. Each line is a FEN or EPD
+// position, followed by a result: "1-0", "0-1" or "1/2-1/2", or a score
+// from white's view such as "[0.5]". An EPD "c9" or "result" operation
+// holding the result works as well. Lines without a valid position or
+// result are skipped.
+pub fn load(path: &str, mg: Arc<MoveGenerator>) -> std::io::Result<Vec<TuneEntry>> {
+    let text = std::fs::read_to_string(path)?;
+    let params = initial_params();
+    let mut board = Board::new();
+    let mut entries: Vec<TuneEntry> = Vec::new();
+    let mut skipped = 0;
+
+    // The quiescence search needs these, even though they are not used.
+    let tt = Arc::new(Mutex::new(TT::<SearchData>::new(0)));
+    let (_control_tx, control_rx) = crossbeam_channel::unbounded::<SearchControl>();
+    let (report_tx, _report_rx) = crossbeam_channel::unbounded::<SearchReport>();
+    let mut search_params = SearchParams::new();
+    search_params.quiet = true;
+
+    for line in text.lines().map(|l| l.trim()) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let result = match Epd::parse(line) {
+            Ok(epd) if board.fen_read(Some(&epd.fen)).is_ok() => parse_result(line, &epd),
+            _ => None,
+        };
+        let result = match result {
+            Some(r) => r,
+            None => {
+                skipped += 1;
+                continue;
+            }
+        };
+
+        // Resolve the position by playing out the quiescence PV.
+        let mut search_info = SearchInfo::new();
+        let mut pv = Vec::new();
+        let mut refs = SearchRefs {
+            board: &mut board,
+            mg: &mg,
+            tt: &tt,
+            tt_enabled: false,
+            search_params: &mut search_params,
+            search_info: &mut search_info,
+            control_rx: &control_rx,
+            report_tx: &report_tx,
+        };
+        Search::quiescence(-INF, INF, &mut pv, &mut refs);
+        for m in pv {
+            board.make_legal(m);
+        }
+
+        // Everything the evaluation adds on top of the tuned terms is
+        // kept as a fixed offset.
+        let features = features(&board);
+        let eval = white_eval(&board) as f64;
+        let offset = eval - linear_eval(&params, &features, 0.0);
+
+        entries.push(TuneEntry {
+            result,
+            offset,
+            features,
+        });
+    }
+
+    println!("Loaded {} positions, skipped {}", entries.len(), skipped);
+    Ok(entries)
+}
+
+// Tune the parameters, starting from the current evaluation, and return
+// the result. First the sigmoid's scaling constant K is fitted to the
+// current evaluation; then the parameters are tuned with K fixed.
+pub fn tune(entries: &[TuneEntry], iterations: usize, learning_rate: f64) -> Vec<f64> {
+    let mut params = initial_params();
+    let k = find_k(entries, &params);
+    println!("K: {:.3}, error: {:.6}", k, error(entries, &params, k));
+
+    let mut m = vec![0.0; NR_OF_PARAMS];
+    let mut v = vec![0.0; NR_OF_PARAMS];
+
+    for iteration in 1..=iterations {
+        let gradient = gradient(entries, &params, k);
+
+        // Adam: each parameter gets its own step size, so it doesn't
+        // matter that some of them occur far more often than others.
+        let t = iteration as i32;
+        for i in 0..NR_OF_PARAMS {
+            m[i] = BETA1 * m[i] + (1.0 - BETA1) * gradient[i];
+            v[i] = BETA2 * v[i] + (1.0 - BETA2) * gradient[i] * gradient[i];
+            let m_hat = m[i] / (1.0 - BETA1.powi(t));
+            let v_hat = v[i] / (1.0 - BETA2.powi(t));
+            params[i] -= learning_rate * m_hat / (v_hat.sqrt() + EPSILON);
+        }
+
+        if iteration % 50 == 0 || iteration == iterations {
+            let e = error(entries, &params, k);
+            println!("Iteration {}: error {:.6}", iteration, e);
+        }
+    }
+
+    params
+}
+
+// Write the tuned parameters as Rust source, laid out as in psqt.rs.
+pub fn to_source(params: &[f64]) -> String {
+    let mut source = String::new();
+
+    for (piece, name) in PSQT_NAMES.iter().enumerate() {
+        source.push_str("#[rustfmt::skip]\n");
+        source.push_str(&format!("const {}: Psqt = [\n", name));
+        for rank in 0..8 {
+            let row: Vec<String> = (0..8)
+                .map(|file| {
+                    let value = params[piece * NrOf::SQUARES + rank * 8 + file];
+                    format!("{:>4}", value.round() as i16)
+                })
+                .collect();
+            source.push_str(&format!("   {},\n", row.join(", ")));
+        }
+        source.push_str("];\n\n");
+    }
+
+    source
+}
+
+// The parameters as they are in the engine right now.
+fn initial_params() -> Vec<f64> {
+    let mut params = vec![0.0; NR_OF_PARAMS];
+    for (piece, table) in psqt::PSQT_MG.iter().enumerate() {
+        for (square, value) in table.iter().enumerate() {
+            params[piece * NrOf::SQUARES + square] = *value as f64;
+        }
+    }
+    params
+}
+
+// Count how often each parameter is used in the evaluation of the
+// position, from white's point of view: a white piece adds its PSQT
+// value, and a black piece subtracts it.
+fn features(board: &Board) -> Vec<(usize, f64)> {
+    let mut counts = [0.0; NR_OF_PARAMS];
+
+    for piece in 0..NrOf::PIECE_TYPES {
+        let mut white_pieces = board.get_pieces(piece, Sides::WHITE);
+        let mut black_pieces = board.get_pieces(piece, Sides::BLACK);
+
+        while white_pieces > 0 {
+            let square = bits::next(&mut white_pieces);
+            counts[piece * NrOf::SQUARES + psqt::FLIP[square]] += 1.0;
+        }
+        while black_pieces > 0 {
+            let square = bits::next(&mut black_pieces);
+            counts[piece * NrOf::SQUARES + square] -= 1.0;
+        }
+    }
+
+    counts
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| **c != 0.0)
+        .map(|(i, c)| (i, *c))
+        .collect()
+}
+
+// The engine's evaluation of the position, from white's point of view.
+fn white_eval(board: &Board) -> i16 {
+    let eval = evaluation::evaluate_position(board);
+    if board.us() == Sides::WHITE {
+        eval
+    } else {
+        -eval
+    }
+}
+
+fn linear_eval(params: &[f64], features: &[(usize, f64)], offset: f64) -> f64 {
+    offset + features.iter().map(|(i, c)| params[*i] * c).sum::<f64>()
+}
+
+// Map an evaluation in centipawns to an expected score between 0 and 1.
+fn sigmoid(eval: f64, k: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * eval / 400.0))
+}
+
+fn error(entries: &[TuneEntry], params: &[f64], k: f64) -> f64 {
+    let total: f64 = entries
+        .iter()
+        .map(|e| {
+            let eval = linear_eval(params, &e.features, e.offset);
+            (e.result - sigmoid(eval, k)).powi(2)
+        })
+        .sum();
+
+    total / entries.len() as f64
+}
+
+// The derivative of the error with respect to each parameter.
+fn gradient(entries: &[TuneEntry], params: &[f64], k: f64) -> Vec<f64> {
+    let mut gradient = vec![0.0; NR_OF_PARAMS];
+    let scale = k * std::f64::consts::LN_10 / 400.0;
+
+    for e in entries.iter() {
+        let s = sigmoid(linear_eval(params, &e.features, e.offset), k);
+        let delta = -2.0 * (e.result - s) * s * (1.0 - s) * scale;
+        for (i, c) in e.features.iter() {
+            gradient[*i] += delta * c;
+        }
+    }
+
+    let n = entries.len() as f64;
+    gradient.iter().map(|g| g / n).collect()
+}
+
+// Find the K that gives the lowest error for the current parameters, by
+// narrowing down the search interval step by step.
+fn find_k(entries: &[TuneEntry], params: &[f64]) -> f64 {
+    let mut best = 1.0;
+    let mut step = 1.0;
+
+    for _ in 0..10 {
+        let mut best_error = error(entries, params, best);
+        let start = (best - step).max(step / 10.0);
+        let mut k = start;
+        while k <= best + step {
+            let e = error(entries, params, k);
+            if e < best_error {
+                best_error = e;
+                best = k;
+            }
+            k += step / 10.0;
+        }
+        step /= 10.0;
+    }
+
+    best
+}
+
+// Take the game result from the "c9" or "result" operation, or else from
+// the last word on the line.
+fn parse_result(line: &str, epd: &Epd) -> Option<f64> {
+    let result = epd
+        .operand("c9")
+        .or(epd.operand("result"))
+        .or(line.split_whitespace().last())?;
+    let result = result.trim_matches(|c| "[]\";".contains(c));
+
+    match result {
+        "1-0" | "1.0" | "1" => Some(1.0),
+        "0-1" | "0.0" | "0" => Some(0.0),
+        "1/2-1/2" | "0.5" => Some(0.5),
+        _ => None,
+    }
+}
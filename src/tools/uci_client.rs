@@ -0,0 +1,284 @@
+/* =======================================================================
+Rustic is a chess playing engine.
+Copyright (C) 2019-2024, Marcel Vanthoor
+https://rustic-chess.org/
+
+Rustic is written in the Rust programming language. It is an original
+work, not derived from any engine that came before it. However, it does
+use a lot of concepts which are well-known and are in use by most if not
+all classical alpha/beta-based chess engines.
+
+Rustic is free software: you can redistribute it and/or modify it under
+the terms of the GNU General Public License version 3 as published by
+the Free Software Foundation.
+
+Rustic is distributed in the hope that it will be useful, but WITHOUT
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+for more details.
+
+You should have received a copy of the GNU General Public License along
+with this program.  If not, see <http://www.gnu.org/licenses/>.
+======================================================================= */
+
+// uci_client.rs runs an external chess engine as a child process, and
+// talks to it as a UCI client; this is the GUI side of the protocol that
+// comm/uci.rs implements for Rustic itself. The engine's output is read by
+// a separate thread, so every response can be waited for with a timeout.
+
+use crate::search::defs::GameTime;
+use crossbeam_channel::{Receiver, RecvTimeoutError};
+use std::{
+    fmt::{self, Display},
+    io::{BufRead, BufReader, Write},
+    process::{Child, ChildStdin, Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+// Time the engine gets to answer "uci" and "isready".
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Time the engine gets to send its best move after being told to stop.
+const STOP_TIMEOUT: Duration = Duration::from_secs(1);
+
+// Time the engine gets to exit after "quit", before it is killed.
+const QUIT_TIMEOUT: Duration = Duration::from_secs(1);
+
+pub enum UciClientError {
+    Start(String),
+    Io(String),
+    Timeout(&'static str),
+    Exited,
+}
+
+impl Display for UciClientError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UciClientError::Start(e) => write!(f, "Engine could not be started: {}", e),
+            UciClientError::Io(e) => write!(f, "Engine communication failed: {}", e),
+            UciClientError::Timeout(cmd) => write!(f, "Engine did not answer '{}' in time", cmd),
+            UciClientError::Exited => write!(f, "Engine exited unexpectedly"),
+        }
+    }
+}
+
+pub type UciClientResult<T> = Result<T, UciClientError>;
+
+// The engine's answer to "go": its best move in coordinate notation, and
+// the last depth and score it reported, if any. A mate score is given in
+// moves, as in the "score mate" info.
+pub struct UciBestMove {
+    pub best_move: String,
+    pub depth: Option<i8>,
+    pub cp: Option<i16>,
+    pub mate: Option<i16>,
+}
+
+pub struct UciClient {
+    name: String,
+    options: Vec<String>,
+    process: Child,
+    stdin: ChildStdin,
+    lines_rx: Receiver<String>,
+}
+
+impl UciClient {
+    // Start the engine at the given path, and perform the UCI handshake.
+    pub fn start(path: &str) -> UciClientResult<Self> {
+        let mut process = Command::new(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| UciClientError::Start(format!("{}: {}", path, e)))?;
+
+        let stdin = process.stdin.take().ok_or(UciClientError::Exited)?;
+        let stdout = process.stdout.take().ok_or(UciClientError::Exited)?;
+
+        // The reader thread ends by itself when the engine exits, because
+        // its output is closed then.
+        let (lines_tx, lines_rx) = crossbeam_channel::unbounded::<String>();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if lines_tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut client = Self {
+            name: String::from(path),
+            options: Vec::new(),
+            process,
+            stdin,
+            lines_rx,
+        };
+
+        client.send("uci")?;
+        let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+        loop {
+            let line = client.receive("uci", deadline)?;
+            if let Some(name) = line.strip_prefix("id name ") {
+                client.name = String::from(name.trim());
+            } else if let Some(option) = line.strip_prefix("option name ") {
+                let name = option.split(" type ").next().unwrap_or(option);
+                client.options.push(String::from(name.trim()));
+            } else if line.trim() == "uciok" {
+                break;
+            }
+        }
+
+        Ok(client)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    // Option names are case-insensitive in UCI.
+    pub fn has_option(&self, name: &str) -> bool {
+        self.options.iter().any(|o| o.eq_ignore_ascii_case(name))
+    }
+
+    pub fn set_option(&mut self, name: &str, value: &str) -> UciClientResult<()> {
+        self.send(&format!("setoption name {} value {}", name, value))
+    }
+
+    // Wait until the engine has processed all previous commands.
+    pub fn is_ready(&mut self) -> UciClientResult<()> {
+        self.send("isready")?;
+        let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+        while self.receive("isready", deadline)?.trim() != "readyok" {}
+        Ok(())
+    }
+
+    pub fn new_game(&mut self) -> UciClientResult<()> {
+        self.send("ucinewgame")?;
+        self.is_ready()
+    }
+
+    // Set up the position with the FEN-string (or the starting position
+    // if there is none), followed by the moves in coordinate notation.
+    pub fn position(&mut self, fen: Option<&str>, moves: &[String]) -> UciClientResult<()> {
+        let mut cmd = match fen {
+            Some(f) => format!("position fen {}", f),
+            None => String::from("position startpos"),
+        };
+        if !moves.is_empty() {
+            cmd.push_str(" moves ");
+            cmd.push_str(&moves.join(" "));
+        }
+        self.send(&cmd)
+    }
+
+    // Start a search with the given clock, and wait for the best move. If
+    // the engine doesn't answer within the timeout, it is told to stop; it
+    // then still has to send a best move, even though it is too late.
+    pub fn go(&mut self, gt: &GameTime, timeout: Duration) -> UciClientResult<UciBestMove> {
+        self.send(&format!(
+            "go wtime {} btime {} winc {} binc {}",
+            gt.wtime, gt.btime, gt.winc, gt.binc
+        ))?;
+
+        let mut result = UciBestMove {
+            best_move: String::new(),
+            depth: None,
+            cp: None,
+            mate: None,
+        };
+
+        let mut deadline = Instant::now() + timeout;
+        let mut stopped = false;
+        loop {
+            let line = match self.receive("go", deadline) {
+                Err(UciClientError::Timeout(_)) if !stopped => {
+                    self.send("stop")?;
+                    stopped = true;
+                    deadline = Instant::now() + STOP_TIMEOUT;
+                    continue;
+                }
+                other => other?,
+            };
+
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("info") => parse_info(&mut result, words),
+                Some("bestmove") => {
+                    result.best_move = String::from(words.next().unwrap_or(""));
+                    break;
+                }
+                _ => (),
+            }
+        }
+
+        Ok(result)
+    }
+
+    // Ask the engine to quit, and kill it if it doesn't.
+    pub fn quit(&mut self) {
+        let _ = self.send("quit");
+        let deadline = Instant::now() + QUIT_TIMEOUT;
+        while Instant::now() < deadline {
+            if let Ok(Some(_)) = self.process.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+
+    fn send(&mut self, cmd: &str) -> UciClientResult<()> {
+        writeln!(self.stdin, "{}", cmd)
+            .and_then(|_| self.stdin.flush())
+            .map_err(|e| UciClientError::Io(e.to_string()))
+    }
+
+    // Receive the next line from the engine, waiting until the deadline.
+    fn receive(&self, cmd: &'static str, deadline: Instant) -> UciClientResult<String> {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        self.lines_rx.recv_timeout(timeout).map_err(|e| match e {
+            RecvTimeoutError::Timeout => UciClientError::Timeout(cmd),
+            RecvTimeoutError::Disconnected => UciClientError::Exited,
+        })
+    }
+}
+
+// Make sure the engine doesn't keep running if the client is dropped
+// without quitting it first.
+impl Drop for UciClient {
+    fn drop(&mut self) {
+        if let Ok(None) = self.process.try_wait() {
+            self.quit();
+        }
+    }
+}
+
+// Take the depth and score from an info line. Bounds are ignored; the
+// score is simply the last one reported.
+fn parse_info<'a>(result: &mut UciBestMove, mut words: impl Iterator<Item = &'a str>) {
+    while let Some(word) = words.next() {
+        match word {
+            "depth" => result.depth = words.next().and_then(|d| d.parse::<i8>().ok()),
+            "score" => match (
+                words.next(),
+                words.next().and_then(|s| s.parse::<i16>().ok()),
+            ) {
+                (Some("cp"), Some(cp)) => {
+                    result.cp = Some(cp);
+                    result.mate = None;
+                }
+                (Some("mate"), Some(mate)) => {
+                    result.mate = Some(mate);
+                    result.cp = None;
+                }
+                _ => (),
+            },
+            // The PV and the current move are the last items on a line,
+            // and what follows are moves, not keywords.
+            "pv" | "string" => break,
+            _ => (),
+        }
+    }
+}
@@ -0,0 +1,779 @@
+/* =======================================================================
+Rustic is a chess playing engine.
+Copyright (C) 2019-2024, Marcel Vanthoor
+https://rustic-chess.org/
+
+Rustic is written in the Rust programming language. It is an original
+work, not derived from any engine that came before it. However, it does
+use a lot of concepts which are well-known and are in use by most if not
+all classical alpha/beta-based chess engines.
+
+Rustic is free software: you can redistribute it and/or modify it under
+the terms of the GNU General Public License version 3 as published by
+the Free Software Foundation.
+
+Rustic is distributed in the hope that it will be useful, but WITHOUT
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+for more details.
+
+You should have received a copy of the GNU General Public License along
+with this program.  If not, see <http://www.gnu.org/licenses/>.
+======================================================================= */
+
+// transposition.rs holds the transposition table (TT). The table is
+// generic over the data in its entries, so the search (SearchData) and
+// perft (PerftData) each have their own kind of TT.
+
+mod huge_table;
+
+use crate::{board::defs::ZobristKey, movegen::defs::ShortMove, search::defs::CHECKMATE_THRESHOLD};
+use huge_table::HugeTable;
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+};
+
+const MEGABYTE: usize = 1024 * 1024;
+const ENTRIES_PER_BUCKET: usize = 4;
+const HIGH_FOUR_BYTES: u64 = 0xFF_FF_FF_FF_00_00_00_00;
+const LOW_FOUR_BYTES: u64 = 0x00_00_00_00_FF_FF_FF_FF;
+const SHIFT_TO_LOWER: u64 = 32;
+const AGE_PENALTY: i16 = 8;
+const PV_BONUS: i16 = 4;
+const HASH_FULL_SAMPLE: usize = 1000;
+const NO_EVAL: i16 = i16::MIN;
+const FLAG_MASK: u8 = 0b011;
+const PV_NODE: u8 = 0b100;
+
+/** Definitions used for saving and loading the TT */
+const FILE_MAGIC: &[u8; 4] = b"RTT1";
+const FILE_VERSION: u32 = 1;
+const FILE_HEADER_BYTES: u64 = 4 + 4 + 8 + 4 + 4 + 8 + 8 + 1;
+const FILE_ENTRY_BYTES: u64 = 4 + 1 + 1 + 1 + 2 + 2 + 4;
+
+/* ===== Data ========================================================= */
+
+pub trait IHashData: Send {
+    fn new() -> Self;
+    fn depth(&self) -> i8;
+    fn is_exact(&self) -> bool;
+    fn is_pv(&self) -> bool;
+}
+#[derive(Copy, Clone)]
+pub struct PerftData {
+    depth: i8,
+    leaf_nodes: u64,
+}
+
+impl IHashData for PerftData {
+    fn new() -> Self {
+        Self {
+            depth: 0,
+            leaf_nodes: 0,
+        }
+    }
+
+    fn depth(&self) -> i8 {
+        self.depth
+    }
+
+    fn is_exact(&self) -> bool {
+        false
+    }
+
+    fn is_pv(&self) -> bool {
+        false
+    }
+}
+
+impl PerftData {
+    pub fn create(depth: i8, leaf_nodes: u64) -> Self {
+        Self { depth, leaf_nodes }
+    }
+
+    pub fn get(&self, depth: i8) -> Option<u64> {
+        if self.depth == depth {
+            Some(self.leaf_nodes)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub enum HashFlag {
+    Nothing,
+    Exact,
+    Alpha,
+    Beta,
+}
+
+impl HashFlag {
+    fn from_bits(bits: u8) -> Self {
+        match bits & FLAG_MASK {
+            1 => HashFlag::Exact,
+            2 => HashFlag::Alpha,
+            3 => HashFlag::Beta,
+            _ => HashFlag::Nothing,
+        }
+    }
+}
+
+// The hash flag and the PV-node flag share one byte, and the data is aligned
+// on 2 bytes instead of the 4 the best move would need. This way, an entry
+// takes 16 bytes, and a bucket of four entries fits a 64-byte cache line.
+#[derive(Copy, Clone)]
+#[repr(C, packed(2))]
+pub struct SearchData {
+    depth: i8,
+    flags: u8,
+    value: i16,
+    eval: i16,
+    best_move: ShortMove,
+}
+
+impl IHashData for SearchData {
+    fn new() -> Self {
+        Self {
+            depth: 0,
+            flags: HashFlag::Nothing as u8,
+            value: 0,
+            eval: NO_EVAL,
+            best_move: ShortMove::new(0),
+        }
+    }
+
+    fn depth(&self) -> i8 {
+        self.depth
+    }
+
+    fn is_exact(&self) -> bool {
+        matches!(HashFlag::from_bits(self.flags), HashFlag::Exact)
+    }
+
+    fn is_pv(&self) -> bool {
+        self.flags & PV_NODE > 0
+    }
+}
+
+impl SearchData {
+    // Next to the search result, the data holds the static evaluation of
+    // the position, if there was one, and if it was stored by a PV-node.
+    pub fn create(
+        depth: i8,
+        ply: i8,
+        flag: HashFlag,
+        value: i16,
+        eval: Option<i16>,
+        is_pv: bool,
+        best_move: ShortMove,
+    ) -> Self {
+        // This is the value we're going to save into the TT.
+        let mut v = value;
+
+        // If we're dealing with checkmate, the value must be adjusted, so
+        // they take the number of plies at which they were found into
+        // account, before storing the value into the TT. These ifs can be
+        // rewritten as a comparative match expression. We don't, because
+        // they're slower. (No inlining by the compiler.)
+        if v > CHECKMATE_THRESHOLD {
+            v += ply as i16;
+        }
+
+        if v < CHECKMATE_THRESHOLD {
+            v -= ply as i16;
+        }
+
+        let pv_node = if is_pv { PV_NODE } else { 0 };
+
+        Self {
+            depth,
+            flags: flag as u8 | pv_node,
+            value: v,
+            eval: eval.unwrap_or(NO_EVAL),
+            best_move,
+        }
+    }
+
+    // The static evaluation stored with the position. There is none if
+    // the side to move was in check.
+    pub fn eval(&self) -> Option<i16> {
+        match self.eval {
+            NO_EVAL => None,
+            eval => Some(eval),
+        }
+    }
+
+    pub fn get(&self, depth: i8, ply: i8, alpha: i16, beta: i16) -> (Option<i16>, ShortMove) {
+        // We either do, or don't have a value to return from the TT.
+        let mut value: Option<i16> = None;
+
+        if self.depth >= depth {
+            match HashFlag::from_bits(self.flags) {
+                HashFlag::Exact => {
+                    // Get the value from the data. We don't want to change
+                    // the value that is in the TT.
+                    let mut v = self.value;
+
+                    // Adjust for the number of plies from where this data
+                    // is probed, if we're dealing with checkmate. Same as
+                    // above: no comparative match expression.
+                    if v > CHECKMATE_THRESHOLD {
+                        v -= ply as i16;
+                    }
+
+                    if v < CHECKMATE_THRESHOLD {
+                        v += ply as i16;
+                    }
+
+                    // This is the value that will be returned.
+                    value = Some(v);
+                }
+                HashFlag::Alpha if self.value <= alpha => value = Some(alpha),
+                HashFlag::Beta if self.value >= beta => value = Some(beta),
+                _ => (),
+            };
+        }
+        (value, self.best_move)
+    }
+}
+
+/* ===== Entry ======================================================== */
+
+#[derive(Copy, Clone)]
+struct Entry<D> {
+    verification: u32,
+    generation: u8,
+    data: D,
+}
+
+impl<D: IHashData> Entry<D> {
+    pub fn new() -> Self {
+        Self {
+            verification: 0,
+            generation: 0,
+            data: D::new(),
+        }
+    }
+
+    // If the verification is 0, this entry was never used.
+    pub fn is_used(&self) -> bool {
+        self.verification != 0
+    }
+
+    // How valuable it is to keep this entry. Deeper entries are worth
+    // more, but an entry loses value with each search it gets older.
+    // Entries stored by PV-nodes of the current search hold the principal
+    // variation, so they are kept over other entries of about the same
+    // depth.
+    pub fn worth(&self, generation: u8) -> i16 {
+        let age = generation.wrapping_sub(self.generation) as i16;
+        let pv = if age == 0 && self.data.is_pv() {
+            PV_BONUS
+        } else {
+            0
+        };
+
+        self.data.depth() as i16 - AGE_PENALTY * age + pv
+    }
+}
+
+/* ===== Bucket ======================================================= */
+
+#[derive(Clone)]
+struct Bucket<D> {
+    bucket: [Entry<D>; ENTRIES_PER_BUCKET],
+}
+
+// A search bucket must fit exactly into one cache line, so a probe reads
+// (and a prefetch loads) only one line from memory.
+const _: () = assert!(std::mem::size_of::<Bucket<SearchData>>() == 64);
+
+impl<D: IHashData + Copy> Bucket<D> {
+    pub fn new() -> Self {
+        Self {
+            bucket: [Entry::new(); ENTRIES_PER_BUCKET],
+        }
+    }
+
+    // Store a position in the bucket. If the position is already in the
+    // bucket, it is updated. Otherwise, an unused entry is taken, or the
+    // entry that is the least valuable to keep is replaced.
+    pub fn store(&mut self, verification: u32, data: D, generation: u8) {
+        let same_position = self
+            .bucket
+            .iter()
+            .position(|e| e.verification == verification);
+
+        let idx = match same_position {
+            Some(i) => {
+                // Don't overwrite an exact entry of this search with a
+                // shallower bound for the same position.
+                let e = &self.bucket[i];
+                if e.generation == generation
+                    && e.data.is_exact()
+                    && !data.is_exact()
+                    && e.data.depth() > data.depth()
+                {
+                    return;
+                }
+                i
+            }
+            None => {
+                let mut idx_lowest_worth = 0;
+                let mut lowest_worth = i16::MAX;
+
+                for (i, e) in self.bucket.iter().enumerate() {
+                    let worth = if e.is_used() {
+                        e.worth(generation)
+                    } else {
+                        i16::MIN
+                    };
+
+                    if worth < lowest_worth {
+                        idx_lowest_worth = i;
+                        lowest_worth = worth;
+                    }
+                }
+                idx_lowest_worth
+            }
+        };
+
+        // Store.
+        self.bucket[idx] = Entry {
+            verification,
+            generation,
+            data,
+        }
+    }
+
+    // Find a position in the bucket, where both the stored verification and
+    // depth match the requested verification and depth.
+    pub fn find(&self, verification: u32) -> Option<&D> {
+        for e in self.bucket.iter() {
+            if e.verification == verification {
+                return Some(&e.data);
+            }
+        }
+        None
+    }
+}
+
+/* ===== TT =================================================== */
+
+// Transposition Table
+pub struct TT<D> {
+    tt: HugeTable<Bucket<D>>,
+    megabytes: usize,
+    generation: u8,
+    total_buckets: usize,
+    total_entries: usize,
+}
+
+// Public functions
+impl<D: IHashData + Copy + Clone> TT<D> {
+    // Create a new TT of the requested size, able to hold the data
+    // of type D, where D has to implement IHashData, and must be clonable
+    // and copyable.
+    pub fn new(megabytes: usize) -> Self {
+        let (total_buckets, total_entries) = Self::calculate_init_values(megabytes);
+
+        Self {
+            tt: HugeTable::new(total_buckets, Bucket::<D>::new),
+            megabytes,
+            generation: 0,
+            total_buckets,
+            total_entries,
+        }
+    }
+
+    // Resizes the TT by replacing the current TT with a new one. The
+    // current TT is freed first, so the old and new one don't have to fit
+    // in memory at the same time.
+    pub fn resize(&mut self, megabytes: usize) {
+        let (total_buckets, total_entries) = TT::<D>::calculate_init_values(megabytes);
+
+        self.tt = HugeTable::new(0, Bucket::<D>::new);
+        self.tt = HugeTable::new(total_buckets, Bucket::<D>::new);
+        self.megabytes = megabytes;
+        self.generation = 0;
+        self.total_buckets = total_buckets;
+        self.total_entries = total_entries;
+    }
+
+    // Insert a position at the calculated index, by storing it in the
+    // index's bucket.
+    pub fn insert(&mut self, zobrist_key: ZobristKey, data: D) {
+        if self.megabytes > 0 {
+            let index = self.calculate_index(zobrist_key);
+            let verification = self.calculate_verification(zobrist_key);
+            self.tt[index].store(verification, data, self.generation);
+        }
+    }
+
+    // Create a prefetcher for this TT. The search takes one when it starts,
+    // so it can prefetch buckets without locking the TT at every node.
+    pub fn prefetcher(&self) -> TTPrefetcher {
+        TTPrefetcher {
+            base: self.tt.as_ptr() as usize,
+            bucket_size: std::mem::size_of::<Bucket<D>>(),
+            total_buckets: self.total_buckets,
+        }
+    }
+
+    // Probe the TT by both verification and depth. Both have to
+    // match for the position to be the correct one we're looking for.
+    pub fn probe(&self, zobrist_key: ZobristKey) -> Option<&D> {
+        if self.megabytes > 0 {
+            let index = self.calculate_index(zobrist_key);
+            let verification = self.calculate_verification(zobrist_key);
+
+            self.tt[index].find(verification)
+        } else {
+            None
+        }
+    }
+
+    // Size of the TT in megabytes.
+    pub fn megabytes(&self) -> usize {
+        self.megabytes
+    }
+
+    // Clear TT by replacing it with a new one.
+    pub fn clear(&mut self) {
+        self.resize(self.megabytes);
+    }
+
+    // Start a new search. Entries stored by earlier searches are now
+    // older, so they will be replaced sooner. The generation wraps around
+    // after 256 searches; by then, those entries are long gone.
+    pub fn new_search(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+    }
+
+    // Provides TT usage in permille (1 per 1000, as oppposed to percent,
+    // which is 1 per 100.) Only entries stored in the current search are
+    // counted. Walking the entire TT would take too long, so the first
+    // entries are taken as a sample.
+    pub fn hash_full(&self) -> u16 {
+        if self.megabytes > 0 && self.total_entries > 0 {
+            let sample = HASH_FULL_SAMPLE.min(self.total_entries);
+            let used = self
+                .tt
+                .iter()
+                .flat_map(|b| b.bucket.iter())
+                .take(sample)
+                .filter(|e| e.is_used() && e.generation == self.generation)
+                .count();
+
+            (used * 1000 / sample) as u16
+        } else {
+            0
+        }
+    }
+}
+
+// Saving and loading. Only the search TT is worth keeping between runs of
+// the engine, for example during a long analysis session.
+impl TT<SearchData> {
+    // Write the TT to a file. The header holds everything needed to check
+    // if the file can be loaded back: the format version, the signature of
+    // the Zobrist random numbers that created the keys, the layout of the
+    // entries, and the size of the TT.
+    pub fn save(&self, path: &str, zobrist_signature: u64) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+
+        file.write_all(FILE_MAGIC)?;
+        file.write_all(&FILE_VERSION.to_le_bytes())?;
+        file.write_all(&zobrist_signature.to_le_bytes())?;
+        file.write_all(&(std::mem::size_of::<Entry<SearchData>>() as u32).to_le_bytes())?;
+        file.write_all(&(ENTRIES_PER_BUCKET as u32).to_le_bytes())?;
+        file.write_all(&(self.megabytes as u64).to_le_bytes())?;
+        file.write_all(&(self.total_buckets as u64).to_le_bytes())?;
+        file.write_all(&[self.generation])?;
+
+        for entry in self.tt.iter().flat_map(|b| b.bucket.iter()) {
+            let data = entry.data;
+            let best_move = data.best_move;
+            file.write_all(&entry.verification.to_le_bytes())?;
+            file.write_all(&[entry.generation, data.depth as u8, data.flags])?;
+            file.write_all(&{ data.value }.to_le_bytes())?;
+            file.write_all(&{ data.eval }.to_le_bytes())?;
+            file.write_all(&best_move.get_move().to_le_bytes())?;
+        }
+
+        file.flush()
+    }
+
+    // Replace the TT with the one in the given file. The TT gets the size
+    // it had when it was saved. If the file can't be used, the TT is not
+    // changed.
+    pub fn load(&mut self, path: &str, zobrist_signature: u64) -> io::Result<()> {
+        let mut file = BufReader::new(File::open(path)?);
+        let file_size = file.get_ref().metadata()?.len();
+        let invalid = |reason: &str| {
+            let msg = format!("{path}: {reason}");
+            io::Error::new(io::ErrorKind::InvalidData, msg)
+        };
+
+        if file_size < FILE_HEADER_BYTES {
+            return Err(invalid("not a hash file for this engine"));
+        }
+
+        let magic: [u8; 4] = read_bytes(&mut file)?;
+        let version = u32::from_le_bytes(read_bytes(&mut file)?);
+        if &magic != FILE_MAGIC || version != FILE_VERSION {
+            return Err(invalid("not a hash file for this engine"));
+        }
+
+        let signature = u64::from_le_bytes(read_bytes(&mut file)?);
+        let entry_size = u32::from_le_bytes(read_bytes(&mut file)?) as usize;
+        let entries_per_bucket = u32::from_le_bytes(read_bytes(&mut file)?) as usize;
+        if signature != zobrist_signature
+            || entry_size != std::mem::size_of::<Entry<SearchData>>()
+            || entries_per_bucket != ENTRIES_PER_BUCKET
+        {
+            return Err(invalid("hash file was saved by a different engine build"));
+        }
+
+        let megabytes = u64::from_le_bytes(read_bytes(&mut file)?) as usize;
+        let total_buckets = u64::from_le_bytes(read_bytes(&mut file)?) as usize;
+        let [generation] = read_bytes(&mut file)?;
+        let (expected_buckets, total_entries) = Self::calculate_init_values(megabytes);
+        let expected_size = FILE_HEADER_BYTES + total_entries as u64 * FILE_ENTRY_BYTES;
+        if total_buckets != expected_buckets || file_size != expected_size {
+            return Err(invalid("hash file is damaged"));
+        }
+
+        let mut tt = HugeTable::new(total_buckets, Bucket::<SearchData>::new);
+        for entry in tt.iter_mut().flat_map(|b| b.bucket.iter_mut()) {
+            let verification = u32::from_le_bytes(read_bytes(&mut file)?);
+            let [generation, depth, flags] = read_bytes(&mut file)?;
+            let value = i16::from_le_bytes(read_bytes(&mut file)?);
+            let eval = i16::from_le_bytes(read_bytes(&mut file)?);
+            let best_move = ShortMove::new(u32::from_le_bytes(read_bytes(&mut file)?));
+
+            *entry = Entry {
+                verification,
+                generation,
+                data: SearchData {
+                    depth: depth as i8,
+                    flags,
+                    value,
+                    eval,
+                    best_move,
+                },
+            };
+        }
+
+        self.tt = tt;
+        self.megabytes = megabytes;
+        self.generation = generation;
+        self.total_buckets = total_buckets;
+        self.total_entries = total_entries;
+
+        Ok(())
+    }
+}
+
+// Calculate the bucket for a Zobrist key, in a table with the given
+// number of buckets.
+fn bucket_index(zobrist_key: ZobristKey, total_buckets: usize) -> usize {
+    let key = (zobrist_key & HIGH_FOUR_BYTES) >> SHIFT_TO_LOWER;
+    let total = total_buckets as u64;
+
+    (key % total) as usize
+}
+
+// Read the next N bytes from the file.
+fn read_bytes<const N: usize>(file: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    file.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+// Private functions
+impl<D: IHashData + Copy + Clone> TT<D> {
+    // Calculate the index (bucket) where the data is going to be stored.
+    // Use only the upper half of the Zobrist key for this, so the lower
+    // half can be used to calculate a verification.
+    fn calculate_index(&self, zobrist_key: ZobristKey) -> usize {
+        bucket_index(zobrist_key, self.total_buckets)
+    }
+
+    // Many positions will end up at the same index, and thus in the same
+    // bucket. Calculate a verification for the position so it can later be
+    // found in the bucket. Use the other half of the Zobrist key for this.
+    fn calculate_verification(&self, zobrist_key: ZobristKey) -> u32 {
+        (zobrist_key & LOW_FOUR_BYTES) as u32
+    }
+
+    // This function calculates the values for total_buckets and
+    // total_entries. These depend on the requested TT size.
+    fn calculate_init_values(megabytes: usize) -> (usize, usize) {
+        let entry_size = std::mem::size_of::<Entry<D>>();
+        let bucket_size = entry_size * ENTRIES_PER_BUCKET;
+        let total_buckets = MEGABYTE / bucket_size * megabytes;
+        let total_entries = total_buckets * ENTRIES_PER_BUCKET;
+
+        (total_buckets, total_entries)
+    }
+}
+
+/* ===== Prefetcher =================================================== */
+
+// The prefetcher holds the address and size of the TT, so the search can
+// start loading a bucket into the CPU cache right after making a move,
+// without locking the TT. By the time the TT is probed for the new
+// position, the bucket is (almost) there. Prefetching is only a hint to
+// the CPU, so even if the TT is resized while a prefetcher still exists,
+// nothing goes wrong: the prefetched memory is just not used.
+#[derive(Clone, Copy, PartialEq)]
+pub struct TTPrefetcher {
+    base: usize,
+    bucket_size: usize,
+    total_buckets: usize,
+}
+
+impl TTPrefetcher {
+    // A prefetcher that does nothing, for when the TT is not used.
+    pub fn none() -> Self {
+        Self {
+            base: 0,
+            bucket_size: 0,
+            total_buckets: 0,
+        }
+    }
+
+    pub fn prefetch(&self, zobrist_key: ZobristKey) {
+        if self.total_buckets > 0 {
+            let index = bucket_index(zobrist_key, self.total_buckets);
+            huge_table::prefetch(self.base + index * self.bucket_size);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIGNATURE: u64 = 0x0123_4567_89AB_CDEF;
+
+    fn temp_file(name: &str) -> String {
+        let file = format!("rustic-tt-{}-{}.hash", std::process::id(), name);
+        std::env::temp_dir()
+            .join(file)
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    fn filled_tt() -> TT<SearchData> {
+        let mut tt = TT::<SearchData>::new(1);
+        tt.new_search();
+        for key in 1..=100u64 {
+            let key = key.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+            let data = SearchData::create(
+                (key % 20) as i8,
+                0,
+                HashFlag::Exact,
+                (key % 500) as i16,
+                Some((key % 300) as i16),
+                key % 2 == 0,
+                ShortMove::new((key & 0xFFFF) as u32),
+            );
+            tt.insert(key, data);
+        }
+        tt
+    }
+
+    fn same_data(a: &SearchData, b: &SearchData) -> bool {
+        let (a_value, a_move) = a.get(0, 0, -1000, 1000);
+        let (b_value, b_move) = b.get(0, 0, -1000, 1000);
+        a.depth() == b.depth()
+            && a.is_pv() == b.is_pv()
+            && a.eval() == b.eval()
+            && a_value == b_value
+            && a_move == b_move
+    }
+
+    #[test]
+    fn saved_tt_loads_back_unchanged() {
+        let path = temp_file("round-trip");
+        let saved = filled_tt();
+        saved.save(&path, SIGNATURE).expect("save");
+
+        let mut loaded = TT::<SearchData>::new(2);
+        let result = loaded.load(&path, SIGNATURE);
+        std::fs::remove_file(&path).ok();
+
+        assert!(result.is_ok(), "{result:?}");
+        assert_eq!(loaded.megabytes(), saved.megabytes());
+        assert_eq!(loaded.generation, saved.generation);
+        assert_eq!(loaded.hash_full(), saved.hash_full());
+        let mut found = 0;
+        for key in 1..=100u64 {
+            let key = key.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+            match (saved.probe(key), loaded.probe(key)) {
+                (Some(a), Some(b)) => {
+                    assert!(same_data(a, b), "key {key:#x}");
+                    found += 1;
+                }
+                (None, None) => (),
+                _ => panic!("key {key:#x} found in only one of the tables"),
+            }
+        }
+        assert!(found > 90, "only {found} of 100 entries found");
+    }
+
+    #[test]
+    fn file_from_another_build_is_rejected() {
+        let path = temp_file("signature");
+        filled_tt().save(&path, SIGNATURE).expect("save");
+
+        let mut tt = TT::<SearchData>::new(2);
+        let result = tt.load(&path, SIGNATURE ^ 1);
+        std::fs::remove_file(&path).ok();
+
+        let error = result.expect_err("loaded with the wrong signature");
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("different engine build"));
+        assert_eq!(tt.megabytes(), 2);
+    }
+
+    #[test]
+    fn damaged_files_are_rejected() {
+        let path = temp_file("damaged");
+        filled_tt().save(&path, SIGNATURE).expect("save");
+        let bytes = std::fs::read(&path).expect("read");
+
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] = b'X';
+        let mut too_long = bytes.clone();
+        too_long.push(0);
+        let truncated = bytes[..bytes.len() - 1].to_vec();
+        let no_header = bytes[..10].to_vec();
+
+        let cases = [
+            (bad_magic, "not a hash file"),
+            (no_header, "not a hash file"),
+            (too_long, "damaged"),
+            (truncated, "damaged"),
+        ];
+
+        let mut tt = TT::<SearchData>::new(2);
+        for (contents, reason) in cases {
+            std::fs::write(&path, contents).expect("write");
+            let error = tt.load(&path, SIGNATURE).expect_err("loaded a bad file");
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+            assert!(error.to_string().contains(reason), "{error}");
+            assert_eq!(tt.megabytes(), 2);
+        }
+        std::fs::remove_file(&path).ok();
+
+        let error = tt
+            .load(&path, SIGNATURE)
+            .expect_err("loaded a missing file");
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+    }
+}
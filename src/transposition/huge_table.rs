@@ -0,0 +1,165 @@
+/* =======================================================================
+Rustic is a chess playing engine.
+Copyright (C) 2019-2024, Marcel Vanthoor
+https://rustic-chess.org/
+
+Rustic is written in the Rust programming language. It is an original
+work, not derived from any engine that came before it. However, it does
+use a lot of concepts which are well-known and are in use by most if not
+all classical alpha/beta-based chess engines.
+
+Rustic is free software: you can redistribute it and/or modify it under
+the terms of the GNU General Public License version 3 as published by
+the Free Software Foundation.
+
+Rustic is distributed in the hope that it will be useful, but WITHOUT
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+for more details.
+
+You should have received a copy of the GNU General Public License along
+with this program.  If not, see <http://www.gnu.org/licenses/>.
+======================================================================= */
+
+// huge_table.rs provides the memory for the transposition table. A TT can
+// be many gigabytes in size, so it is allocated differently from a normal
+// vector:
+//
+// - The memory is aligned on 2 MB. On Linux, the kernel is asked to back
+//   it with huge pages. Each TT probe hits a random bucket, so with normal
+//   4 KB pages, almost every probe misses the TLB.
+// - The elements are initialized by multiple threads, each one working on
+//   its own part of the table. Initializing is what actually makes the
+//   operating system provide the memory, which takes a while for large
+//   tables.
+
+use crate::defs::ErrFatal;
+use std::{
+    alloc::{self, Layout},
+    mem::MaybeUninit,
+    ops::{Deref, DerefMut},
+    ptr::NonNull,
+    slice, thread,
+};
+
+const HUGE_PAGE: usize = 2 * 1024 * 1024;
+
+pub struct HugeTable<T> {
+    ptr: NonNull<T>,
+    len: usize,
+    layout: Option<Layout>,
+}
+
+// The table owns its elements, just like a vector does.
+unsafe impl<T: Send> Send for HugeTable<T> {}
+unsafe impl<T: Sync> Sync for HugeTable<T> {}
+
+impl<T: Send> HugeTable<T> {
+    // Create a table of "len" elements, each one created by "init".
+    pub fn new(len: usize, init: fn() -> T) -> Self {
+        let size = len * std::mem::size_of::<T>();
+        if size == 0 {
+            return Self {
+                ptr: NonNull::dangling(),
+                len,
+                layout: None,
+            };
+        }
+
+        // Round the size up to full huge pages, so the last part of the
+        // table can also be backed by a huge page.
+        let size = size.div_ceil(HUGE_PAGE) * HUGE_PAGE;
+        let align = HUGE_PAGE.max(std::mem::align_of::<T>());
+        let layout = Layout::from_size_align(size, align).expect(ErrFatal::TT_SIZE);
+
+        // Safety: the layout has a non-zero size.
+        let raw = unsafe { alloc::alloc(layout) };
+        let ptr = match NonNull::new(raw as *mut T) {
+            Some(p) => p,
+            None => alloc::handle_alloc_error(layout),
+        };
+
+        advise_huge_pages(raw, size);
+
+        // Safety: the memory is allocated for "len" elements, which are not
+        // initialized yet. MaybeUninit<T> has the same layout as T.
+        let elements =
+            unsafe { slice::from_raw_parts_mut(ptr.as_ptr() as *mut MaybeUninit<T>, len) };
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        let chunk = len.div_ceil(threads);
+
+        thread::scope(|s| {
+            for part in elements.chunks_mut(chunk) {
+                s.spawn(move || {
+                    part.iter_mut().for_each(|e| {
+                        e.write(init());
+                    })
+                });
+            }
+        });
+
+        Self {
+            ptr,
+            len,
+            layout: Some(layout),
+        }
+    }
+}
+
+impl<T> Deref for HugeTable<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        // Safety: all "len" elements were initialized in new().
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<T> DerefMut for HugeTable<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        // Safety: all "len" elements were initialized in new().
+        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<T> Drop for HugeTable<T> {
+    fn drop(&mut self) {
+        if let Some(layout) = self.layout {
+            // Safety: the elements were initialized in new(), and the memory
+            // was allocated there with the same layout.
+            unsafe {
+                std::ptr::drop_in_place(self.deref_mut() as *mut [T]);
+                alloc::dealloc(self.ptr.as_ptr() as *mut u8, layout);
+            }
+        }
+    }
+}
+
+// Tell the CPU to start loading the memory at this address into its cache,
+// so it is already there when it's needed a bit later. This is only a
+// hint: the memory is not read, and an address that is not (or no longer)
+// valid does not cause a fault. On CPUs other than x86-64, it does nothing.
+#[inline(always)]
+pub fn prefetch(address: usize) {
+    #[cfg(target_arch = "x86_64")]
+    {
+        use std::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+
+        // Safety: prefetching does not access the memory.
+        unsafe { _mm_prefetch(address as *const i8, _MM_HINT_T0) };
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    let _ = address;
+}
+
+// Ask the kernel to back the memory with huge pages. If transparent huge
+// pages are disabled, this fails, and normal pages are used.
+#[cfg(target_os = "linux")]
+fn advise_huge_pages(ptr: *mut u8, size: usize) {
+    // Safety: the memory range was just allocated by us.
+    unsafe { libc::madvise(ptr as *mut libc::c_void, size, libc::MADV_HUGEPAGE) };
+}
+
+#[cfg(not(target_os = "linux"))]
+fn advise_huge_pages(_ptr: *mut u8, _size: usize) {}
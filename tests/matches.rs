@@ -0,0 +1,160 @@
+/* =======================================================================
+Rustic is a chess playing engine.
+Copyright (C) 2019-2024, Marcel Vanthoor
+https://rustic-chess.org/
+
+Rustic is written in the Rust programming language. It is an original
+work, not derived from any engine that came before it. However, it does
+use a lot of concepts which are well-known and are in use by most if not
+all classical alpha/beta-based chess engines.
+
+Rustic is free software: you can redistribute it and/or modify it under
+the terms of the GNU General Public License version 3 as published by
+the Free Software Foundation.
+
+Rustic is distributed in the hope that it will be useful, but WITHOUT
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+for more details.
+
+You should have received a copy of the GNU General Public License along
+with this program.  If not, see <http://www.gnu.org/licenses/>.
+======================================================================= */
+
+// These tests run the engine binary itself. In the gauntlet, a second
+// copy of the same binary acts as the external opponent, so the UCI client
+// is tested against a real engine without needing anything else installed.
+
+use rustic_alpha::evaluation::nnue::{INPUTS, L1, L2, L3};
+use std::{
+    fs,
+    path::PathBuf,
+    process::{Command, Output},
+};
+
+const ENGINE: &str = env!("CARGO_BIN_EXE_rustic-alpha");
+
+// A fast time control with a large increment keeps the games short, while
+// leaving even a debug build enough time for every move.
+const TIME_CONTROL: &str = "1+0.1";
+
+// Each test writes to its own files, so tests can run in parallel.
+fn temp_file(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("rustic-{}-{}", std::process::id(), name))
+}
+
+// Write a network with all weights set to zero. It evaluates every
+// position as equal, but it is enough to have a player use NNUE.
+fn zero_network(name: &str) -> PathBuf {
+    let size = (INPUTS * L1 + L1) * 2 + L2 * 2 * L1 + L2 * 4 + L3 * L2 + L3 * 4 + L3 + 4;
+    let mut data = b"RNN1".to_vec();
+    data.resize(data.len() + size, 0);
+
+    let path = temp_file(name);
+    fs::write(&path, data).expect("network could not be written");
+    path
+}
+
+fn run(args: &[&str]) -> Output {
+    Command::new(ENGINE)
+        .args(args)
+        .output()
+        .expect("engine could not be started")
+}
+
+#[test]
+fn gauntlet_against_rustic() {
+    let pgn = temp_file("gauntlet.pgn");
+    let output = run(&[
+        "gauntlet",
+        "--engine",
+        ENGINE,
+        "--games",
+        "2",
+        "--tc",
+        TIME_CONTROL,
+        "--pgnout",
+        pgn.to_str().unwrap(),
+    ]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let games = fs::read_to_string(&pgn).unwrap_or_default();
+    let _ = fs::remove_file(&pgn);
+
+    assert!(output.status.success(), "{stdout}");
+    assert!(stdout.contains("Gauntlet results"), "{stdout}");
+
+    // Both games are played to the end, from both sides, and none of them
+    // is lost because the client and the opponent went out of sync.
+    assert_eq!(games.matches("[Result ").count(), 2, "{games}");
+    assert_eq!(games.matches("[Result \"*\"]").count(), 0, "{games}");
+    assert!(!games.contains("illegal move"), "{games}");
+    assert!(!games.contains("time forfeit"), "{games}");
+}
+
+#[test]
+fn gauntlet_engine_not_found() {
+    let pgn = temp_file("not-found.pgn");
+    let output = run(&[
+        "gauntlet",
+        "--engine",
+        "./no-such-engine",
+        "--pgnout",
+        pgn.to_str().unwrap(),
+    ]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let _ = fs::remove_file(&pgn);
+
+    assert!(!output.status.success());
+    assert!(stdout.contains("Gauntlet: "), "{stdout}");
+}
+
+#[test]
+fn selfplay_nnue_against_psqt() {
+    let pgn = temp_file("selfplay.pgn");
+    let network = zero_network("zero.nnue");
+    let nnue = format!("name=NNUE,hash=8,evalfile={}", network.display());
+    let output = run(&[
+        "selfplay",
+        "--engine1",
+        &nnue,
+        "--engine2",
+        "name=PSQT,hash=8",
+        "--games",
+        "2",
+        "--tc",
+        TIME_CONTROL,
+        "--pgnout",
+        pgn.to_str().unwrap(),
+    ]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let games = fs::read_to_string(&pgn).unwrap_or_default();
+    let _ = fs::remove_file(&pgn);
+    let _ = fs::remove_file(&network);
+
+    assert!(output.status.success(), "{stdout}");
+    assert!(stdout.contains("Score of NNUE vs PSQT"), "{stdout}");
+
+    // Each player had white once.
+    assert_eq!(games.matches("[White \"NNUE\"]").count(), 1, "{games}");
+    assert_eq!(games.matches("[White \"PSQT\"]").count(), 1, "{games}");
+    assert!(!games.contains("illegal move"), "{games}");
+}
+
+#[test]
+fn selfplay_refuses_bad_configurations() {
+    for config in [
+        "name=X,Clear Hash=1",
+        "foo=1",
+        "hash=big",
+        "evalfile=./no-such.nnue",
+    ] {
+        let output = run(&["selfplay", "--engine1", config, "--games", "2"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        assert!(!output.status.success(), "{config}");
+        assert!(
+            stdout.contains("Engine configuration incorrect"),
+            "{stdout}"
+        );
+    }
+}